@@ -0,0 +1,46 @@
+use bevy_dutch_road_highway_node_network::{nwb::NWBNetworkData, read_file, versioned_path};
+use criterion::{criterion_group, criterion_main, Criterion};
+use graph::{DirectedNetworkGraph, NodeId};
+use highway::generation::ComputedState;
+
+/// Effect of the node order on the neighbourhood calculation.
+fn bench(b: &mut Criterion) {
+    let network: DirectedNetworkGraph<NWBNetworkData> =
+        read_file(versioned_path("data/directed_graph.graph")).unwrap();
+    let nodes = network.nodes().len() as u64;
+
+    // Multiplying by a large odd number modulo a power of two is a permutation,
+    // which scatters nodes that are close to each other.
+    let mask = nodes.next_power_of_two() - 1;
+    let scrambled = (0..=mask)
+        .map(|x| x.wrapping_mul(0x9E37_79B9_7F4A_7C15) & mask)
+        .filter(|x| *x < nodes)
+        .map(|x| NodeId(x as u32))
+        .collect::<Vec<_>>();
+
+    let orders = [
+        ("scrambled", scrambled),
+        ("bfs", network.bfs_order()),
+        (
+            "hilbert",
+            network.hilbert_order(|node| {
+                let location = network.node_data(node).1;
+                [location.x, location.y]
+            }),
+        ),
+    ];
+
+    let mut group = b.benchmark_group("sample: 10");
+    group.sample_size(10);
+
+    for (name, order) in orders {
+        let (reordered, _) = network.reorder(&order);
+        group.bench_function(format!("computed_state::{}", name), |b| {
+            b.iter(|| ComputedState::new(30, &reordered));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//! Conversions between the bevy-free types of this crate and their bevy counterparts.
+//! Bevy uses `f32`, so converting a full precision `Aabb` loses precision for large coordinates.
+use crate::Aabb;
+use bevy::render::primitives;
+
+impl From<Aabb> for primitives::Aabb {
+    fn from(aabb: Aabb) -> Self {
+        primitives::Aabb::from_min_max(aabb.min().as_vec3(), aabb.max().as_vec3())
+    }
+}
+
+impl From<primitives::Aabb> for Aabb {
+    fn from(aabb: primitives::Aabb) -> Self {
+        Aabb::from_min_max(aabb.min().as_dvec3(), aabb.max().as_dvec3())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::DVec3;
+
+    #[test]
+    fn aabb_round_trip() {
+        let aabb = Aabb::from_min_max(DVec3::new(1.0, 2.0, 0.0), DVec3::new(3.0, 6.0, 0.0));
+        let bevy_aabb = primitives::Aabb::from(aabb);
+
+        assert_eq!(bevy_aabb.min().as_dvec3(), aabb.min());
+        assert_eq!(bevy_aabb.max().as_dvec3(), aabb.max());
+        assert_eq!(Aabb::from(bevy_aabb), aabb);
+    }
+}
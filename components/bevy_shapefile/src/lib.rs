@@ -1,13 +1,18 @@
+pub use road_data::*;
+pub use repair::*;
 pub use road_map::*;
 use serde::{Deserialize, Serialize};
 pub use spatial::*;
 use std::path::Path;
 
-// mod road_data;
+#[cfg(feature = "bevy")]
+mod bevy_support;
+mod road_data;
+mod repair;
 mod road_map;
 mod spatial;
 
-pub type AABB = rstar::AABB<[f32; 2]>;
+pub type AABB = rstar::AABB<[f64; 2]>;
 
 pub fn from_shapefile<P: AsRef<Path>>(path: P) -> Result<RoadMap, ShapeError> {
     println!("Start loading file");
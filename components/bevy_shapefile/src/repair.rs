@@ -0,0 +1,692 @@
+use crate::{JunctionId, RoadId, RoadMap, RoadSection, AABB};
+use glam::DVec2;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
+
+/// Tolerances of [`RoadMap::repair_junctions`], in metres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JunctionRepairConfig {
+    /// Junctions closer than this to each other are merged into one junction.
+    pub merge_distance: f64,
+    /// Dead ends closer than this to another road are connected to that road.
+    pub dangling_distance: f64,
+}
+
+impl Default for JunctionRepairConfig {
+    fn default() -> Self {
+        Self {
+            merge_distance: 0.5,
+            dangling_distance: 1.0,
+        }
+    }
+}
+
+/// The changes made by [`RoadMap::repair_junctions`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JunctionRepairReport {
+    /// Junctions that were replaced by another junction, as (removed, kept).
+    pub merged: Vec<(JunctionId, JunctionId)>,
+    /// Roads that were split to connect a dead end, with the ids of the pieces.
+    /// The first piece keeps the id of the original road.
+    pub split: Vec<(RoadId, Vec<RoadId>)>,
+}
+
+impl Display for JunctionRepairReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Merged {} junctions, split {} roads",
+            self.merged.len(),
+            self.split.len()
+        )?;
+
+        for (removed, kept) in &self.merged {
+            writeln!(f, "junction {} -> {}", removed.num(), kept.num())?;
+        }
+        for (road, pieces) in &self.split {
+            let pieces = pieces
+                .iter()
+                .map(|x| x.num().to_string())
+                .collect::<Vec<_>>();
+            writeln!(f, "road {} -> {}", road.num(), pieces.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RoadMap {
+    /// Repair the topology of the road map before a graph is built from it.
+    ///
+    /// The junction ids of the NWB contain digitisation errors: two ids at the same spot,
+    /// or a road that ends just next to another road. Both split the network into islands.
+    /// Junctions within `merge_distance` of each other are merged, and dead ends within
+    /// `dangling_distance` of another road are connected to it, splitting that road when needed.
+    /// Roads on a different relative height (REL_HOOGTE) are never connected,
+    /// so a bridge is not glued to the road underneath.
+    pub fn repair_junctions(&mut self, config: &JunctionRepairConfig) -> JunctionRepairReport {
+        let mut junctions = JunctionUnion::default();
+
+        self.merge_coincident(config.merge_distance, &mut junctions);
+        let (splits, connections) = self.find_dangling(config, &mut junctions);
+
+        let mut next_road = self.roads.keys().map(|x| x.num() + 1).max().unwrap_or(0);
+        let mut roads = std::mem::take(&mut self.roads);
+        let mut report = JunctionRepairReport::default();
+
+        for (road_id, positions) in splits {
+            let road_id = RoadId::from(road_id);
+            let section = roads.remove(&road_id).unwrap();
+            let pieces = split_parts(&section.parts, &positions);
+            let last = pieces.len() - 1;
+            let mut ids = Vec::new();
+
+            for (i, parts) in pieces.into_iter().enumerate() {
+                let id = if i == 0 {
+                    road_id
+                } else {
+                    next_road += 1;
+                    RoadId::from(next_road - 1)
+                };
+
+                let mut attributes = section.attributes.clone();
+                if i > 0 {
+                    attributes.junction_start = positions[i - 1].junction;
+                }
+                if i < last {
+                    attributes.junction_end = positions[i].junction;
+                }
+
+                let section = RoadSection::new(id, parts, attributes).expect("Split has points");
+                roads.insert(id, section);
+                ids.push(id);
+            }
+
+            report.split.push((road_id, ids));
+        }
+
+        for section in roads.values_mut() {
+            let start = section.attributes.junction_start;
+            let end = section.attributes.junction_end;
+
+            if connections.contains_key(&start) || connections.contains_key(&end) {
+                // The pieces of a split road already end at the connection point.
+                let mut parts = std::mem::take(&mut section.parts);
+                if let Some(point) = connections.get(&start) {
+                    let first = parts.first_mut().unwrap();
+                    if first.first() != Some(point) {
+                        first.insert(0, *point);
+                    }
+                }
+                if let Some(point) = connections.get(&end) {
+                    let last = parts.last_mut().unwrap();
+                    if last.last() != Some(point) {
+                        last.push(*point);
+                    }
+                }
+                *section = RoadSection::new(section.id, parts, section.attributes.clone())
+                    .expect("Road has points");
+            }
+
+            section.attributes.junction_start = junctions.find(start);
+            section.attributes.junction_end = junctions.find(end);
+        }
+
+        report.merged = junctions.merged();
+        *self = RoadMap::from_sections(roads);
+
+        report
+    }
+
+    /// Merge junctions that lie within `distance` of each other and share a height level.
+    fn merge_coincident(&self, distance: f64, junctions: &mut JunctionUnion) {
+        let heights = self.junction_heights();
+        let connected = self
+            .roads
+            .values()
+            .flat_map(|section| {
+                let start = section.attributes.junction_start;
+                let end = section.attributes.junction_end;
+                [(start, end), (end, start)]
+            })
+            .collect::<HashSet<_>>();
+
+        let mut indices = self.junction_spatial.iter().collect::<Vec<_>>();
+        indices.sort_by_key(|x| x.junction_id.num());
+
+        for junction in indices {
+            let location = [junction.location.x, junction.location.y];
+
+            for other in self
+                .junction_spatial
+                .locate_within_distance(location, distance * distance)
+            {
+                let (a, b) = (junction.junction_id, other.junction_id);
+
+                // Merging both ends of a very short road would turn it into a loop.
+                if a == b || connected.contains(&(a, b)) {
+                    continue;
+                }
+                if heights[&a].iter().any(|x| heights[&b].contains(x)) {
+                    junctions.union(a, b);
+                }
+            }
+        }
+    }
+
+    /// Find the dead ends that are close to another road on the same height.
+    /// A dead end close to the start or end of that road is merged with that junction,
+    /// otherwise the road is split at the closest point, which becomes the dead end junction.
+    fn find_dangling(
+        &self,
+        config: &JunctionRepairConfig,
+        junctions: &mut JunctionUnion,
+    ) -> (
+        BTreeMap<usize, Vec<SectionPosition>>,
+        HashMap<JunctionId, DVec2>,
+    ) {
+        let mut degree = HashMap::<JunctionId, usize>::new();
+        for section in self.roads.values() {
+            *degree
+                .entry(junctions.find(section.attributes.junction_start))
+                .or_default() += 1;
+            *degree
+                .entry(junctions.find(section.attributes.junction_end))
+                .or_default() += 1;
+        }
+
+        let mut ends = self
+            .roads
+            .values()
+            .flat_map(|section| {
+                [
+                    (section, section.attributes.junction_start, section.start()),
+                    (section, section.attributes.junction_end, section.end()),
+                ]
+            })
+            .filter(|(_, junction, _)| degree[&junctions.find(*junction)] == 1)
+            .collect::<Vec<_>>();
+        ends.sort_by_key(|(_, junction, _)| junction.num());
+
+        let mut splits = BTreeMap::<usize, Vec<SectionPosition>>::new();
+        let mut connections = HashMap::new();
+
+        for (section, junction, location) in ends {
+            let Some((other, position)) = self.closest_road(section, location, config) else {
+                continue;
+            };
+
+            if position.point.distance(other.start()) <= config.merge_distance {
+                junctions.union(junction, other.attributes.junction_start);
+            } else if position.point.distance(other.end()) <= config.merge_distance {
+                junctions.union(junction, other.attributes.junction_end);
+            } else {
+                connections.insert(junction, position.point);
+                splits
+                    .entry(other.id.num())
+                    .or_default()
+                    .push(SectionPosition {
+                        junction,
+                        ..position
+                    });
+            }
+        }
+
+        for positions in splits.values_mut() {
+            positions.sort_by(|a, b| {
+                (a.part, a.segment)
+                    .cmp(&(b.part, b.segment))
+                    .then(a.t.total_cmp(&b.t))
+            });
+
+            // Dead ends that hit the road at the same point share a single split.
+            let mut kept: Vec<SectionPosition> = Vec::with_capacity(positions.len());
+            for position in positions.drain(..) {
+                match kept.last() {
+                    Some(last) if last.point.distance(position.point) <= config.merge_distance => {
+                        junctions.union(last.junction, position.junction);
+                        connections.insert(position.junction, last.point);
+                    }
+                    _ => kept.push(position),
+                }
+            }
+            *positions = kept;
+        }
+
+        for positions in splits.values_mut() {
+            for position in positions {
+                position.junction = junctions.find(position.junction);
+            }
+        }
+
+        (splits, connections)
+    }
+
+    /// The closest road to `location` other than `section` on the same height,
+    /// within the dangling distance.
+    fn closest_road(
+        &self,
+        section: &RoadSection,
+        location: DVec2,
+        config: &JunctionRepairConfig,
+    ) -> Option<(&RoadSection, SectionPosition)> {
+        let distance = config.dangling_distance;
+        let envelope = AABB::from_corners(
+            [location.x - distance, location.y - distance],
+            [location.x + distance, location.y + distance],
+        );
+
+        self.road_spatial
+            .locate_in_envelope_intersecting(&envelope)
+            .map(|index| &self.roads[&index.id])
+            .filter(|other| {
+                other.id != section.id
+                    && other.attributes.relative_height == section.attributes.relative_height
+            })
+            .filter_map(|other| Some((other, closest_position(other, location)?)))
+            .filter(|(_, position)| position.point.distance(location) <= distance)
+            .min_by(|(a, x), (b, y)| {
+                let x = x.point.distance_squared(location);
+                let y = y.point.distance_squared(location);
+                x.total_cmp(&y).then(a.id.num().cmp(&b.id.num()))
+            })
+    }
+
+    /// The relative heights of the roads that start or end at every junction.
+    fn junction_heights(&self) -> HashMap<JunctionId, Vec<i8>> {
+        let mut heights = HashMap::<JunctionId, Vec<i8>>::new();
+
+        for section in self.roads.values() {
+            let height = section.attributes.relative_height;
+            for junction in [
+                section.attributes.junction_start,
+                section.attributes.junction_end,
+            ] {
+                let entry = heights.entry(junction).or_default();
+                if !entry.contains(&height) {
+                    entry.push(height);
+                }
+            }
+        }
+
+        heights
+    }
+}
+
+/// Union-find of junction ids, the lowest id of a group is kept.
+#[derive(Debug, Default)]
+struct JunctionUnion {
+    parent: HashMap<JunctionId, JunctionId>,
+}
+
+impl JunctionUnion {
+    fn find(&mut self, junction: JunctionId) -> JunctionId {
+        let mut root = junction;
+        while let Some(parent) = self.parent.get(&root) {
+            root = *parent;
+        }
+
+        let mut current = junction;
+        while current != root {
+            current = self.parent.insert(current, root).unwrap();
+        }
+
+        root
+    }
+
+    fn union(&mut self, a: JunctionId, b: JunctionId) {
+        let a = self.find(a);
+        let b = self.find(b);
+
+        match a.num().cmp(&b.num()) {
+            std::cmp::Ordering::Less => self.parent.insert(b, a),
+            std::cmp::Ordering::Greater => self.parent.insert(a, b),
+            std::cmp::Ordering::Equal => None,
+        };
+    }
+
+    fn merged(&mut self) -> Vec<(JunctionId, JunctionId)> {
+        let mut junctions = self.parent.keys().cloned().collect::<Vec<_>>();
+        junctions.sort_by_key(|x| x.num());
+
+        junctions
+            .into_iter()
+            .map(|junction| (junction, self.find(junction)))
+            .collect()
+    }
+}
+
+/// A point on a road section: the segment of a part and the fraction along that segment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SectionPosition {
+    part: usize,
+    segment: usize,
+    t: f64,
+    point: DVec2,
+    junction: JunctionId,
+}
+
+fn closest_position(section: &RoadSection, location: DVec2) -> Option<SectionPosition> {
+    section
+        .parts
+        .iter()
+        .enumerate()
+        .flat_map(|(part, points)| {
+            points.windows(2).enumerate().map(move |(segment, w)| {
+                let ab = w[1] - w[0];
+                let t = if ab.length_squared() == 0.0 {
+                    0.0
+                } else {
+                    ((location - w[0]).dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+                };
+
+                SectionPosition {
+                    part,
+                    segment,
+                    t,
+                    point: w[0] + ab * t,
+                    junction: section.attributes.junction_start,
+                }
+            })
+        })
+        .min_by(|a, b| {
+            let a = a.point.distance_squared(location);
+            let b = b.point.distance_squared(location);
+            a.total_cmp(&b)
+        })
+}
+
+/// Split the parts of a section at the sorted `positions`, one piece more than there are positions.
+fn split_parts(parts: &[Vec<DVec2>], positions: &[SectionPosition]) -> Vec<Vec<Vec<DVec2>>> {
+    fn push_point(line: &mut Vec<DVec2>, point: DVec2) {
+        if line.last() != Some(&point) {
+            line.push(point);
+        }
+    }
+
+    let mut pieces = Vec::with_capacity(positions.len() + 1);
+    let mut current = Vec::new();
+    let mut positions = positions.iter().peekable();
+
+    for (part, points) in parts.iter().enumerate() {
+        let mut line = vec![points[0]];
+
+        for (segment, point) in points.iter().enumerate().skip(1) {
+            while let Some(position) =
+                positions.next_if(|x| x.part == part && x.segment == segment - 1)
+            {
+                push_point(&mut line, position.point);
+                current.push(std::mem::replace(&mut line, vec![position.point]));
+                pieces.push(std::mem::take(&mut current));
+            }
+            push_point(&mut line, *point);
+        }
+
+        current.push(line);
+    }
+    pieces.push(current);
+
+    pieces
+        .into_iter()
+        .map(|piece| piece.into_iter().filter(|line| line.len() >= 2).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DrivingDirection, RoadAttributes};
+
+    fn section(id: usize, points: Vec<DVec2>, start: usize, end: usize, height: i8) -> RoadSection {
+        let attributes = RoadAttributes {
+            wegvak_id: id,
+            junction_start: JunctionId::from(start),
+            junction_end: JunctionId::from(end),
+            road_type: None,
+            road_number: None,
+            street_name: None,
+            alternative_name: None,
+            municipality: None,
+            residence: None,
+            authority: None,
+            authority_type: None,
+            route_numbers: vec![],
+            frc: None,
+            fow: None,
+            direction: DrivingDirection::Both,
+            relative_height: height,
+            house_numbers_left: None,
+            house_numbers_right: None,
+            speed_limit: None,
+        };
+
+        RoadSection::new(RoadId::from(id), vec![points], attributes).unwrap()
+    }
+
+    fn road_map(sections: Vec<RoadSection>) -> RoadMap {
+        RoadMap::from_sections(sections.into_iter().map(|x| (x.id, x)).collect())
+    }
+
+    fn junctions(road_map: &RoadMap, id: usize) -> (usize, usize) {
+        let attributes = &road_map.roads[&RoadId::from(id)].attributes;
+        (
+            attributes.junction_start.num(),
+            attributes.junction_end.num(),
+        )
+    }
+
+    #[test]
+    fn merges_coincident_junctions() {
+        let mut road_map = road_map(vec![
+            section(
+                0,
+                vec![DVec2::new(0.0, 0.0), DVec2::new(100.0, 0.0)],
+                0,
+                1,
+                0,
+            ),
+            section(
+                1,
+                vec![DVec2::new(100.2, 0.0), DVec2::new(200.0, 0.0)],
+                2,
+                3,
+                0,
+            ),
+        ]);
+
+        let report = road_map.repair_junctions(&JunctionRepairConfig::default());
+
+        assert_eq!(
+            report.merged,
+            vec![(JunctionId::from(2), JunctionId::from(1))]
+        );
+        assert!(report.split.is_empty());
+        assert_eq!(junctions(&road_map, 1), (1, 3));
+        assert_eq!(road_map.junction_spatial.size(), 3);
+    }
+
+    #[test]
+    fn keeps_bridges_apart() {
+        let mut road_map = road_map(vec![
+            section(
+                0,
+                vec![DVec2::new(0.0, 0.0), DVec2::new(100.0, 0.0)],
+                0,
+                1,
+                0,
+            ),
+            section(
+                1,
+                vec![DVec2::new(100.2, 0.0), DVec2::new(200.0, 0.0)],
+                2,
+                3,
+                1,
+            ),
+            section(
+                2,
+                vec![DVec2::new(50.0, 50.0), DVec2::new(50.0, 0.4)],
+                4,
+                5,
+                1,
+            ),
+        ]);
+
+        let report = road_map.repair_junctions(&JunctionRepairConfig::default());
+
+        assert_eq!(report, JunctionRepairReport::default());
+        assert_eq!(junctions(&road_map, 1), (2, 3));
+        assert_eq!(junctions(&road_map, 2), (4, 5));
+    }
+
+    #[test]
+    fn keeps_short_roads() {
+        let mut road_map = road_map(vec![section(
+            0,
+            vec![DVec2::new(0.0, 0.0), DVec2::new(0.3, 0.0)],
+            0,
+            1,
+            0,
+        )]);
+
+        let report = road_map.repair_junctions(&JunctionRepairConfig::default());
+
+        assert_eq!(report, JunctionRepairReport::default());
+        assert_eq!(junctions(&road_map, 0), (0, 1));
+    }
+
+    #[test]
+    fn connects_dangling_end_to_road() {
+        let mut road_map = road_map(vec![
+            section(
+                0,
+                vec![DVec2::new(0.0, 0.0), DVec2::new(200.0, 0.0)],
+                0,
+                1,
+                0,
+            ),
+            section(
+                1,
+                vec![DVec2::new(100.0, 100.0), DVec2::new(100.0, 0.6)],
+                2,
+                3,
+                0,
+            ),
+        ]);
+
+        let report = road_map.repair_junctions(&JunctionRepairConfig::default());
+
+        assert!(report.merged.is_empty());
+        assert_eq!(
+            report.split,
+            vec![(RoadId::from(0), vec![RoadId::from(0), RoadId::from(2)])]
+        );
+
+        assert_eq!(junctions(&road_map, 0), (0, 3));
+        assert_eq!(junctions(&road_map, 2), (3, 1));
+        assert_eq!(junctions(&road_map, 1), (2, 3));
+
+        let split = DVec2::new(100.0, 0.0);
+        assert_eq!(road_map.roads[&RoadId::from(0)].end(), split);
+        assert_eq!(road_map.roads[&RoadId::from(2)].start(), split);
+        assert_eq!(road_map.roads[&RoadId::from(1)].end(), split);
+        assert_eq!(road_map.roads[&RoadId::from(2)].length(), 100.0);
+        // The pieces of the split road end at the connection point, it is not added again.
+        assert_eq!(
+            road_map.roads[&RoadId::from(0)].parts,
+            vec![vec![DVec2::new(0.0, 0.0), split]]
+        );
+        assert_eq!(
+            road_map.roads[&RoadId::from(2)].parts,
+            vec![vec![split, DVec2::new(200.0, 0.0)]]
+        );
+        for section in road_map.roads.values() {
+            assert!(section.segments().all(|(a, b)| a != b));
+        }
+
+        let nearest = road_map.junction_spatial.nearest_neighbor(&[100.0, 0.0]);
+        assert_eq!(nearest.unwrap().junction_id, JunctionId::from(3));
+    }
+
+    #[test]
+    fn merges_dangling_end_near_junction() {
+        let mut road_map = road_map(vec![
+            section(
+                0,
+                vec![DVec2::new(0.0, 0.0), DVec2::new(100.0, 0.0)],
+                0,
+                1,
+                0,
+            ),
+            section(
+                1,
+                vec![DVec2::new(100.0, 100.0), DVec2::new(100.0, 0.8)],
+                2,
+                3,
+                0,
+            ),
+        ]);
+
+        let report = road_map.repair_junctions(&JunctionRepairConfig::default());
+
+        assert_eq!(
+            report.merged,
+            vec![(JunctionId::from(3), JunctionId::from(1))]
+        );
+        assert!(report.split.is_empty());
+        assert_eq!(junctions(&road_map, 1), (2, 1));
+    }
+
+    #[test]
+    fn splits_road_once_for_multiple_dead_ends() {
+        let mut road_map = road_map(vec![
+            section(
+                0,
+                vec![DVec2::new(0.0, 0.0), DVec2::new(300.0, 0.0)],
+                0,
+                1,
+                0,
+            ),
+            section(
+                1,
+                vec![DVec2::new(200.0, 100.0), DVec2::new(200.0, 0.7)],
+                2,
+                3,
+                0,
+            ),
+            section(
+                2,
+                vec![DVec2::new(100.0, 100.0), DVec2::new(100.0, 0.7)],
+                4,
+                5,
+                0,
+            ),
+            section(
+                3,
+                vec![DVec2::new(100.1, -100.0), DVec2::new(100.1, -0.7)],
+                6,
+                7,
+                0,
+            ),
+        ]);
+
+        let report = road_map.repair_junctions(&JunctionRepairConfig::default());
+
+        assert_eq!(
+            report.merged,
+            vec![(JunctionId::from(7), JunctionId::from(5))]
+        );
+        assert_eq!(
+            report.split,
+            vec![(
+                RoadId::from(0),
+                vec![RoadId::from(0), RoadId::from(4), RoadId::from(5)]
+            )]
+        );
+        assert_eq!(junctions(&road_map, 0), (0, 5));
+        assert_eq!(junctions(&road_map, 4), (5, 3));
+        assert_eq!(junctions(&road_map, 5), (3, 1));
+        assert_eq!(junctions(&road_map, 3), (6, 5));
+    }
+}
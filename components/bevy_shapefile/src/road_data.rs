@@ -1,232 +1,168 @@
-// RPE_CODE
-// WEGTYPE
+use crate::JunctionId;
+use serde::{Deserialize, Serialize};
+use shapefile::dbase::{FieldValue, Record};
+use std::fmt::Display;
 
+/// Driving direction relative to the digitisation direction of the road (RIJRICHTNG).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DrivingDirection {
+    /// H: only in the direction of digitisation.
+    Forward,
+    /// T: only against the direction of digitisation.
+    Backward,
+    /// B: both directions.
+    Both,
+    /// O: unknown, treated as both directions.
+    Unknown,
+}
 
-// map: {
+impl DrivingDirection {
+    fn from_code(code: Option<&str>) -> Self {
+        match code.and_then(|x| x.chars().next()) {
+            Some('H') => DrivingDirection::Forward,
+            Some('T') => DrivingDirection::Backward,
+            Some('B') => DrivingDirection::Both,
+            _ => DrivingDirection::Unknown,
+        }
+    }
+}
 
+/// House numbers on one side of a road section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HouseNumberRange {
+    pub first: usize,
+    pub last: usize,
+}
 
-//     "L_HNR_LNKS": Numeric(
-//         Some(
-//             19.0,
-//         ),
-//     ),
-//     "DISTRCODE": Numeric(
-//         Some(
-//             0.0,
-//         ),
-//     ),
-//     "HECTO_LTTR": Character(
-//         Some(
-//             "#",
-//         ),
-//     ),
-//     "DIENSTCODE": Character(
-//         None,
-//     ),
-//     "POS_TV_WOL": Character(
-//         None,
-//     ),
-//     "ROUTENR4": Numeric(
-//         None,
-//     ),
-//     "WEGBEHSRT": Character(
-//         Some(
-//             "G",
-//         ),
-//     ),
-//     "ROUTELTR4": Character(
-//         None,
-//     ),
-//     "WEGNR_AW": Character(
-//         None,
-//     ),
-//     "ALT_NR": Character(
-//         None,
-//     ),
-//     "REL_HOOGTE": Numeric(
-//         None,
-//     ),
-//     "STT_BRON": Character(
-//         Some(
-//             "BAG schrijfwijze",
-//         ),
-//     ),
-//     "GME_NAAM": Character(
-//         Some(
-//             "Rotterdam",
-//         ),
-//     ),
-//     "WEGBEHCODE": Character(
-//         Some(
-//             "599",
-//         ),
-//     ),
-//     "HNRSTRLNKS": Character(
-//         Some(
-//             "O",
-//         ),
-//     ),
-//     "JTE_ID_BEG": Numeric(
-//         Some(
-//             182274152.0,
-//         ),
-//     ),
-//     "ROUTENR2": Numeric(
-//         None,
-//     ),
-//     "WVK_ID": Numeric(
-//         Some(
-//             182274140.0,
-//         ),
-//     ),
-//     "BAG_ORL": Character(
-//         Some(
-//             "0599300000001007",
-//         ),
-//     ),
-//     "WEGNR_HMP": Character(
-//         None,
-//     ),
-//     "BEGINKM": Numeric(
-//         None,
-//     ),
-//     "WPSNAAM": Character(
-//         Some(
-//             "Rotterdam",
-//         ),
-//     ),
-//     "ENDAFSTAND": Numeric(
-//         None,
-//     ),
-//     "DIENSTNAAM": Character(
-//         None,
-//     ),
-//     "ADMRICHTNG": Character(
-//         None,
-//     ),
-//     "ROUTELTR": Character(
-//         None,
-//     ),
-//     "ROUTENR": Numeric(
-//         None,
-//     ),
-//     "WEGTYPE": Character(
-//         None,
-//     ),
-//     "GME_ID": Numeric(
-//         Some(
-//             599.0,
-//         ),
-//     ),
-//     "WEGBEHNAAM": Character(
-//         Some(
-//             "Rotterdam",
-//         ),
-//     ),
-//     "GEOBRON_ID": Numeric(
-//         Some(
-//             10.0,
-//         ),
-//     ),
-//     "GEOBRON_NM": Character(
-//         Some(
-//             "Hartlijnenbestand Topografische Dienst",
-//         ),
-//     ),
-//     "BEGAFSTAND": Numeric(
-//         None,
-//     ),
-//     "ALT_NAAM": Character(
-//         None,
-//     ),
-//     "E_HNR_RHTS": Numeric(
-//         None,
-//     ),
-//     "WEGDEELLTR": Character(
-//         Some(
-//             "#",
-//         ),
-//     ),
-//     "WEGNUMMER": Character(
-//         None,
-//     ),
-//     "EINDKM": Numeric(
-//         None,
-//     ),
-//     "JTE_ID_END": Numeric(
-//         Some(
-//             182274200.0,
-//         ),
-//     ),
-//     "WVK_BEGDAT": Date(
-//         Some(
-//             Date {
-//                 year: 1997,
-//                 month: 2,
-//                 day: 14,
-//             },
-//         ),
-//     ),
-//     "WGTYPE_OMS": Character(
-//         None,
-//     ),
-//     "ROUTENR3": Numeric(
-//         None,
-//     ),
-//     "FOW": Character(
-//         Some(
-//             "3",
-//         ),
-//     ),
-//     "RIJRICHTNG": Character(
-//         Some(
-//             "B",
-//         ),
-//     ),
-//     "L_HNR_RHTS": Numeric(
-//         None,
-//     ),
-//     "ROUTELTR3": Character(
-//         None,
-//     ),
-//     "BST_CODE": Character(
-//         None,
-//     ),
-//     "BRONJAAR": Numeric(
-//         Some(
-//             1993.0,
-//         ),
-//     ),
-//     "RPE_CODE": Character(
-//         Some(
-//             "#",
-//         ),
-//     ),
-//     "OPENLR": Character(
-//         Some(
-//             "CwMr7STsGjPGAgC0AB8zFQ==",
-//         ),
-//     ),
-//     "STT_NAAM": Character(
-//         Some(
-//             "Beukelsdijk",
-//         ),
-//     ),
-//     "E_HNR_LNKS": Numeric(
-//         Some(
-//             63.0,
-//         ),
-//     ),
-//     "DISTRNAAM": Character(
-//         None,
-//     ),
-//     "FRC": Character(
-//         Some(
-//             "6",
-//         ),
-//     ),
-//     "ROUTELTR2": Character(
-//         None,
-//     ),
-// },
-// }
+impl HouseNumberRange {
+    fn new(first: Option<usize>, last: Option<usize>) -> Option<Self> {
+        match (first, last) {
+            (Some(first), Some(last)) => Some(Self { first, last }),
+            (Some(number), None) | (None, Some(number)) => Some(Self {
+                first: number,
+                last: number,
+            }),
+            (None, None) => None,
+        }
+    }
+}
+
+impl Display for HouseNumberRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}", self.first)
+        } else {
+            write!(f, "{}-{}", self.first, self.last)
+        }
+    }
+}
+
+/// The DBF attributes of a road section (wegvak).
+/// https://docs.ndw.nu/handleidingen/nwb/nwb-basisstructuur/overige-attributen/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoadAttributes {
+    /// WVK_ID
+    pub wegvak_id: usize,
+    /// JTE_ID_BEG
+    pub junction_start: JunctionId,
+    /// JTE_ID_END
+    pub junction_end: JunctionId,
+    /// BST_CODE, see the README for the possible values.
+    pub road_type: Option<String>,
+    /// WEGNUMMER
+    pub road_number: Option<String>,
+    /// STT_NAAM
+    pub street_name: Option<String>,
+    /// ALT_NAAM
+    pub alternative_name: Option<String>,
+    /// GME_NAAM
+    pub municipality: Option<String>,
+    /// WPSNAAM
+    pub residence: Option<String>,
+    /// WEGBEHNAAM
+    pub authority: Option<String>,
+    /// WEGBEHSRT: R(ijk), P(rovincie), G(emeente), W(aterschap) or T (other).
+    pub authority_type: Option<String>,
+    /// ROUTELTR + ROUTENR up to ROUTELTR4 + ROUTENR4, for example `A12`.
+    pub route_numbers: Vec<String>,
+    /// Functional road class (FRC)
+    pub frc: Option<u8>,
+    /// Form of way (FOW)
+    pub fow: Option<u8>,
+    /// RIJRICHTNG
+    pub direction: DrivingDirection,
+    /// REL_HOOGTE, relative height level of the road, 0 when not given.
+    pub relative_height: i8,
+    /// E_HNR_LNKS - L_HNR_LNKS
+    pub house_numbers_left: Option<HouseNumberRange>,
+    /// E_HNR_RHTS - L_HNR_RHTS
+    pub house_numbers_right: Option<HouseNumberRange>,
+    /// MAXSHD, not part of the road section file, see [`crate::RoadMap::load_speed_limits`].
+    pub speed_limit: Option<f32>,
+}
+
+impl RoadAttributes {
+    pub fn from_record(record: &Record) -> Self {
+        let route_numbers = [
+            ("ROUTELTR", "ROUTENR"),
+            ("ROUTELTR2", "ROUTENR2"),
+            ("ROUTELTR3", "ROUTENR3"),
+            ("ROUTELTR4", "ROUTENR4"),
+        ]
+        .into_iter()
+        .filter_map(|(letter, number)| {
+            let number = get_usize(record, number)?;
+            let letter = get_text(record, letter).unwrap_or_default();
+
+            Some(format!("{}{}", letter, number))
+        })
+        .collect();
+
+        RoadAttributes {
+            wegvak_id: get_usize(record, "WVK_ID").unwrap(),
+            junction_start: get_usize(record, "JTE_ID_BEG").unwrap().into(),
+            junction_end: get_usize(record, "JTE_ID_END").unwrap().into(),
+            road_type: get_text(record, "BST_CODE"),
+            road_number: get_text(record, "WEGNUMMER"),
+            street_name: get_text(record, "STT_NAAM"),
+            alternative_name: get_text(record, "ALT_NAAM"),
+            municipality: get_text(record, "GME_NAAM"),
+            residence: get_text(record, "WPSNAAM"),
+            authority: get_text(record, "WEGBEHNAAM"),
+            authority_type: get_text(record, "WEGBEHSRT"),
+            route_numbers,
+            frc: get_text(record, "FRC").and_then(|x| x.parse().ok()),
+            fow: get_text(record, "FOW").and_then(|x| x.parse().ok()),
+            direction: DrivingDirection::from_code(get_text(record, "RIJRICHTNG").as_deref()),
+            relative_height: get_float(record, "REL_HOOGTE").unwrap_or_default() as i8,
+            house_numbers_left: HouseNumberRange::new(
+                get_usize(record, "E_HNR_LNKS"),
+                get_usize(record, "L_HNR_LNKS"),
+            ),
+            house_numbers_right: HouseNumberRange::new(
+                get_usize(record, "E_HNR_RHTS"),
+                get_usize(record, "L_HNR_RHTS"),
+            ),
+            speed_limit: None,
+        }
+    }
+}
+
+/// Missing fields are treated as empty, so older or trimmed releases still load.
+pub(crate) fn get_text(record: &Record, name: &str) -> Option<String> {
+    match record.get(name)? {
+        FieldValue::Character(x) => x.clone().filter(|x| !x.is_empty()),
+        value => unreachable!("Could not get text: {:?}", value),
+    }
+}
+
+pub(crate) fn get_usize(record: &Record, name: &str) -> Option<usize> {
+    get_float(record, name).map(|x| x as usize)
+}
+
+pub(crate) fn get_float(record: &Record, name: &str) -> Option<f64> {
+    match record.get(name)? {
+        FieldValue::Numeric(x) => *x,
+        value => unreachable!("Could not get float: {:?}", value),
+    }
+}
@@ -1,26 +1,20 @@
 use crate::{
+    road_data::{get_text, get_usize, RoadAttributes},
     spatial::{JunctionSpatialIndex, RoadSection, RoadSpatialIndex},
     JunctionId, RoadId, ShapeError,
 };
-use bevy::{
-    math::{Vec2, Vec3},
-    prelude::Resource,
-    render::primitives::Aabb,
-};
+use glam::DVec2;
 use rayon::prelude::*;
 use rstar::{RStarInsertionStrategy, RTree, RTreeParams};
 use serde::{Deserialize, Serialize};
-use shapefile::{
-    dbase::{FieldValue, Record},
-    record::polyline::GenericPolyline,
-    Point, Polyline,
-};
+use shapefile::{dbase::Record, record::polyline::GenericPolyline, Point, Polyline};
 use std::{collections::HashMap, fs::File, path::Path};
 
 /// Load shapefile.
 /// This shapefile is used vor visualization of road data.
 /// It loads all the road sections, and puts it in spatial data structures.
-#[derive(Serialize, Deserialize, Debug, Resource)]
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy::prelude::Resource))]
 pub struct RoadMap {
     pub roads: HashMap<RoadId, RoadSection>,
     pub junction_spatial: rstar::RTree<JunctionSpatialIndex, Params>,
@@ -28,14 +22,32 @@ pub struct RoadMap {
 }
 
 impl RoadMap {
-    pub fn road_length(&self, road_id: RoadId) -> f32 {
-        let section = &self.roads[&road_id];
-
-        section
-            .points
-            .windows(2)
-            .map(|points| points[0].distance(points[1]))
-            .sum()
+    pub fn road_length(&self, road_id: RoadId) -> f64 {
+        self.roads[&road_id].length()
+    }
+
+    /// Find the road section closest to `point`, together with its distance.
+    /// The spatial index only stores bounding boxes, so candidates are refined
+    /// against the actual polyline until no closer bounding box remains.
+    pub fn nearest_road(&self, point: DVec2) -> Option<(RoadId, f64)> {
+        let mut nearest: Option<(RoadId, f64)> = None;
+
+        for (index, envelope_distance) in self
+            .road_spatial
+            .nearest_neighbor_iter_with_distance_2(&[point.x, point.y])
+        {
+            if matches!(nearest, Some((_, distance)) if envelope_distance > distance) {
+                break;
+            }
+
+            let distance = self.roads[&index.id].distance_squared(point);
+
+            if nearest.map_or(true, |(_, nearest)| distance < nearest) {
+                nearest = Some((index.id, distance));
+            }
+        }
+
+        nearest.map(|(id, distance)| (id, distance.sqrt()))
     }
 }
 
@@ -66,13 +78,19 @@ impl RoadMap {
         let roads =
             shapefile::read_as::<P, Polyline, Record>(path).map_err(|x| ShapeError::Shape(x))?;
 
-        println!("Loading junction data");
-        let junctions = load_junctions(&roads);
-
         println!("Loading roads");
         let roads = load_road_sections(roads);
         println!("Finish loading roads");
 
+        Ok(Self::from_sections(roads))
+    }
+
+    /// Create a road map from already loaded road sections.
+    /// The junctions are derived from the start and end points of the sections.
+    pub fn from_sections(roads: HashMap<RoadId, RoadSection>) -> Self {
+        println!("Loading junction data");
+        let junctions = load_junctions(&roads);
+
         println!("Creating spatial data");
         let spatial_indeces = roads
             .iter()
@@ -99,43 +117,47 @@ impl RoadMap {
 
         println!("Created tree");
 
-        Ok(RoadMap {
+        RoadMap {
             roads,
             road_spatial,
             junction_spatial,
-        })
+        }
     }
-}
 
-fn get_usize(record: &Record, name: &str) -> Option<usize> {
-    let value = record.get(name).unwrap();
+    /// Add the speed limits (MAXSHD) from the separate speed limit shapefile to the road attributes.
+    pub fn load_speed_limits<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ShapeError> {
+        println!("Start read of speed limit data");
+        let records = shapefile::read(path).map_err(|x| ShapeError::Shape(x))?;
+
+        let speed_limits = records
+            .into_par_iter()
+            .filter_map(|(_, record)| {
+                let wegvak_id = get_usize(&record, "WVK_ID")?;
+                let speed_limit = get_text(&record, "MAXSHD")?.parse::<f32>().ok()?;
+
+                Some((wegvak_id, speed_limit))
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.roads.par_iter_mut().for_each(|(_, section)| {
+            section.attributes.speed_limit =
+                speed_limits.get(&section.attributes.wegvak_id).cloned();
+        });
+
+        println!("Finish loading speed limits");
 
-    if let FieldValue::Numeric(x) = value {
-        return x.map(|x| x as usize);
+        Ok(())
     }
-    unreachable!();
 }
 
 /// Load junction point data
-fn load_junctions(roads: &Vec<(GenericPolyline<Point>, Record)>) -> HashMap<JunctionId, Vec2> {
+fn load_junctions(roads: &HashMap<RoadId, RoadSection>) -> HashMap<JunctionId, DVec2> {
     roads
         .par_iter()
-        .flat_map_iter(|(line, record)| {
-            let junction_start = get_usize(record, "JTE_ID_BEG").unwrap();
-            let junction_end = get_usize(record, "JTE_ID_END").unwrap();
-
-            let start = line.part(0).and_then(|p| p.first()).unwrap();
-            let end = line.part(0).and_then(|p| p.last()).unwrap();
-
+        .flat_map_iter(|(_, section)| {
             [
-                (
-                    JunctionId::from(junction_start),
-                    Vec2::new(start.x as f32, start.y as f32),
-                ),
-                (
-                    JunctionId::from(junction_end),
-                    Vec2::new(end.x as f32, end.y as f32),
-                ),
+                (section.attributes.junction_start, section.start()),
+                (section.attributes.junction_end, section.end()),
             ]
         })
         .collect::<HashMap<_, _>>()
@@ -145,28 +167,30 @@ fn load_junctions(roads: &Vec<(GenericPolyline<Point>, Record)>) -> HashMap<Junc
 fn load_road_sections(
     roads: Vec<(GenericPolyline<Point>, Record)>,
 ) -> HashMap<RoadId, RoadSection> {
+    let count = roads.len();
     let roads = roads
         .into_par_iter()
         .enumerate()
-        .map(|(id, (line, _))| {
-            assert!(line.parts().len() == 1);
-
-            let points = line
-                .part(0)
-                .expect("Could not get ?")
+        .filter_map(|(id, (line, record))| {
+            let parts = line
+                .parts()
                 .iter()
-                .map(|point| Vec2::new(point.x as f32, point.y as f32))
+                .map(|part| {
+                    part.iter()
+                        .map(|point| DVec2::new(point.x, point.y))
+                        .collect::<Vec<_>>()
+                })
                 .collect::<Vec<_>>();
 
-            let bbox = line.bbox();
-            let aabb = Aabb::from_min_max(
-                Vec3::new(bbox.x_range()[0] as f32, bbox.y_range()[0] as f32, 0.0),
-                Vec3::new(bbox.x_range()[1] as f32, bbox.y_range()[1] as f32, 0.0),
-            );
             let id = RoadId::from(id);
+            let attributes = RoadAttributes::from_record(&record);
 
-            (id, RoadSection { id, points, aabb })
+            Some((id, RoadSection::new(id, parts, attributes)?))
         })
         .collect::<HashMap<_, _>>();
+
+    if roads.len() < count {
+        println!("Skipped {} records without points", count - roads.len());
+    }
     roads
 }
@@ -1,32 +1,150 @@
-use bevy::{
-    math::{Vec2, Vec3},
-    render::primitives::Aabb,
-};
+use glam::{DVec2, DVec3};
 use rstar::{PointDistance, RTreeObject, AABB};
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{Deserialize, Serialize};
 
-use crate::{JunctionId, RoadId};
+use crate::{JunctionId, RoadAttributes, RoadId};
 
-/// A road section, with id, points and bounding box
+/// Axis aligned bounding box of a road section, in full precision RD coordinates.
+/// Serialized as `(min, max)`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(from = "(DVec3, DVec3)", into = "(DVec3, DVec3)")]
+pub struct Aabb {
+    min: DVec3,
+    max: DVec3,
+}
+
+impl Aabb {
+    pub fn from_min_max(min: DVec3, max: DVec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn min(&self) -> DVec3 {
+        self.min
+    }
+
+    pub fn max(&self) -> DVec3 {
+        self.max
+    }
+}
+
+impl From<(DVec3, DVec3)> for Aabb {
+    fn from((min, max): (DVec3, DVec3)) -> Self {
+        Self::from_min_max(min, max)
+    }
+}
+
+impl From<Aabb> for (DVec3, DVec3) {
+    fn from(aabb: Aabb) -> Self {
+        (aabb.min, aabb.max)
+    }
+}
+
+/// A road section, with id, the points of every part, bounding box and the attributes from the DBF file.
+/// Most sections consist of a single part, but a polyline record can contain several disjoint parts.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RoadSection {
     pub id: RoadId,
-    pub points: Vec<Vec2>,
-    #[serde(
-        serialize_with = "serialize_aabb",
-        deserialize_with = "deserialize_aabb"
-    )]
+    pub parts: Vec<Vec<DVec2>>,
     pub aabb: Aabb,
+    pub attributes: RoadAttributes,
+}
+
+impl RoadSection {
+    /// Create a road section from its parts.
+    /// Empty parts are dropped and parts that continue where the previous part ended are joined.
+    /// The bounding box is calculated from the remaining points, `None` when there are no points.
+    pub fn new(id: RoadId, parts: Vec<Vec<DVec2>>, attributes: RoadAttributes) -> Option<Self> {
+        let mut joined: Vec<Vec<DVec2>> = Vec::with_capacity(parts.len());
+
+        for part in parts.into_iter().filter(|part| !part.is_empty()) {
+            match joined.last_mut() {
+                Some(previous) if previous.last() == part.first() => {
+                    previous.extend_from_slice(&part[1..]);
+                }
+                _ => joined.push(part),
+            }
+        }
+
+        if joined.is_empty() {
+            return None;
+        }
+
+        let (min, max) = joined.iter().flatten().fold(
+            (DVec2::splat(f64::INFINITY), DVec2::splat(f64::NEG_INFINITY)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        );
+
+        Some(Self {
+            id,
+            parts: joined,
+            aabb: Aabb::from_min_max(min.extend(0.0), max.extend(0.0)),
+            attributes,
+        })
+    }
+
+    /// All points of the section, part after part.
+    pub fn points(&self) -> impl Iterator<Item = DVec2> + '_ {
+        self.parts.iter().flatten().copied()
+    }
+
+    /// The line segments of the section. A segment never connects two different parts.
+    pub fn segments(&self) -> impl Iterator<Item = (DVec2, DVec2)> + '_ {
+        self.parts
+            .iter()
+            .flat_map(|part| part.windows(2).map(|w| (w[0], w[1])))
+    }
+
+    /// First point of the first part, the location of the start junction.
+    pub fn start(&self) -> DVec2 {
+        *self.parts.first().and_then(|part| part.first()).unwrap()
+    }
+
+    /// Last point of the last part, the location of the end junction.
+    pub fn end(&self) -> DVec2 {
+        *self.parts.last().and_then(|part| part.last()).unwrap()
+    }
+
+    /// Length of the section, the gaps between parts are not counted.
+    pub fn length(&self) -> f64 {
+        self.segments().map(|(a, b)| a.distance(b)).sum()
+    }
+
+    /// Squared distance from `point` to the closest segment of this section.
+    pub fn distance_squared(&self, point: DVec2) -> f64 {
+        self.parts
+            .iter()
+            .map(|part| match part.as_slice() {
+                [single] => single.distance_squared(point),
+                points => points
+                    .windows(2)
+                    .map(|w| segment_distance_squared(w[0], w[1], point))
+                    .fold(f64::INFINITY, f64::min),
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+fn segment_distance_squared(a: DVec2, b: DVec2, point: DVec2) -> f64 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+
+    if length_squared == 0.0 {
+        return a.distance_squared(point);
+    }
+
+    let t = ((point - a).dot(ab) / length_squared).clamp(0.0, 1.0);
+
+    (a + ab * t).distance_squared(point)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JunctionSpatialIndex {
     pub junction_id: JunctionId,
-    pub location: Vec2,
+    pub location: DVec2,
 }
 
 impl RTreeObject for JunctionSpatialIndex {
-    type Envelope = AABB<[f32; 2]>;
+    type Envelope = AABB<[f64; 2]>;
 
     fn envelope(&self) -> Self::Envelope {
         AABB::from_point([self.location.x, self.location.y])
@@ -39,22 +157,18 @@ impl PointDistance for JunctionSpatialIndex {
         point: &<Self::Envelope as rstar::Envelope>::Point,
     ) -> <<Self::Envelope as rstar::Envelope>::Point as rstar::Point>::Scalar {
         self.location
-            .distance_squared(Vec2::new(point[0], point[1]))
+            .distance_squared(DVec2::new(point[0], point[1]))
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RoadSpatialIndex {
     pub id: RoadId, // Points to a road in the RoadMap
-    #[serde(
-        serialize_with = "serialize_aabb",
-        deserialize_with = "deserialize_aabb"
-    )]
     pub aabb: Aabb,
 }
 
 impl RTreeObject for RoadSpatialIndex {
-    type Envelope = rstar::AABB<[f32; 2]>;
+    type Envelope = rstar::AABB<[f64; 2]>;
 
     fn envelope(&self) -> Self::Envelope {
         let min = self.aabb.min();
@@ -63,45 +177,60 @@ impl RTreeObject for RoadSpatialIndex {
     }
 }
 
-fn deserialize_aabb<'de, D>(deserializer: D) -> Result<Aabb, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let (min, max): (Vec3, Vec3) = Deserialize::deserialize(deserializer)?;
-
-    Ok(Aabb::from_min_max(min, max))
-}
-
-fn serialize_aabb<S>(aabb: &Aabb, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let min = aabb.min();
-    let max = aabb.max();
-
-    (min, max).serialize(serializer)
+impl PointDistance for RoadSpatialIndex {
+    fn distance_2(
+        &self,
+        point: &<Self::Envelope as rstar::Envelope>::Point,
+    ) -> <<Self::Envelope as rstar::Envelope>::Point as rstar::Point>::Scalar {
+        self.envelope().distance_2(point)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::DrivingDirection;
     use rstar::RTree;
 
+    fn attributes() -> RoadAttributes {
+        RoadAttributes {
+            wegvak_id: 0,
+            junction_start: JunctionId(0),
+            junction_end: JunctionId(1),
+            road_type: None,
+            road_number: None,
+            street_name: Some("Beukelsdijk".into()),
+            alternative_name: None,
+            municipality: Some("Rotterdam".into()),
+            residence: Some("Rotterdam".into()),
+            authority: Some("Rotterdam".into()),
+            authority_type: Some("G".into()),
+            route_numbers: vec![],
+            frc: Some(6),
+            fow: Some(3),
+            direction: DrivingDirection::Both,
+            relative_height: 0,
+            house_numbers_left: None,
+            house_numbers_right: None,
+            speed_limit: None,
+        }
+    }
+
     #[test]
     fn test_junction_spatial_index() {
         let junction = JunctionSpatialIndex {
             junction_id: JunctionId(0),
-            location: Vec2::new(0.0, 0.0),
+            location: DVec2::new(0.0, 0.0),
         };
 
         let junction2 = JunctionSpatialIndex {
             junction_id: JunctionId(1),
-            location: Vec2::new(1.0, 1.0),
+            location: DVec2::new(1.0, 1.0),
         };
 
         let junction3 = JunctionSpatialIndex {
             junction_id: JunctionId(2),
-            location: Vec2::new(2.0, 2.0),
+            location: DVec2::new(2.0, 2.0),
         };
 
         let mut rtree = RTree::new();
@@ -117,19 +246,19 @@ mod tests {
     fn test_road_spatial_index() {
         let road = RoadSpatialIndex {
             id: RoadId(0),
-            aabb: Aabb::from_min_max(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)),
+            aabb: Aabb::from_min_max(DVec3::new(0.0, 0.0, 0.0), DVec3::new(1.0, 1.0, 1.0)),
         }
         .envelope();
 
         let road2 = RoadSpatialIndex {
             id: RoadId(1),
-            aabb: Aabb::from_min_max(Vec3::new(1.0, 1.0, 1.0), Vec3::new(2.0, 2.0, 2.0)),
+            aabb: Aabb::from_min_max(DVec3::new(1.0, 1.0, 1.0), DVec3::new(2.0, 2.0, 2.0)),
         }
         .envelope();
 
         let road3 = RoadSpatialIndex {
             id: RoadId(2),
-            aabb: Aabb::from_min_max(Vec3::new(2.0, 2.0, 2.0), Vec3::new(3.0, 3.0, 3.0)),
+            aabb: Aabb::from_min_max(DVec3::new(2.0, 2.0, 2.0), DVec3::new(3.0, 3.0, 3.0)),
         }
         .envelope();
 
@@ -138,36 +267,139 @@ mod tests {
         assert_eq!(road3, rstar::AABB::from_corners([2.0, 2.0], [3.0, 3.0]));
     }
 
+    #[test]
+    fn test_road_section_distance() {
+        let road_section = RoadSection::new(
+            RoadId(0),
+            vec![vec![
+                DVec2::new(0.0, 0.0),
+                DVec2::new(10.0, 0.0),
+                DVec2::new(10.0, 10.0),
+            ]],
+            attributes(),
+        )
+        .unwrap();
+
+        assert_eq!(road_section.distance_squared(DVec2::new(5.0, 2.0)), 4.0);
+        assert_eq!(road_section.distance_squared(DVec2::new(13.0, 5.0)), 9.0);
+        assert_eq!(road_section.distance_squared(DVec2::new(-3.0, -4.0)), 25.0);
+    }
+
+    #[test]
+    fn test_multi_part_road_section() {
+        let road_section = RoadSection::new(
+            RoadId(0),
+            vec![
+                vec![DVec2::new(0.0, 0.0), DVec2::new(10.0, 0.0)],
+                vec![DVec2::new(20.0, 0.0), DVec2::new(20.0, 10.0)],
+            ],
+            attributes(),
+        )
+        .unwrap();
+
+        assert_eq!(road_section.parts.len(), 2);
+        assert_eq!(road_section.length(), 20.0);
+        assert_eq!(road_section.start(), DVec2::new(0.0, 0.0));
+        assert_eq!(road_section.end(), DVec2::new(20.0, 10.0));
+        assert_eq!(
+            road_section.aabb,
+            Aabb::from_min_max(DVec3::new(0.0, 0.0, 0.0), DVec3::new(20.0, 10.0, 0.0))
+        );
+        // The gap between both parts is not part of the road.
+        assert_eq!(road_section.distance_squared(DVec2::new(15.0, 0.0)), 25.0);
+    }
+
+    #[test]
+    fn test_precision_at_rd_coordinates() {
+        // A 3 cm segment in the east of the country, too short for f32 coordinates.
+        let road_section = RoadSection::new(
+            RoadId(0),
+            vec![vec![
+                DVec2::new(255_000.01, 580_000.02),
+                DVec2::new(255_000.04, 580_000.02),
+            ]],
+            attributes(),
+        )
+        .unwrap();
+
+        assert!((road_section.length() - 0.03).abs() < 1e-6);
+        assert_ne!(road_section.start(), road_section.end());
+    }
+
+    #[test]
+    fn test_road_section_joins_connected_parts() {
+        let road_section = RoadSection::new(
+            RoadId(0),
+            vec![
+                vec![DVec2::new(0.0, 0.0), DVec2::new(10.0, 0.0)],
+                vec![],
+                vec![DVec2::new(10.0, 0.0), DVec2::new(10.0, 10.0)],
+            ],
+            attributes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            road_section.parts,
+            vec![vec![
+                DVec2::new(0.0, 0.0),
+                DVec2::new(10.0, 0.0),
+                DVec2::new(10.0, 10.0)
+            ]]
+        );
+        assert_eq!(road_section.segments().count(), 2);
+        // A record without points is not a road section.
+        assert!(RoadSection::new(RoadId(1), vec![vec![], vec![]], attributes()).is_none());
+    }
+
     #[test]
     fn serialize_road_section() {
         let road_section = RoadSection {
             id: RoadId(0),
-            points: vec![Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0)],
-            aabb: Aabb::from_min_max(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)),
+            parts: vec![vec![DVec2::new(0.0, 0.0), DVec2::new(1.0, 1.0)]],
+            aabb: Aabb::from_min_max(DVec3::new(0.0, 0.0, 0.0), DVec3::new(1.0, 1.0, 1.0)),
+            attributes: attributes(),
         };
 
         let serialized = serde_json::to_string(&road_section).unwrap();
 
         assert_eq!(
             serialized,
-            "{\"id\":0,\"points\":[[0.0,0.0],[1.0,1.0]],\"aabb\":[[0.0,0.0,0.0],[1.0,1.0,1.0]]}"
+            "{\"id\":0,\"parts\":[[[0.0,0.0],[1.0,1.0]]],\"aabb\":[[0.0,0.0,0.0],[1.0,1.0,1.0]],\"attributes\":{\"wegvak_id\":0,\"junction_start\":0,\"junction_end\":1,\"road_type\":null,\"road_number\":null,\"street_name\":\"Beukelsdijk\",\"alternative_name\":null,\"municipality\":\"Rotterdam\",\"residence\":\"Rotterdam\",\"authority\":\"Rotterdam\",\"authority_type\":\"G\",\"route_numbers\":[],\"frc\":6,\"fow\":3,\"direction\":\"Both\",\"relative_height\":0,\"house_numbers_left\":null,\"house_numbers_right\":null,\"speed_limit\":null}}"
         );
     }
 
     #[test]
     fn deserialize_road_section() {
-        let serialized =
-            "{\"id\":0,\"points\":[[0.0,0.0],[1.0,1.0]],\"aabb\":[[0.0,0.0,0.0],[1.0,1.0,1.0]]}";
-        let road_section: RoadSection = serde_json::from_str(serialized).unwrap();
+        let serialized = format!(
+            "{{\"id\":0,\"parts\":[[[0.0,0.0],[1.0,1.0]]],\"aabb\":[[0.0,0.0,0.0],[1.0,1.0,1.0]],\"attributes\":{}}}",
+            serde_json::to_string(&attributes()).unwrap()
+        );
+        let road_section: RoadSection = serde_json::from_str(&serialized).unwrap();
 
         assert_eq!(road_section.id, RoadId(0));
         assert_eq!(
-            road_section.points,
-            vec![Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0)]
+            road_section.parts,
+            vec![vec![DVec2::new(0.0, 0.0), DVec2::new(1.0, 1.0)]]
         );
         assert_eq!(
             road_section.aabb,
-            Aabb::from_min_max(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0))
+            Aabb::from_min_max(DVec3::new(0.0, 0.0, 0.0), DVec3::new(1.0, 1.0, 1.0))
+        );
+        assert_eq!(road_section.attributes, attributes());
+    }
+
+    #[test]
+    fn aabb_serialization_layout() {
+        let min = DVec3::new(1.0, 2.0, 3.0);
+        let max = DVec3::new(4.0, 5.0, 6.0);
+
+        let serialized = bincode::serialize(&Aabb::from_min_max(min, max)).unwrap();
+
+        assert_eq!(serialized, bincode::serialize(&(min, max)).unwrap());
+        assert_eq!(
+            bincode::deserialize::<Aabb>(&serialized).unwrap(),
+            Aabb::from_min_max(min, max)
         );
     }
 }
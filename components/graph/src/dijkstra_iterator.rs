@@ -1,59 +1,79 @@
 use crate::{
-    Backward, DirectedNetworkGraph, EdgeId, Forward, NetworkData, NetworkEdge, NodeId, F32,
-};
-use std::{
-    cmp::Reverse,
-    collections::{BinaryHeap, HashSet},
+    Backward, DijkstraWorkspace, DirectedNetworkGraph, EdgeId, EdgeOverlay, Forward, NetworkData,
+    NetworkEdge, NodeId,
 };
+use std::borrow::BorrowMut;
 
-pub struct DijkstraIterator<'a, T: DijkstraDirection, D: NetworkData> {
+/// Dijkstra search from a single node, the nodes are returned in order of their distance.
+/// The search state is kept in `W`, an owned workspace or one that is borrowed and reused between searches.
+pub struct DijkstraIterator<'a, T: DijkstraDirection, D: NetworkData, W> {
     pub network: &'a DirectedNetworkGraph<D>,
     pub distance: f32,
-    pub visited: HashSet<NodeId>,
-    pub heap: BinaryHeap<Reverse<(F32, NodeId)>>,
+    workspace: W,
+    overlay: Option<&'a EdgeOverlay>,
     _marker: std::marker::PhantomData<T>,
 }
 
-impl<'a, T: DijkstraDirection, D: NetworkData> DijkstraIterator<'a, T, D> {
-    pub fn new(network: &'a DirectedNetworkGraph<D>, start: NodeId) -> Self {
-        let mut heap = BinaryHeap::new();
-        heap.push(Reverse((F32(0.0), start)));
+impl<'a, T, D, W> DijkstraIterator<'a, T, D, W>
+where
+    T: DijkstraDirection,
+    D: NetworkData,
+    W: BorrowMut<DijkstraWorkspace>,
+{
+    pub fn with_workspace(
+        network: &'a DirectedNetworkGraph<D>,
+        start: NodeId,
+        mut workspace: W,
+    ) -> Self {
+        workspace.borrow_mut().reset(start);
 
         Self {
             network,
             distance: 0.0,
-            visited: HashSet::new(),
-            heap,
+            workspace,
+            overlay: None,
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// Search with the blocked edges and weights of `overlay`.
+    pub fn with_overlay(mut self, overlay: &'a EdgeOverlay) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
 }
 
-impl<'a, T, D> Iterator for DijkstraIterator<'a, T, D>
+impl<'a, T, D, W> Iterator for DijkstraIterator<'a, T, D, W>
 where
     T: DijkstraDirection,
     D: NetworkData,
+    W: BorrowMut<DijkstraWorkspace>,
 {
     type Item = (NodeId, f32);
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(Reverse((F32(distance), node))) = self.heap.pop() {
-            if !self.visited.insert(node) {
-                continue;
-            }
-            for (_, edge) in T::edges(self.network, node) {
-                let target = edge.target();
-                let edge_distance = edge.distance();
+        let workspace = self.workspace.borrow_mut();
+        let (node, distance) = workspace.heap.pop()?;
+        workspace.settled.insert(node, distance);
 
-                self.heap
-                    .push(Reverse((F32(distance + edge_distance), target)));
+        for (id, edge) in T::edges(self.network, node) {
+            let weight = match self.overlay {
+                Some(overlay) => match overlay.weight(id, edge) {
+                    Some(weight) => weight,
+                    None => continue,
+                },
+                None => edge.distance(),
+            };
+
+            let target = edge.target();
+            if !workspace.settled.contains(target) {
+                workspace.heap.push(target, distance + weight);
             }
+        }
 
-            self.distance = distance;
+        self.distance = distance;
 
-            return Some((node, distance));
-        }
-        return None;
+        Some((node, distance))
     }
 }
 
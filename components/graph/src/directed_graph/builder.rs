@@ -2,7 +2,7 @@ use crate::{DirectedNetworkGraph, NetworkData, NetworkEdge, NetworkNode, NodeId,
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fmt::Debug, hash::Hash, ops::Neg};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum EdgeDirection {
     Forward,
     Both,
@@ -139,9 +139,13 @@ impl<V: NodeBuilder, E: EdgeBuilder> DirectedNetworkBuilder<V, E> {
         let mut build_nodes = self.nodes.into_iter().collect::<Vec<_>>();
         build_nodes.sort_by_key(|d| d.1);
 
+        // In order of source and target, so the order of the edges does not depend on the hash map.
+        let mut build_edges = self.edges.iter().collect::<Vec<_>>();
+        build_edges.sort_by_key(|(key, _)| **key);
+
         let mut map = HashMap::<NodeId, Vec<&E>>::new();
 
-        for (_, edge) in &self.edges {
+        for (_, edge) in build_edges {
             map.entry(edge.source()).or_default().push(edge);
             map.entry(edge.target()).or_default().push(edge);
         }
@@ -172,7 +176,10 @@ impl<V: NodeBuilder, E: EdgeBuilder> DirectedNetworkBuilder<V, E> {
             nodes.push(network_node);
         }
 
-        DirectedNetworkGraph::new(nodes, edges, network_data)
+        let network = DirectedNetworkGraph::new(nodes, edges, network_data);
+        network.debug_validate();
+
+        network
     }
 }
 
@@ -0,0 +1,249 @@
+use super::builder::EdgeDirection;
+use crate::{DirectedNetworkGraph, EdgeId, NetworkData, NetworkEdge, NetworkNode, NodeId};
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Display};
+
+const UNVISITED: u32 = u32::MAX;
+
+/// The strongly connected components of a graph.
+/// Components are numbered by size, component 0 is the largest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StronglyConnectedComponents {
+    node_component: Vec<u32>,
+    sizes: Vec<usize>,
+}
+
+impl StronglyConnectedComponents {
+    /// The component of `node`.
+    pub fn component(&self, node: NodeId) -> usize {
+        self.node_component[node.0 as usize] as usize
+    }
+
+    /// The number of nodes of every component, from large to small.
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// Whether `node` is part of the largest component.
+    pub fn in_largest(&self, node: NodeId) -> bool {
+        self.component(node) == 0
+    }
+
+    /// The nodes of `component`, in order of their id.
+    pub fn nodes(&self, component: usize) -> impl Iterator<Item = NodeId> + '_ {
+        self.node_component
+            .iter()
+            .enumerate()
+            .filter(move |(_, c)| **c as usize == component)
+            .map(|(node, _)| NodeId::from(node))
+    }
+}
+
+/// Number of components of every size, followed by the sizes of the largest components.
+impl Display for StronglyConnectedComponents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let nodes = self.node_component.len();
+        let largest = self.sizes.first().cloned().unwrap_or_default();
+
+        writeln!(
+            f,
+            "{} components, largest: {} of {} nodes ({:.2}%)",
+            self.len(),
+            largest,
+            nodes,
+            100.0 * largest as f32 / nodes.max(1) as f32
+        )?;
+
+        let mut histogram = BTreeMap::<usize, usize>::new();
+        for size in &self.sizes {
+            *histogram.entry(*size).or_default() += 1;
+        }
+        for (size, count) in histogram.iter().rev() {
+            writeln!(f, "size {}: {} components", size, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<D: NetworkData> DirectedNetworkGraph<D> {
+    /// Find the strongly connected components with Tarjan's algorithm.
+    /// Iterative, so large road networks do not overflow the stack.
+    pub fn strongly_connected_components(&self) -> StronglyConnectedComponents {
+        let node_count = self.nodes().len();
+
+        let mut index = vec![UNVISITED; node_count];
+        let mut low = vec![0u32; node_count];
+        let mut on_stack = vec![false; node_count];
+        let mut component = vec![UNVISITED; node_count];
+        let mut sizes = Vec::new();
+
+        let mut next_index = 0;
+        let mut stack = Vec::new();
+        // The node that is visited and the next of its edges to explore.
+        let mut call_stack: Vec<(usize, u32)> = Vec::new();
+
+        for root in 0..node_count {
+            if index[root] != UNVISITED {
+                continue;
+            }
+
+            index[root] = next_index;
+            low[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            call_stack.push((root, self.nodes()[root].start_edge_index));
+
+            while let Some((node, mut edge_index)) = call_stack.pop() {
+                let last_edge_index = self.nodes()[node].last_edge_index;
+                let mut descend = None;
+
+                while edge_index < last_edge_index {
+                    let edge = &self.edges()[edge_index as usize];
+                    edge_index += 1;
+
+                    if edge.direction() == EdgeDirection::Backward {
+                        continue;
+                    }
+
+                    let target = edge.target().0 as usize;
+                    if index[target] == UNVISITED {
+                        descend = Some(target);
+                        break;
+                    } else if on_stack[target] {
+                        low[node] = low[node].min(index[target]);
+                    }
+                }
+
+                if let Some(target) = descend {
+                    call_stack.push((node, edge_index));
+
+                    index[target] = next_index;
+                    low[target] = next_index;
+                    next_index += 1;
+                    stack.push(target);
+                    on_stack[target] = true;
+                    call_stack.push((target, self.nodes()[target].start_edge_index));
+                    continue;
+                }
+
+                if let Some((parent, _)) = call_stack.last() {
+                    low[*parent] = low[*parent].min(low[node]);
+                }
+
+                if low[node] == index[node] {
+                    let id = sizes.len() as u32;
+                    let mut size = 0;
+
+                    while let Some(member) = stack.pop() {
+                        on_stack[member] = false;
+                        component[member] = id;
+                        size += 1;
+
+                        if member == node {
+                            break;
+                        }
+                    }
+
+                    sizes.push(size);
+                }
+            }
+        }
+
+        // Renumber the components from large to small.
+        let mut order = (0..sizes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&c| (Reverse(sizes[c]), c));
+
+        let mut rank = vec![0; sizes.len()];
+        for (new, old) in order.iter().enumerate() {
+            rank[*old] = new as u32;
+        }
+
+        StronglyConnectedComponents {
+            node_component: component.into_iter().map(|c| rank[c as usize]).collect(),
+            sizes: order.into_iter().map(|c| sizes[c]).collect(),
+        }
+    }
+
+    /// Create the graph of the nodes for which `keep` returns true.
+    /// Edges to removed nodes are dropped, node and edge ids are renumbered in their original order.
+    pub fn subgraph<F>(&self, keep: F) -> Self
+    where
+        F: Fn(NodeId) -> bool,
+        D::NodeData: Clone,
+        D::EdgeData: Clone,
+    {
+        let mut new_ids = vec![None; self.nodes().len()];
+        let mut node_count = 0;
+        for (node, new_id) in new_ids.iter_mut().enumerate() {
+            if keep(NodeId::from(node)) {
+                *new_id = Some(NodeId::from(node_count));
+                node_count += 1;
+            }
+        }
+
+        let kept_edges = self
+            .edges()
+            .iter()
+            .filter(|edge| new_ids[edge.target().0 as usize].is_some())
+            .count();
+
+        let mut data = self.data.derive_with_size(node_count, kept_edges);
+        let mut nodes = Vec::with_capacity(node_count);
+        let mut edges = Vec::with_capacity(kept_edges);
+
+        for (node, network_node) in self.nodes().iter().enumerate() {
+            let Some(new_id) = new_ids[node] else {
+                continue;
+            };
+            data.add_node(new_id, self.node_data(NodeId::from(node)).clone());
+
+            let start_edge_index = edges.len() as u32;
+            for index in network_node.start_edge_index..network_node.last_edge_index {
+                let edge = &self.edges()[index as usize];
+                let Some(target) = new_ids[edge.target().0 as usize] else {
+                    continue;
+                };
+
+                let id = EdgeId::from(edges.len());
+                let old_id = EdgeId::from(index);
+                data.add_edge(
+                    id,
+                    self.edge_data(old_id).clone(),
+                    self.data.edge_road_id(old_id),
+                );
+                edges.push(NetworkEdge::new(
+                    id.0,
+                    target,
+                    edge.distance(),
+                    edge.direction(),
+                ));
+            }
+
+            nodes.push(NetworkNode::new(start_edge_index, edges.len() as u32));
+        }
+
+        let network = DirectedNetworkGraph::new(nodes, edges, data);
+        network.debug_validate();
+
+        network
+    }
+
+    /// Keep only the largest strongly connected component, so every node can reach every other node.
+    pub fn largest_component(&self) -> Self
+    where
+        D::NodeData: Clone,
+        D::EdgeData: Clone,
+    {
+        let components = self.strongly_connected_components();
+        self.subgraph(|node| components.in_largest(node))
+    }
+}
@@ -1,13 +1,20 @@
 use self::{builder::EdgeDirection, iterators::EdgeIterator};
 use crate::{
-    dijkstra_iterator::DijkstraIterator, Backward, EdgeId, Forward, Neighbourhood, NodeId,
+    dijkstra_iterator::DijkstraIterator, Backward, DijkstraWorkspace, EdgeId, Forward,
+    Neighbourhood, NodeId,
 };
+pub use components::StronglyConnectedComponents;
 pub use node_data::NetworkData;
+pub use reorder::Permutation;
 use serde::{Deserialize, Serialize};
+pub use validate::ValidationError;
 
 pub mod builder;
+pub mod components;
 pub mod iterators;
 pub mod node_data;
+pub mod reorder;
+pub mod validate;
 
 /// A node in the graph.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -61,6 +68,10 @@ impl NetworkEdge {
     pub fn distance(&self) -> f32 {
         self.edge_weight
     }
+
+    pub fn direction(&self) -> EdgeDirection {
+        self.direction
+    }
 }
 
 /// A Directed network graph, the graph is represented by a list of nodes and a list of edges.
@@ -102,6 +113,47 @@ impl<D: NetworkData> DirectedNetworkGraph<D> {
         &self.edges
     }
 
+    /// A hash of the nodes, edges and weights, to check that data computed from a graph,
+    /// like its neighbourhoods, belongs to this graph. The network data is not part of it.
+    pub fn fingerprint(&self) -> u64 {
+        // FNV-1a, which gives the same hash in every build.
+        const PRIME: u64 = 0x100000001b3;
+
+        let nodes = self
+            .nodes
+            .iter()
+            .flat_map(|x| [x.start_edge_index as u64, x.last_edge_index as u64]);
+        let edges = self.edges.iter().flat_map(|x| {
+            [
+                x.target_node.0 as u64,
+                x.edge_weight.to_bits() as u64,
+                x.direction as u64,
+            ]
+        });
+
+        nodes
+            .chain(edges)
+            .fold(0xcbf29ce484222325, |hash, x| (hash ^ x).wrapping_mul(PRIME))
+    }
+
+    /// The same graph, with the weight of every edge from `weight`.
+    pub fn with_weights<F: Fn(EdgeId) -> f32>(&self, weight: F) -> Self
+    where
+        D: Clone,
+    {
+        let edges = self
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(id, edge)| NetworkEdge {
+                edge_weight: weight(EdgeId::from(id)),
+                ..edge.clone()
+            })
+            .collect();
+
+        Self::new(self.nodes.clone(), edges, self.data.clone())
+    }
+
     fn create_iterator(&self, node: NodeId, direction: EdgeDirection) -> EdgeIterator {
         self.create_iterator_raw(self.node(node), direction)
     }
@@ -133,12 +185,22 @@ impl<D: NetworkData> DirectedNetworkGraph<D> {
         self.create_iterator_raw(node, EdgeDirection::Backward)
     }
 
-    pub fn forward_iterator(&self, node: NodeId) -> DijkstraIterator<'_, Forward, D> {
-        DijkstraIterator::new(self, node)
+    /// Dijkstra search from `node`, the memory of `workspace` is reused between searches.
+    pub fn forward_search<'w>(
+        &self,
+        node: NodeId,
+        workspace: &'w mut DijkstraWorkspace,
+    ) -> DijkstraIterator<'_, Forward, D, &'w mut DijkstraWorkspace> {
+        DijkstraIterator::with_workspace(self, node, workspace)
     }
 
-    pub fn backward_iterator(&self, node: NodeId) -> DijkstraIterator<'_, Backward, D> {
-        DijkstraIterator::new(self, node)
+    /// Dijkstra search to `node` over the incoming edges, see [`Self::forward_search`].
+    pub fn backward_search<'w>(
+        &self,
+        node: NodeId,
+        workspace: &'w mut DijkstraWorkspace,
+    ) -> DijkstraIterator<'_, Backward, D, &'w mut DijkstraWorkspace> {
+        DijkstraIterator::with_workspace(self, node, workspace)
     }
 
     pub fn forward_neighbourhood(&self, size: usize) -> Neighbourhood<Forward> {
@@ -8,6 +8,11 @@ pub trait NetworkData: Send + Sync + Default {
     fn edge_road_id(&self, edge: EdgeId) -> ShortcutState<usize>;
 
     fn with_size(node_size: usize, edge_size: usize) -> Self;
+    /// Empty data for a graph that is derived from this one, like a reordered graph or a subgraph.
+    /// Data that is shared by the edges instead of stored per edge is kept.
+    fn derive_with_size(&self, node_size: usize, edge_size: usize) -> Self {
+        Self::with_size(node_size, edge_size)
+    }
     fn add_node(&mut self, node: NodeId, data: Self::NodeData);
     fn add_edge(&mut self, edge: EdgeId, data: Self::EdgeData, road_id: ShortcutState<usize>);
 }
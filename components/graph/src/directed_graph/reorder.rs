@@ -0,0 +1,222 @@
+use crate::{DirectedNetworkGraph, EdgeId, NetworkData, NetworkEdge, NetworkNode, NodeId};
+use std::collections::VecDeque;
+
+/// Number of bits per axis of the Hilbert curve grid.
+const HILBERT_BITS: u32 = 16;
+
+/// The renumbering of the nodes and edges done by [`DirectedNetworkGraph::reorder`].
+/// Data that refers to the nodes or edges of the original graph, like the edges of a layer
+/// that was calculated from it, can be updated with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permutation {
+    nodes: Vec<NodeId>,
+    edges: Vec<EdgeId>,
+}
+
+impl Permutation {
+    /// The new id of node `old`.
+    pub fn node(&self, old: NodeId) -> NodeId {
+        self.nodes[old.0 as usize]
+    }
+
+    /// The new id of edge `old`.
+    pub fn edge(&self, old: EdgeId) -> EdgeId {
+        self.edges[old.0 as usize]
+    }
+}
+
+impl<D: NetworkData> DirectedNetworkGraph<D> {
+    /// Renumber the nodes, `order[i]` becomes node `i`.
+    /// The edges are renumbered along with their source node, and the node and edge data is moved with them.
+    pub fn reorder(&self, order: &[NodeId]) -> (Self, Permutation)
+    where
+        D::NodeData: Clone,
+        D::EdgeData: Clone,
+    {
+        assert_eq!(
+            order.len(),
+            self.nodes().len(),
+            "Order must contain every node"
+        );
+
+        let mut new_nodes = vec![NodeId(u32::MAX); order.len()];
+        for (new, old) in order.iter().enumerate() {
+            assert_eq!(
+                new_nodes[old.0 as usize].0,
+                u32::MAX,
+                "Node {} is repeated",
+                old.0
+            );
+            new_nodes[old.0 as usize] = NodeId::from(new);
+        }
+
+        let mut new_edges = vec![EdgeId(u32::MAX); self.edges().len()];
+        let mut data = self.data.derive_with_size(order.len(), self.edges().len());
+        let mut nodes = Vec::with_capacity(order.len());
+        let mut edges = Vec::with_capacity(self.edges().len());
+
+        for (new, old) in order.iter().enumerate() {
+            data.add_node(NodeId::from(new), self.node_data(*old).clone());
+
+            let node = self.node(*old);
+            let mut node_edges = (node.start_edge_index..node.last_edge_index)
+                .map(|index| {
+                    let edge = self.edge(EdgeId::from(index));
+                    (
+                        EdgeId::from(index),
+                        new_nodes[edge.target().0 as usize],
+                        edge,
+                    )
+                })
+                .collect::<Vec<_>>();
+            // Targets are renumbered, restore the order of the builder.
+            node_edges.sort_by_key(|(_, target, edge)| (edge.direction(), *target));
+
+            let start_edge_index = edges.len() as u32;
+            for (old_id, target, edge) in node_edges {
+                let id = EdgeId::from(edges.len());
+                new_edges[old_id.0 as usize] = id;
+
+                data.add_edge(
+                    id,
+                    self.edge_data(old_id).clone(),
+                    self.data.edge_road_id(old_id),
+                );
+                edges.push(NetworkEdge::new(
+                    id.0,
+                    target,
+                    edge.distance(),
+                    edge.direction(),
+                ));
+            }
+
+            nodes.push(NetworkNode::new(start_edge_index, edges.len() as u32));
+        }
+
+        let network = DirectedNetworkGraph::new(nodes, edges, data);
+        network.debug_validate();
+
+        let permutation = Permutation {
+            nodes: new_nodes,
+            edges: new_edges,
+        };
+
+        (network, permutation)
+    }
+
+    /// Breadth first order over the edges in both directions.
+    /// Every unvisited node starts a new search, in order of their id.
+    pub fn bfs_order(&self) -> Vec<NodeId> {
+        let mut visited = vec![false; self.nodes().len()];
+        let mut order = Vec::with_capacity(self.nodes().len());
+        let mut queue = VecDeque::new();
+
+        for root in 0..self.nodes().len() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            queue.push_back(NodeId::from(root));
+
+            while let Some(node) = queue.pop_front() {
+                order.push(node);
+
+                let network_node = self.node(node);
+                for index in network_node.start_edge_index..network_node.last_edge_index {
+                    let target = self.edge(EdgeId::from(index)).target();
+                    if !visited[target.0 as usize] {
+                        visited[target.0 as usize] = true;
+                        queue.push_back(target);
+                    }
+                }
+            }
+        }
+
+        order
+    }
+
+    /// Order the nodes along a Hilbert curve through their `location`,
+    /// so nodes that are close to each other get ids that are close to each other.
+    pub fn hilbert_order<F>(&self, location: F) -> Vec<NodeId>
+    where
+        F: Fn(NodeId) -> [f64; 2],
+    {
+        let locations = (0..self.nodes().len())
+            .map(|node| location(NodeId::from(node)))
+            .collect::<Vec<_>>();
+
+        let (min, max) = locations.iter().fold(
+            ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]),
+            |(min, max), [x, y]| {
+                (
+                    [min[0].min(*x), min[1].min(*y)],
+                    [max[0].max(*x), max[1].max(*y)],
+                )
+            },
+        );
+        let cells = ((1u32 << HILBERT_BITS) - 1) as f64;
+        let scale = cells / f64::max(max[0] - min[0], max[1] - min[1]).max(f64::EPSILON);
+
+        let mut order = locations
+            .iter()
+            .enumerate()
+            .map(|(node, [x, y])| {
+                let x = ((x - min[0]) * scale) as u32;
+                let y = ((y - min[1]) * scale) as u32;
+                (hilbert_index(x, y), NodeId::from(node))
+            })
+            .collect::<Vec<_>>();
+        order.sort_unstable();
+
+        order.into_iter().map(|(_, node)| node).collect()
+    }
+}
+
+/// Distance along the Hilbert curve of the cell `(x, y)` in a grid of `2^HILBERT_BITS` cells per axis.
+fn hilbert_index(mut x: u32, mut y: u32) -> u64 {
+    let n = 1u32 << HILBERT_BITS;
+    let mut index = 0;
+    let mut s = n / 2;
+
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        index += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+        // Rotate the quadrant, so the curve stays continuous.
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hilbert_index;
+
+    #[test]
+    fn hilbert_index_is_continuous() {
+        let size = 1u32 << 4;
+        let shift = super::HILBERT_BITS - 4;
+
+        let mut cells = (0..size)
+            .flat_map(|x| (0..size).map(move |y| (x, y)))
+            .map(|(x, y)| (hilbert_index(x << shift, y << shift), x, y))
+            .collect::<Vec<_>>();
+        cells.sort();
+
+        for w in cells.windows(2) {
+            let (_, x0, y0) = w[0];
+            let (_, x1, y1) = w[1];
+            assert_eq!(x0.abs_diff(x1) + y0.abs_diff(y1), 1);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use super::builder::EdgeDirection;
+use crate::{DirectedNetworkGraph, EdgeId, NetworkData, NodeId};
+use std::{collections::HashMap, fmt::Display};
+
+/// Maximum number of errors that is shown when [`DirectedNetworkGraph::debug_validate`] fails.
+const SHOWN_ERRORS: usize = 20;
+
+/// A broken invariant of a [`DirectedNetworkGraph`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// The edge range of a node ends before it starts, or after the last edge.
+    InvalidEdgeRange { node: NodeId, start: u32, last: u32 },
+    /// The edge ranges of two nodes overlap.
+    OverlappingEdgeRange { node: NodeId, other: NodeId },
+    /// An edge that is not in the edge range of any node.
+    UnusedEdge { edge: EdgeId },
+    /// The `edge_id` of an edge is not its index in the list of edges.
+    EdgeIdMismatch { edge: EdgeId, edge_id: u32 },
+    /// An edge points to a node that does not exist.
+    InvalidTarget { edge: EdgeId, target: NodeId },
+    /// The weight of an edge is NaN, infinite or negative.
+    InvalidWeight { edge: EdgeId, weight: f32 },
+    /// The edges of a node are not sorted by direction and target.
+    UnsortedEdges { node: NodeId },
+    /// The other end of an edge does not have the mirrored edge, with the opposite direction and the same weight.
+    MissingMirror {
+        node: NodeId,
+        edge: EdgeId,
+        target: NodeId,
+        direction: EdgeDirection,
+    },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::InvalidEdgeRange { node, start, last } => write!(
+                f,
+                "node {} has an invalid edge range {}..{}",
+                node.0, start, last
+            ),
+            ValidationError::OverlappingEdgeRange { node, other } => write!(
+                f,
+                "edge ranges of node {} and node {} overlap",
+                node.0, other.0
+            ),
+            ValidationError::UnusedEdge { edge } => {
+                write!(f, "edge {} does not belong to a node", edge.0)
+            }
+            ValidationError::EdgeIdMismatch { edge, edge_id } => {
+                write!(f, "edge {} has edge_id {}", edge.0, edge_id)
+            }
+            ValidationError::InvalidTarget { edge, target } => write!(
+                f,
+                "edge {} points to node {} which does not exist",
+                edge.0, target.0
+            ),
+            ValidationError::InvalidWeight { edge, weight } => {
+                write!(f, "edge {} has an invalid weight {}", edge.0, weight)
+            }
+            ValidationError::UnsortedEdges { node } => write!(
+                f,
+                "edges of node {} are not sorted by direction and target",
+                node.0
+            ),
+            ValidationError::MissingMirror {
+                node,
+                edge,
+                target,
+                direction,
+            } => write!(
+                f,
+                "edge {} ({} -> {}, {:?}) has no mirrored edge at node {}",
+                edge.0, node.0, target.0, direction, target.0
+            ),
+        }
+    }
+}
+
+impl<D: NetworkData> DirectedNetworkGraph<D> {
+    /// Check the invariants of the graph that the algorithms rely on.
+    /// Every broken invariant is returned, so the cause can be found.
+    ///
+    /// - The edge ranges of the nodes are valid, do not overlap and cover all edges.
+    /// - The `edge_id` of every edge is its index.
+    /// - Every edge points to an existing node and has a finite, non negative weight.
+    /// - The edges of a node are sorted by direction and target, the order of the builder.
+    /// - Every edge has a mirrored edge at its target with the opposite direction and the same weight,
+    ///   except for loops.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let nodes = self.nodes();
+        let edges = self.edges();
+        let mut errors = Vec::new();
+
+        // Nodes with a valid edge range, (start, last, node).
+        let mut ranges = Vec::with_capacity(nodes.len());
+        for (id, node) in nodes.iter().enumerate() {
+            let (start, last) = (node.start_edge_index, node.last_edge_index);
+            if start > last || last as usize > edges.len() {
+                errors.push(ValidationError::InvalidEdgeRange {
+                    node: NodeId::from(id),
+                    start,
+                    last,
+                });
+            } else {
+                ranges.push((start, last, NodeId::from(id)));
+            }
+        }
+
+        ranges.sort();
+        let mut covered = 0;
+        // The node whose edge range ends at `covered`.
+        let mut previous = None;
+        for (start, last, node) in &ranges {
+            if start == last {
+                continue;
+            }
+
+            if *start > covered {
+                for edge in covered..*start {
+                    errors.push(ValidationError::UnusedEdge {
+                        edge: EdgeId::from(edge),
+                    });
+                }
+            } else if *start < covered {
+                if let Some(other) = previous {
+                    errors.push(ValidationError::OverlappingEdgeRange { node: *node, other });
+                }
+            }
+
+            if *last > covered {
+                covered = *last;
+                previous = Some(*node);
+            }
+        }
+        for edge in covered..edges.len() as u32 {
+            errors.push(ValidationError::UnusedEdge {
+                edge: EdgeId::from(edge),
+            });
+        }
+
+        for (id, edge) in edges.iter().enumerate() {
+            let id = EdgeId::from(id);
+
+            if edge.edge_id != id.0 {
+                errors.push(ValidationError::EdgeIdMismatch {
+                    edge: id,
+                    edge_id: edge.edge_id,
+                });
+            }
+            if edge.target().0 as usize >= nodes.len() {
+                errors.push(ValidationError::InvalidTarget {
+                    edge: id,
+                    target: edge.target(),
+                });
+            }
+            if !edge.distance().is_finite() || edge.distance() < 0.0 {
+                errors.push(ValidationError::InvalidWeight {
+                    edge: id,
+                    weight: edge.distance(),
+                });
+            }
+        }
+
+        // Count the edges by (source, target, direction, weight), every edge needs a mirror.
+        let mut counts = HashMap::<(u32, u32, EdgeDirection, u32), usize>::new();
+        for (start, last, node) in &ranges {
+            let node_edges = &edges[*start as usize..*last as usize];
+
+            if !node_edges.is_sorted_by_key(|x| (x.direction(), x.target())) {
+                errors.push(ValidationError::UnsortedEdges { node: *node });
+            }
+            for edge in node_edges {
+                let key = (
+                    node.0,
+                    edge.target().0,
+                    edge.direction(),
+                    edge.distance().to_bits(),
+                );
+                *counts.entry(key).or_default() += 1;
+            }
+        }
+
+        for (start, last, node) in &ranges {
+            for index in *start..*last {
+                let edge = &edges[index as usize];
+                let key = (
+                    node.0,
+                    edge.target().0,
+                    edge.direction(),
+                    edge.distance().to_bits(),
+                );
+                let mirror = (key.1, key.0, -key.2, key.3);
+
+                // A loop is its own mirror, the builder adds it twice to the same node.
+                if key.0 != key.1 && counts.get(&mirror) != counts.get(&key) {
+                    errors.push(ValidationError::MissingMirror {
+                        node: *node,
+                        edge: EdgeId::from(index),
+                        target: edge.target(),
+                        direction: edge.direction(),
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Panic with the diagnostics of [`Self::validate`] when an invariant is broken.
+    /// Only checked in debug builds, it visits every node and edge.
+    pub fn debug_validate(&self) {
+        #[cfg(debug_assertions)]
+        if let Err(errors) = self.validate() {
+            let shown = errors
+                .iter()
+                .take(SHOWN_ERRORS)
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>();
+
+            panic!(
+                "Invalid graph, {} errors:\n{}",
+                errors.len(),
+                shown.join("\n")
+            );
+        }
+    }
+}
@@ -1,9 +1,6 @@
-use std::collections::HashMap;
-
-use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
-use crate::{builder::EdgeDirection, DirectedNetworkGraph, NodeId};
+use crate::{builder::EdgeDirection, DirectedNetworkGraph, NetworkData, NodeId};
 
 use self::iterators::EdgeIterator;
 
@@ -73,65 +70,103 @@ pub struct HighwayNetwork {
  * - weight
  */
 impl HighwayNetwork {
-    pub fn new(base: DirectedNetworkGraph, layers: Vec<DirectedNetworkGraph>) -> Self {
-        // unimplemented!()
-        // Level 0  is base,
-        // Level >1 are layers
-        // let max_level = layers.len() as u8;
-        // let mut nodes = Vec::with_capacity(base.nodes().len());
-        // let mut highway_nodes = Vec::with_capacity(base.nodes().len() * (max_level / 2u8));
-        // let mut edges = Vec::with_capacity(base.edges().len());
-
-        let mut collect_mapper = HashMap::new();
-
-        for (id, node) in base.nodes().into_iter().enumerate() {
-            let out_edges = base.out_edges_raw(node).collect_vec();
-            let in_edges = base.in_edges_raw(node).collect_vec();
-            collect_mapper.insert(id, vec![(node, out_edges, in_edges)]);
+    /// Combine the base graph, level 0, with its layers, `layers[i]` is level `i + 1`.
+    /// The node data of a layer is the node of the level below it, the nodes of a layer are a subset of that level.
+    /// `radius` is the forward and backward neighbourhood radius of a node of a level, with the node id of that level.
+    pub fn new<B, L, R>(
+        base: &DirectedNetworkGraph<B>,
+        layers: &[DirectedNetworkGraph<L>],
+        radius: R,
+    ) -> Self
+    where
+        B: NetworkData,
+        L: NetworkData<NodeData = NodeId>,
+        R: Fn(u8, NodeId) -> (f32, f32),
+    {
+        let max_level = layers.len() as u8;
+
+        // The base node of every node of every level.
+        let mut base_nodes = vec![(0..base.nodes().len())
+            .map(NodeId::from)
+            .collect::<Vec<_>>()];
+        for layer in layers {
+            let lower = base_nodes.last().unwrap();
+            let level = (0..layer.nodes().len())
+                .map(|node| lower[layer.node_data(NodeId::from(node)).0 as usize])
+                .collect();
+            base_nodes.push(level);
+        }
+
+        // The node of every level a base node is in, a node is in every level up to the highest level it is in.
+        let mut level_nodes = vec![Vec::new(); base.nodes().len()];
+        for (level, nodes) in base_nodes.iter().enumerate() {
+            for (node, base_node) in nodes.iter().enumerate() {
+                let levels = &mut level_nodes[base_node.0 as usize];
+                debug_assert_eq!(levels.len(), level);
+                levels.push(NodeId::from(node));
+            }
         }
 
-        for layer in &layers {
-            for (id, node) in layer.nodes().into_iter().enumerate() {
-                let out_edges = layer.out_edges_raw(node).collect_vec();
-                let in_edges = layer.in_edges_raw(node).collect_vec();
-                collect_mapper.entry(id).and_modify(|d| {
-                    d.push((node, out_edges, in_edges));
+        let mut nodes = Vec::with_capacity(base.nodes().len());
+        let mut highway_nodes = Vec::with_capacity(base.nodes().len());
+        let mut edges = Vec::with_capacity(base.edges().len());
+
+        for levels in level_nodes {
+            let start_node_index = highway_nodes.len() as u32;
+            let start_edge_index = edges.len() as u32;
+
+            for (level, node) in levels.into_iter().enumerate() {
+                let level = level as u8;
+
+                let (network_node, level_edges) = match level {
+                    0 => (base.node(node), base.edges()),
+                    _ => {
+                        let layer = &layers[level as usize - 1];
+                        (layer.node(node), layer.edges())
+                    }
+                };
+                let level_start = edges.len() as u32;
+
+                for (edge_id, edge) in (network_node.start_edge_index..network_node.last_edge_index)
+                    .zip(
+                        &level_edges[network_node.start_edge_index as usize
+                            ..network_node.last_edge_index as usize],
+                    )
+                {
+                    edges.push(NetworkEdge {
+                        edge_id,
+                        target_node: base_nodes[level as usize][edge.target().0 as usize],
+                        edge_weight: edge.distance(),
+                        level,
+                        direction: edge.direction(),
+                    });
+                }
+
+                let (forward_radius, backward_radius) = radius(level, node);
+                highway_nodes.push(HighwayNetworkLevelNode {
+                    start_edge_index: level_start,
+                    last_edge_index: edges.len() as u32,
+                    forward_radius,
+                    backward_radius,
+                    // The layers only keep the core, the bypassed nodes are not part of them.
+                    is_bypassed: false,
                 });
             }
+
+            nodes.push(HighwayNetworkBaseNode {
+                start_edge_index,
+                last_edge_index: edges.len() as u32,
+                start_node_index,
+                end_node_index: highway_nodes.len() as u32,
+            });
         }
 
-        unimplemented!()
-
-        // for base_node in base.nodes() {
-        //     let start_node_index = highway_nodes.len();
-        //     let start_edge_index = edges.len();
-
-        //     // let node_edges = base.out_edges_raw(node).map(|(id, e)| {
-        //     //     NetworkEdge {
-        //     //         level: 0,
-
-        //     //     }
-        //     // });
-
-        //     for layer in layers {
-        //         for n in layer.nodes() {
-
-        //         }
-        //     }
-
-        //     nodes.push(HighwayNetworkBaseNode {
-        //         start_node_index,
-        //         start_edge_index,
-        //         end_node_index: unimplemented!(),
-        //         last_edge_index: unimplemented!(),
-        //     });
-        // }
-        // Self {
-        //     max_level,
-        //     nodes,
-        //     highway_nodes,
-        //     edges,
-        // }
+        Self {
+            max_level,
+            nodes,
+            highway_nodes,
+            edges,
+        }
     }
 
     pub fn node(&self, node: NodeId) -> &HighwayNetworkBaseNode {
@@ -142,7 +177,7 @@ impl HighwayNetwork {
         let node = self.node(node);
         let levels = node.end_node_index - node.start_node_index;
 
-        if level as u32 > levels {
+        if level as u32 >= levels {
             return None;
         }
 
@@ -8,19 +8,27 @@ use std::ops::Deref;
 
 pub use directed_graph::*;
 pub use neighbourhood::*;
+pub use overlay::*;
+pub use progress::*;
+pub use workspace::*;
 
 /// Tja
 pub mod directed_graph;
 
 pub mod highway_network;
 pub mod neighbourhood;
+pub mod overlay;
+pub mod progress;
+pub mod workspace;
 
 use serde::Deserialize;
 use serde::Serialize;
 
 mod dijkstra_iterator;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Forward;
+#[derive(Debug, Clone, Copy)]
 pub struct Backward;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
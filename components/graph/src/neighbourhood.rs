@@ -1,10 +1,71 @@
-use crate::{Backward, DirectedNetworkGraph, Forward, NetworkData, NodeId};
+use crate::{
+    Backward, DijkstraWorkspace, DirectedNetworkGraph, Forward, NetworkData, NodeId, Progress,
+};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
 
 pub type ForwardNeighbourhood = Neighbourhood<Forward>;
 pub type BackwardNeighbourhood = Neighbourhood<Backward>;
 
-#[derive(Debug)]
+/// Which nodes are in the neighbourhood of a node, the node itself always is.
+/// The radius is the distance to the farthest node of the neighbourhood, so a node with fewer reachable nodes,
+/// in a small component, has all of them in its neighbourhood.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NeighbourhoodSize {
+    /// The `n` closest nodes.
+    Nodes(usize),
+    /// The nodes within a distance.
+    Distance(f32),
+    /// The `nodes` closest nodes that are within `distance`.
+    Bounded { nodes: usize, distance: f32 },
+}
+
+impl NeighbourhoodSize {
+    /// The maximum number of nodes and the maximum distance of the neighbourhood.
+    fn limits(&self) -> (usize, f32) {
+        match *self {
+            NeighbourhoodSize::Nodes(nodes) => (nodes, f32::INFINITY),
+            NeighbourhoodSize::Distance(distance) => (usize::MAX, distance),
+            NeighbourhoodSize::Bounded { nodes, distance } => (nodes, distance),
+        }
+    }
+
+    /// The nodes of the neighbourhood, with the reachable nodes in order of distance.
+    pub fn members(
+        &self,
+        search: impl Iterator<Item = (NodeId, f32)>,
+    ) -> impl Iterator<Item = (NodeId, f32)> {
+        let (nodes, distance) = self.limits();
+
+        search.take(nodes).take_while(move |(_, x)| *x <= distance)
+    }
+
+    /// The radius of the neighbourhood, with the reachable nodes in order of distance.
+    /// 0 when the neighbourhood is empty.
+    pub fn radius(&self, search: impl Iterator<Item = (NodeId, f32)>) -> f32 {
+        self.members(search).last().map_or(0.0, |(_, x)| x)
+    }
+}
+
+impl From<usize> for NeighbourhoodSize {
+    fn from(nodes: usize) -> Self {
+        NeighbourhoodSize::Nodes(nodes)
+    }
+}
+
+impl Display for NeighbourhoodSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NeighbourhoodSize::Nodes(nodes) => write!(f, "{}", nodes),
+            NeighbourhoodSize::Distance(distance) => write!(f, "d {}", distance),
+            NeighbourhoodSize::Bounded { nodes, distance } => write!(f, "{} d {}", nodes, distance),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Neighbourhood<T>
 where
     T: NeighbourhoodDirection,
@@ -22,21 +83,52 @@ where
         self.radius[node.0 as usize]
     }
 
+    /// The number of nodes of the network.
+    pub fn len(&self) -> usize {
+        self.radius.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.radius.is_empty()
+    }
+
+    /// The radius of the `size` closest nodes of every node.
     pub fn from_network<D: NetworkData>(size: usize, network: &DirectedNetworkGraph<D>) -> Self {
-        T::from_network(size, network)
+        Self::with_size(size.into(), network)
+    }
+
+    pub fn with_size<D: NetworkData>(
+        size: NeighbourhoodSize,
+        network: &DirectedNetworkGraph<D>,
+    ) -> Self {
+        T::from_network(size, network, &Progress::default())
+    }
+
+    /// Like [`Self::with_size`], advances `progress` for every node.
+    /// Returns `None` when `progress` is cancelled.
+    pub fn with_progress<D: NetworkData>(
+        size: NeighbourhoodSize,
+        network: &DirectedNetworkGraph<D>,
+        progress: &Progress,
+    ) -> Option<Self> {
+        let neighbourhood = T::from_network(size, network, progress);
+        (!progress.is_cancelled()).then_some(neighbourhood)
     }
 }
 
 pub trait NeighbourhoodDirection {
     fn find_neighbourhood_radius<D: NetworkData>(
         node: NodeId,
-        size: usize,
+        size: NeighbourhoodSize,
         network: &DirectedNetworkGraph<D>,
-    ) -> Option<f32>;
+        workspace: &mut DijkstraWorkspace,
+    ) -> f32;
 
+    /// The nodes are skipped once `progress` is cancelled.
     fn from_network<D: NetworkData>(
-        size: usize,
+        size: NeighbourhoodSize,
         network: &DirectedNetworkGraph<D>,
+        progress: &Progress,
     ) -> Neighbourhood<Self>
     where
         Self: Sized,
@@ -47,7 +139,19 @@ pub trait NeighbourhoodDirection {
             .nodes()
             .par_iter()
             .enumerate()
-            .map(|(id, _)| Self::find_neighbourhood_radius(id.into(), size, network).unwrap())
+            .map_init(
+                || DijkstraWorkspace::new(network.nodes().len()),
+                |workspace, (id, _)| {
+                    if progress.is_cancelled() {
+                        return 0.0;
+                    }
+
+                    let radius =
+                        Self::find_neighbourhood_radius(id.into(), size, network, workspace);
+                    progress.advance(1);
+                    radius
+                },
+            )
             .collect_into_vec(&mut radius);
 
         Neighbourhood {
@@ -60,27 +164,21 @@ pub trait NeighbourhoodDirection {
 impl NeighbourhoodDirection for Forward {
     fn find_neighbourhood_radius<D: NetworkData>(
         node: NodeId,
-        size: usize,
+        size: NeighbourhoodSize,
         network: &DirectedNetworkGraph<D>,
-    ) -> Option<f32> {
-        network
-            .forward_iterator(node)
-            .take(size)
-            .last()
-            .map(|x| x.1)
+        workspace: &mut DijkstraWorkspace,
+    ) -> f32 {
+        size.radius(network.forward_search(node, workspace))
     }
 }
 
 impl NeighbourhoodDirection for Backward {
     fn find_neighbourhood_radius<D: NetworkData>(
         node: NodeId,
-        size: usize,
+        size: NeighbourhoodSize,
         network: &DirectedNetworkGraph<D>,
-    ) -> Option<f32> {
-        network
-            .backward_iterator(node)
-            .take(size)
-            .last()
-            .map(|x| x.1)
+        workspace: &mut DijkstraWorkspace,
+    ) -> f32 {
+        size.radius(network.backward_search(node, workspace))
     }
 }
@@ -0,0 +1,56 @@
+use crate::{EdgeId, NetworkEdge};
+use std::collections::{HashMap, HashSet};
+
+/// Changes to the edges of a [`crate::DirectedNetworkGraph`] without building it again, like a closed road.
+/// The graph is immutable, the overlay is kept next to it and given to the searches.
+/// An edge is stored at both of its nodes, block both to close it for the forward and the backward search.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EdgeOverlay {
+    blocked: HashSet<EdgeId>,
+    weights: HashMap<EdgeId, f32>,
+}
+
+impl EdgeOverlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn block(&mut self, edge: EdgeId) {
+        self.blocked.insert(edge);
+    }
+
+    pub fn unblock(&mut self, edge: EdgeId) {
+        self.blocked.remove(&edge);
+    }
+
+    pub fn is_blocked(&self, edge: EdgeId) -> bool {
+        self.blocked.contains(&edge)
+    }
+
+    /// Use `weight` instead of the weight of the graph.
+    pub fn set_weight(&mut self, edge: EdgeId, weight: f32) {
+        self.weights.insert(edge, weight);
+    }
+
+    pub fn reset_weight(&mut self, edge: EdgeId) {
+        self.weights.remove(&edge);
+    }
+
+    pub fn clear(&mut self) {
+        self.blocked.clear();
+        self.weights.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocked.is_empty() && self.weights.is_empty()
+    }
+
+    /// The weight of `edge` with the changes of the overlay, `None` when it is blocked.
+    pub fn weight(&self, id: EdgeId, edge: &NetworkEdge) -> Option<f32> {
+        if self.blocked.contains(&id) {
+            return None;
+        }
+
+        Some(self.weights.get(&id).copied().unwrap_or(edge.distance()))
+    }
+}
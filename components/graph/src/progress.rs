@@ -0,0 +1,104 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// A handle to follow and cancel a long computation from another thread.
+/// The computation reports the phase it is in and the number of processed items,
+/// and checks [`Progress::is_cancelled`] to stop early. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    state: Arc<ProgressState>,
+}
+
+#[derive(Debug, Default)]
+struct ProgressState {
+    phase: Mutex<Option<(&'static str, Instant)>>,
+    level: AtomicUsize,
+    done: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+/// The state of a [`Progress`] at one moment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressReport {
+    pub phase: &'static str,
+    pub level: usize,
+    pub done: usize,
+    pub total: usize,
+    pub elapsed: Duration,
+}
+
+impl ProgressReport {
+    /// The processed part of the current phase, between 0 and 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        (self.done as f32 / self.total as f32).min(1.0)
+    }
+
+    /// The remaining time of the current phase, when it continues at the same rate.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+
+        let remaining = self.total.saturating_sub(self.done) as f64 / self.done as f64;
+        Some(self.elapsed.mul_f64(remaining))
+    }
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start `phase` with `total` items to process.
+    pub fn start(&self, phase: &'static str, total: usize) {
+        *self.state.phase.lock().unwrap() = Some((phase, Instant::now()));
+        self.state.done.store(0, Ordering::Relaxed);
+        self.state.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Set the level of the hierarchy that is computed.
+    pub fn set_level(&self, level: usize) {
+        self.state.level.store(level, Ordering::Relaxed);
+    }
+
+    /// Add `count` processed items.
+    pub fn advance(&self, count: usize) {
+        self.state.done.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Set the number of processed items.
+    pub fn set_done(&self, done: usize) {
+        self.state.done.store(done, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The current phase, `None` before the first phase started.
+    pub fn report(&self) -> Option<ProgressReport> {
+        let (phase, start) = (*self.state.phase.lock().unwrap())?;
+
+        Some(ProgressReport {
+            phase,
+            level: self.state.level.load(Ordering::Relaxed),
+            done: self.state.done.load(Ordering::Relaxed),
+            total: self.state.total.load(Ordering::Relaxed),
+            elapsed: start.elapsed(),
+        })
+    }
+}
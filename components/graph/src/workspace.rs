@@ -0,0 +1,241 @@
+use crate::{NodeId, F32};
+
+/// A map from every node of a graph to a value, stored in a dense array.
+/// Clearing is O(1), every entry is stamped with the generation it was inserted in,
+/// entries of an earlier generation are absent. The values are kept, so their allocations can be reused.
+#[derive(Debug, Clone)]
+pub struct NodeMap<T> {
+    generation: u32,
+    stamps: Vec<u32>,
+    values: Vec<T>,
+}
+
+impl<T: Default> NodeMap<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            generation: 1,
+            stamps: vec![0; size],
+            values: (0..size).map(|_| T::default()).collect(),
+        }
+    }
+
+    /// Remove all entries.
+    pub fn clear(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+
+        // Stamp 0 is never current, reset the stamps when the counter wraps.
+        if self.generation == 0 {
+            self.stamps.fill(0);
+            self.generation = 1;
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.stamps[node.0 as usize] == self.generation
+    }
+
+    #[inline]
+    pub fn get(&self, node: NodeId) -> Option<&T> {
+        self.contains(node).then(|| &self.values[node.0 as usize])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, node: NodeId) -> Option<&mut T> {
+        if self.contains(node) {
+            Some(&mut self.values[node.0 as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Insert `value`, returning the previous value when `node` was present.
+    pub fn insert(&mut self, node: NodeId, value: T) -> Option<T> {
+        let present = self.contains(node);
+        self.stamps[node.0 as usize] = self.generation;
+
+        let previous = std::mem::replace(&mut self.values[node.0 as usize], value);
+        present.then_some(previous)
+    }
+
+    pub fn get_or_insert_with<F>(&mut self, node: NodeId, value: F) -> &mut T
+    where
+        F: FnOnce() -> T,
+    {
+        if !self.contains(node) {
+            self.stamps[node.0 as usize] = self.generation;
+            self.values[node.0 as usize] = value();
+        }
+
+        &mut self.values[node.0 as usize]
+    }
+
+    /// Mark `node` as present and return its slot.
+    /// When it was absent the slot still holds the value of an earlier generation,
+    /// which must be reset by the caller, but keeps its allocations.
+    pub fn reuse(&mut self, node: NodeId) -> &mut T {
+        self.stamps[node.0 as usize] = self.generation;
+        &mut self.values[node.0 as usize]
+    }
+
+    pub fn remove(&mut self, node: NodeId) {
+        self.stamps[node.0 as usize] = 0;
+    }
+}
+
+/// A binary min heap of nodes with a key, with at most one entry per node.
+/// The key of a queued node can be decreased, entries with the same key are ordered by node.
+#[derive(Debug, Clone)]
+pub struct AddressableHeap {
+    entries: Vec<(F32, NodeId)>,
+    positions: NodeMap<u32>,
+}
+
+impl AddressableHeap {
+    pub fn new(size: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            positions: NodeMap::new(size),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.positions.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The key of `node` when it is queued.
+    pub fn key(&self, node: NodeId) -> Option<f32> {
+        self.positions
+            .get(node)
+            .map(|position| self.entries[*position as usize].0 .0)
+    }
+
+    /// The queued nodes, in no particular order.
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.entries.iter().map(|(_, node)| *node)
+    }
+
+    /// Queue `node`, or decrease its key when it is already queued with a larger key.
+    /// Returns whether the heap changed.
+    pub fn push(&mut self, node: NodeId, key: f32) -> bool {
+        match self.positions.get(node) {
+            Some(position) => {
+                let position = *position as usize;
+                if key >= self.entries[position].0 .0 {
+                    return false;
+                }
+
+                self.entries[position].0 = F32(key);
+                self.sift_up(position);
+            }
+            None => {
+                self.entries.push((F32(key), node));
+                self.positions.insert(node, (self.entries.len() - 1) as u32);
+                self.sift_up(self.entries.len() - 1);
+            }
+        }
+
+        true
+    }
+
+    pub fn peek(&self) -> Option<(NodeId, f32)> {
+        self.entries.first().map(|(key, node)| (*node, key.0))
+    }
+
+    /// Remove the node with the smallest key.
+    pub fn pop(&mut self) -> Option<(NodeId, f32)> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let last = self.entries.len() - 1;
+        self.swap(0, last);
+        let (key, node) = self.entries.pop().unwrap();
+        self.positions.remove(node);
+
+        if !self.entries.is_empty() {
+            self.sift_down(0);
+        }
+
+        Some((node, key.0))
+    }
+
+    fn sift_up(&mut self, mut position: usize) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if self.entries[parent] <= self.entries[position] {
+                break;
+            }
+
+            self.swap(parent, position);
+            position = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut position: usize) {
+        loop {
+            let left = 2 * position + 1;
+            let right = left + 1;
+
+            let mut smallest = position;
+            if left < self.entries.len() && self.entries[left] < self.entries[smallest] {
+                smallest = left;
+            }
+            if right < self.entries.len() && self.entries[right] < self.entries[smallest] {
+                smallest = right;
+            }
+            if smallest == position {
+                break;
+            }
+
+            self.swap(smallest, position);
+            position = smallest;
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.entries.swap(a, b);
+        self.positions.insert(self.entries[a].1, a as u32);
+        self.positions.insert(self.entries[b].1, b as u32);
+    }
+}
+
+/// The state of a Dijkstra search, that is reused between searches to avoid allocations.
+/// Create one per thread, for example with rayon's `map_init`.
+#[derive(Debug, Clone)]
+pub struct DijkstraWorkspace {
+    pub(crate) heap: AddressableHeap,
+    /// The distance of the settled nodes.
+    pub(crate) settled: NodeMap<f32>,
+}
+
+impl DijkstraWorkspace {
+    /// A workspace for a graph with `size` nodes.
+    pub fn new(size: usize) -> Self {
+        Self {
+            heap: AddressableHeap::new(size),
+            settled: NodeMap::new(size),
+        }
+    }
+
+    /// Start a new search from `start`.
+    pub fn reset(&mut self, start: NodeId) {
+        self.heap.clear();
+        self.settled.clear();
+        self.heap.push(start, 0.0);
+    }
+
+    /// The distance of `node` when it is settled by the current search.
+    pub fn distance(&self, node: NodeId) -> Option<f32> {
+        self.settled.get(node).cloned()
+    }
+}
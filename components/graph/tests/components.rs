@@ -0,0 +1,90 @@
+use graph::{
+    builder::{DefaultEdgeBuilder, DirectedNetworkBuilder},
+    create_network, DijkstraWorkspace, DirectedNetworkGraph, NodeId,
+};
+
+fn create_islands() -> DirectedNetworkGraph<()> {
+    create_network!(
+        0..6,
+        0 => 1; 1.0,
+        1 => 2; 2.0,
+        2 => 0; 3.0,
+        2 => 3; 4.0, // One way to the second island
+        3 => 4; 5.0,
+        4 => 3; 6.0,
+        5 => 6; 7.0 // One way dead end
+    )
+}
+
+#[test]
+fn strongly_connected_components() {
+    let network = create_islands();
+    let components = network.strongly_connected_components();
+
+    assert_eq!(components.sizes(), &[3, 2, 1, 1]);
+    assert_eq!(components.len(), 4);
+
+    for node in 0..3u32 {
+        assert!(components.in_largest(node.into()));
+    }
+    assert_eq!(components.component(3u32.into()), 1);
+    assert_eq!(components.component(4u32.into()), 1);
+    assert_ne!(
+        components.component(5u32.into()),
+        components.component(6u32.into())
+    );
+    assert_eq!(
+        components.nodes(1).collect::<Vec<_>>(),
+        vec![NodeId(3), NodeId(4)]
+    );
+}
+
+#[test]
+fn components_report() {
+    let report = create_islands().strongly_connected_components().to_string();
+
+    assert_eq!(
+        report,
+        "4 components, largest: 3 of 7 nodes (42.86%)\n\
+         size 3: 1 components\n\
+         size 2: 1 components\n\
+         size 1: 2 components\n"
+    );
+}
+
+#[test]
+fn largest_component() {
+    let network = create_islands();
+    let largest = network.largest_component();
+
+    assert_eq!(largest.nodes().len(), 3);
+    assert_eq!(largest.edges().len(), 6);
+
+    let forward = largest
+        .forward_search(0u32.into(), &mut DijkstraWorkspace::new(3))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        forward,
+        vec![(0u32.into(), 0.0), (1u32.into(), 1.0), (2u32.into(), 3.0)]
+    );
+
+    let components = largest.strongly_connected_components();
+    assert_eq!(components.sizes(), &[3]);
+}
+
+#[test]
+fn long_cycle_does_not_overflow() {
+    let size = 100_000;
+    let mut builder = DirectedNetworkBuilder::<usize, DefaultEdgeBuilder>::new();
+
+    for x in 0..size {
+        let source = builder.add_node(x);
+        let target = builder.add_node((x + 1) % size);
+        builder.add_edge(DefaultEdgeBuilder::forward(source, target, x, 1.0));
+    }
+
+    let network = builder.build::<()>();
+    let components = network.strongly_connected_components();
+
+    assert_eq!(components.sizes(), &[size]);
+}
@@ -1,4 +1,4 @@
-use graph::create_network;
+use graph::{create_network, DijkstraWorkspace};
 
 #[test]
 fn forward_dijkstra_test() {
@@ -15,8 +15,13 @@ fn forward_dijkstra_test() {
         5 => 4; 5.0 // F => E
     );
 
+    let mut workspace = DijkstraWorkspace::new(network.nodes().len());
     let forward = (0..=5u32)
-        .map(|i| network.forward_iterator(i.into()).collect::<Vec<_>>())
+        .map(|i| {
+            network
+                .forward_search(i.into(), &mut workspace)
+                .collect::<Vec<_>>()
+        })
         .collect::<Vec<_>>();
 
     assert_eq!(
@@ -74,8 +79,13 @@ fn backward_dijkstra_test() {
         5 => 4; 5.0 // F => E
     );
 
+    let mut workspace = DijkstraWorkspace::new(network.nodes().len());
     let backward = (0..=5u32)
-        .map(|i| network.backward_iterator(i.into()).collect::<Vec<_>>())
+        .map(|i| {
+            network
+                .backward_search(i.into(), &mut workspace)
+                .collect::<Vec<_>>()
+        })
         .collect::<Vec<_>>();
 
     assert_eq!(backward[0], vec![(0u32.into(), 0.0f32),]);
@@ -1,4 +1,6 @@
-use graph::{create_network, BackwardNeighbourhood, ForwardNeighbourhood, NodeId};
+use graph::{
+    create_network, BackwardNeighbourhood, ForwardNeighbourhood, NeighbourhoodSize, NodeId,
+};
 
 #[test]
 fn forward_test() {
@@ -25,8 +27,6 @@ fn forward_test() {
     assert_eq!(forward.radius(NodeId(5)), 5.0);
 }
 
-
-
 #[test]
 fn backward_test() {
     // https://www.baeldung.com/wp-content/uploads/2017/01/initial-graph.png
@@ -51,3 +51,56 @@ fn backward_test() {
     assert_eq!(forward.radius(NodeId(4)), 5.0);
     assert_eq!(forward.radius(NodeId(5)), 6.0);
 }
+
+#[test]
+fn neighbourhood_sizes() {
+    // A path 0 -> 1 -> 2 -> 3, node 3 reaches no other node.
+    let network = create_network!(
+        0..3,
+        0 => 1; 2.0,
+        1 => 2; 3.0,
+        2 => 3; 4.0
+    );
+    let radius = |size| {
+        let forward = ForwardNeighbourhood::with_size(size, &network);
+        (0..4)
+            .map(|x| forward.radius(NodeId(x)))
+            .collect::<Vec<_>>()
+    };
+
+    // Fewer reachable nodes than the size, the neighbourhood is all reachable nodes.
+    assert_eq!(
+        radius(NeighbourhoodSize::Nodes(3)),
+        vec![5.0, 7.0, 4.0, 0.0]
+    );
+    assert_eq!(radius(NeighbourhoodSize::Nodes(0)), vec![0.0; 4]);
+    assert_eq!(
+        radius(NeighbourhoodSize::Distance(5.0)),
+        vec![5.0, 3.0, 4.0, 0.0]
+    );
+    assert_eq!(
+        radius(NeighbourhoodSize::Bounded {
+            nodes: 2,
+            distance: 3.0
+        }),
+        vec![2.0, 3.0, 0.0, 0.0]
+    );
+}
+
+#[test]
+fn serialize_neighbourhood() {
+    let network = create_network!(
+        0..2,
+        0 => 1; 2.0,
+        1 => 2; 3.0,
+        2 => 0; 4.0
+    );
+    let backward = BackwardNeighbourhood::from_network(2, &network);
+    let bytes = bincode::serialize(&backward).unwrap();
+    let read: BackwardNeighbourhood = bincode::deserialize(&bytes).unwrap();
+
+    assert_eq!(read.len(), 3);
+    for node in (0..3).map(NodeId) {
+        assert_eq!(read.radius(node), backward.radius(node));
+    }
+}
@@ -0,0 +1,70 @@
+use graph::{create_network, DijkstraWorkspace, DirectedNetworkGraph, EdgeId, EdgeOverlay, NodeId};
+
+/// The edge between `node` and `target` that is stored at `node`.
+fn edge(network: &DirectedNetworkGraph, node: u32, target: u32) -> EdgeId {
+    network
+        .out_edges(NodeId(node))
+        .chain(network.in_edges(NodeId(node)))
+        .find(|(_, edge)| edge.target() == NodeId(target))
+        .map(|(id, _)| id)
+        .unwrap()
+}
+
+#[test]
+fn overlay_search() {
+    // https://www.baeldung.com/wp-content/uploads/2017/01/initial-graph.png
+    let network = create_network!(
+        0..5,
+        0 => 1; 10.0, // A => B
+        0 => 2; 15.0, // A => C
+        1 => 3; 12.0, // B => D
+        1 => 5; 15.0, // B => F
+        2 => 4; 10.0, // C => E
+        3 => 4; 2.0, // D => E
+        3 => 5; 1.0, // D => F
+        5 => 4; 5.0 // F => E
+    );
+
+    let mut workspace = DijkstraWorkspace::new(network.nodes().len());
+    let mut overlay = EdgeOverlay::new();
+    assert!(overlay.is_empty());
+
+    // Close A => B at both ends.
+    overlay.block(edge(&network, 0, 1));
+    overlay.block(edge(&network, 1, 0));
+    let forward = network
+        .forward_search(NodeId(0), &mut workspace)
+        .with_overlay(&overlay)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        forward,
+        vec![(NodeId(0), 0.0), (NodeId(2), 15.0), (NodeId(4), 25.0)]
+    );
+    let backward = network
+        .backward_search(NodeId(1), &mut workspace)
+        .with_overlay(&overlay)
+        .collect::<Vec<_>>();
+    assert_eq!(backward, vec![(NodeId(1), 0.0)]);
+
+    overlay.set_weight(edge(&network, 2, 4), 1.0);
+    let forward = network
+        .forward_search(NodeId(0), &mut workspace)
+        .with_overlay(&overlay)
+        .collect::<Vec<_>>();
+    assert_eq!(forward.last(), Some(&(NodeId(4), 16.0)));
+
+    // Without the overlay the graph is unchanged.
+    overlay.clear();
+    assert!(overlay.is_empty());
+    let forward = network
+        .forward_search(NodeId(0), &mut workspace)
+        .with_overlay(&overlay)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        forward,
+        network
+            .forward_search(NodeId(0), &mut workspace)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(forward.last(), Some(&(NodeId(4), 24.0)));
+}
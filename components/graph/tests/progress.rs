@@ -0,0 +1,30 @@
+use graph::Progress;
+
+#[test]
+fn progress_report() {
+    let progress = Progress::new();
+    assert_eq!(progress.report(), None);
+
+    progress.set_level(2);
+    progress.start("Edges", 10);
+    let report = progress.report().unwrap();
+    assert_eq!((report.phase, report.level), ("Edges", 2));
+    assert_eq!(report.fraction(), 0.0);
+    assert_eq!(report.eta(), None);
+
+    // Clones share the state.
+    let worker = progress.clone();
+    worker.advance(4);
+    worker.advance(1);
+    let report = progress.report().unwrap();
+    assert_eq!(report.done, 5);
+    assert_eq!(report.fraction(), 0.5);
+    assert!(report.eta().is_some());
+
+    progress.start("Contraction", 4);
+    assert_eq!(progress.report().unwrap().done, 0);
+
+    assert!(!worker.is_cancelled());
+    progress.cancel();
+    assert!(worker.is_cancelled());
+}
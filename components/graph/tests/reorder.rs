@@ -0,0 +1,93 @@
+use graph::{create_network, DijkstraWorkspace, DirectedNetworkGraph, EdgeId, NodeId};
+
+fn create_grid() -> DirectedNetworkGraph<()> {
+    // 0 - 1 - 2
+    // |   |   |
+    // 3 - 4 - 5
+    create_network!(
+        0..5,
+        0 => 1; 1.0,
+        1 => 0; 1.0,
+        1 => 2; 2.0,
+        2 => 1; 2.0,
+        0 => 3; 3.0,
+        3 => 0; 3.0,
+        1 => 4; 4.0,
+        2 => 5; 5.0,
+        5 => 2; 5.0,
+        3 => 4; 6.0,
+        4 => 5; 7.0
+    )
+}
+
+#[test]
+fn reorder_keeps_distances() {
+    let network = create_grid();
+    let order = [5u32, 3, 1, 0, 4, 2].map(NodeId).to_vec();
+    let (reordered, permutation) = network.reorder(&order);
+
+    assert_eq!(reordered.validate(), Ok(()));
+    assert_eq!(reordered.nodes().len(), network.nodes().len());
+    assert_eq!(reordered.edges().len(), network.edges().len());
+
+    let mut workspace = DijkstraWorkspace::new(network.nodes().len());
+    for (new, old) in order.iter().enumerate() {
+        assert_eq!(permutation.node(*old), NodeId::from(new));
+
+        let mut expected = network
+            .forward_search(*old, &mut workspace)
+            .map(|(node, distance)| (permutation.node(node), distance))
+            .collect::<Vec<_>>();
+        let mut distances = reordered
+            .forward_search(NodeId::from(new), &mut workspace)
+            .collect::<Vec<_>>();
+
+        expected.sort_by_key(|x| x.0);
+        distances.sort_by_key(|x| x.0);
+        assert_eq!(distances, expected);
+    }
+
+    for id in 0..network.edges().len() {
+        let edge = network.edge(EdgeId::from(id));
+        let moved = reordered.edge(permutation.edge(EdgeId::from(id)));
+
+        assert_eq!(moved.target(), permutation.node(edge.target()));
+        assert_eq!(moved.distance(), edge.distance());
+        assert_eq!(moved.direction(), edge.direction());
+    }
+}
+
+#[test]
+fn bfs_order() {
+    let network = create_grid();
+
+    assert_eq!(
+        network.bfs_order(),
+        [0u32, 1, 3, 2, 4, 5].map(NodeId).to_vec()
+    );
+}
+
+#[test]
+fn hilbert_order() {
+    let network = create_grid();
+    let locations = [
+        [0.0, 1.0],
+        [1.0, 1.0],
+        [2.0, 1.0],
+        [0.0, 0.0],
+        [1.0, 0.0],
+        [2.0, 0.0],
+    ];
+
+    let order = network.hilbert_order(|node| locations[node.0 as usize]);
+
+    assert_eq!(order, [3u32, 4, 1, 0, 2, 5].map(NodeId).to_vec());
+}
+
+#[test]
+#[should_panic(expected = "Node 1 is repeated")]
+fn reorder_rejects_repeated_nodes() {
+    let order = [0u32, 1, 1, 2, 3, 4].map(NodeId).to_vec();
+
+    create_grid().reorder(&order);
+}
@@ -0,0 +1,168 @@
+use graph::{
+    builder::EdgeDirection, create_network, DirectedNetworkGraph, EdgeId, NetworkEdge, NetworkNode,
+    NodeId, ValidationError,
+};
+
+// A -> B, A <-> C
+fn nodes() -> Vec<NetworkNode> {
+    vec![
+        NetworkNode::new(0, 2),
+        NetworkNode::new(2, 3),
+        NetworkNode::new(3, 4),
+    ]
+}
+
+fn edges() -> Vec<NetworkEdge> {
+    vec![
+        NetworkEdge::new(0, 1u32.into(), 10.0, EdgeDirection::Forward), // A -> B
+        NetworkEdge::new(1, 2u32.into(), 5.0, EdgeDirection::Both),     // A <-> C
+        NetworkEdge::new(2, 0u32.into(), 10.0, EdgeDirection::Backward), // A <- B
+        NetworkEdge::new(3, 0u32.into(), 5.0, EdgeDirection::Both),     // C <-> A
+    ]
+}
+
+fn errors(nodes: Vec<NetworkNode>, edges: Vec<NetworkEdge>) -> Vec<ValidationError> {
+    DirectedNetworkGraph::new(nodes, edges, ())
+        .validate()
+        .unwrap_err()
+}
+
+#[test]
+fn valid_graphs() {
+    assert_eq!(
+        DirectedNetworkGraph::new(nodes(), edges(), ()).validate(),
+        Ok(())
+    );
+
+    let network = create_network!(
+        0..3,
+        0 => 1; 1.0,
+        1 => 0; 1.0,
+        1 => 2; 2.0,
+        3 => 3; 0.0
+    );
+    assert_eq!(network.validate(), Ok(()));
+}
+
+#[test]
+fn invalid_weight() {
+    let mut edges = edges();
+    edges[1] = NetworkEdge::new(1, 2u32.into(), f32::NAN, EdgeDirection::Both);
+    edges[3] = NetworkEdge::new(3, 0u32.into(), f32::NAN, EdgeDirection::Both);
+
+    let errors = errors(nodes(), edges);
+
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|x| matches!(
+        x,
+        ValidationError::InvalidWeight { weight, .. } if weight.is_nan()
+    )));
+}
+
+#[test]
+fn negative_weight() {
+    let mut edges = edges();
+    edges[0] = NetworkEdge::new(0, 1u32.into(), -10.0, EdgeDirection::Forward);
+    edges[2] = NetworkEdge::new(2, 0u32.into(), -10.0, EdgeDirection::Backward);
+
+    assert_eq!(
+        errors(nodes(), edges),
+        vec![
+            ValidationError::InvalidWeight {
+                edge: EdgeId(0),
+                weight: -10.0
+            },
+            ValidationError::InvalidWeight {
+                edge: EdgeId(2),
+                weight: -10.0
+            },
+        ]
+    );
+}
+
+#[test]
+fn missing_mirror() {
+    let mut edges = edges();
+    edges[2] = NetworkEdge::new(2, 0u32.into(), 12.0, EdgeDirection::Backward);
+
+    assert_eq!(
+        errors(nodes(), edges),
+        vec![
+            ValidationError::MissingMirror {
+                node: NodeId(0),
+                edge: EdgeId(0),
+                target: NodeId(1),
+                direction: EdgeDirection::Forward
+            },
+            ValidationError::MissingMirror {
+                node: NodeId(1),
+                edge: EdgeId(2),
+                target: NodeId(0),
+                direction: EdgeDirection::Backward
+            },
+        ]
+    );
+}
+
+#[test]
+fn unsorted_edges() {
+    let mut edges = edges();
+    edges.swap(0, 1);
+    edges[0].edge_id = 0;
+    edges[1].edge_id = 1;
+
+    assert_eq!(
+        errors(nodes(), edges),
+        vec![ValidationError::UnsortedEdges { node: NodeId(0) }]
+    );
+}
+
+#[test]
+fn edge_ranges() {
+    let nodes = vec![
+        NetworkNode::new(0, 2),
+        NetworkNode::new(1, 3),
+        NetworkNode::new(4, 3),
+    ];
+
+    let errors = errors(nodes, edges());
+
+    assert!(errors.contains(&ValidationError::InvalidEdgeRange {
+        node: NodeId(2),
+        start: 4,
+        last: 3
+    }));
+    assert!(errors.contains(&ValidationError::OverlappingEdgeRange {
+        node: NodeId(1),
+        other: NodeId(0)
+    }));
+    assert!(errors.contains(&ValidationError::UnusedEdge { edge: EdgeId(3) }));
+}
+
+#[test]
+fn invalid_target_and_edge_id() {
+    let mut edges = edges();
+    edges[3] = NetworkEdge::new(7, 9u32.into(), 5.0, EdgeDirection::Both);
+
+    let errors = errors(nodes(), edges);
+
+    assert!(errors.contains(&ValidationError::EdgeIdMismatch {
+        edge: EdgeId(3),
+        edge_id: 7
+    }));
+    assert!(errors.contains(&ValidationError::InvalidTarget {
+        edge: EdgeId(3),
+        target: NodeId(9)
+    }));
+}
+
+#[test]
+#[should_panic(expected = "Invalid graph, 2 errors")]
+#[cfg(debug_assertions)]
+fn debug_validate_panics() {
+    let mut edges = edges();
+    edges[1] = NetworkEdge::new(1, 2u32.into(), f32::INFINITY, EdgeDirection::Both);
+    edges[3] = NetworkEdge::new(3, 0u32.into(), f32::INFINITY, EdgeDirection::Both);
+
+    DirectedNetworkGraph::new(nodes(), edges, ()).debug_validate();
+}
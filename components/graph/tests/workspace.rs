@@ -0,0 +1,90 @@
+use graph::{create_network, AddressableHeap, DijkstraWorkspace, NodeId, NodeMap};
+
+#[test]
+fn node_map_clear() {
+    let mut map = NodeMap::<Vec<u32>>::new(4);
+
+    map.insert(NodeId(1), vec![1, 2]);
+    assert_eq!(map.get(NodeId(1)), Some(&vec![1, 2]));
+    assert!(!map.contains(NodeId(2)));
+
+    map.clear();
+    assert_eq!(map.get(NodeId(1)), None);
+
+    // The value of the earlier generation is kept, with its allocation.
+    let slot = map.reuse(NodeId(1));
+    assert_eq!(slot, &vec![1, 2]);
+    slot.clear();
+    assert_eq!(map.get(NodeId(1)), Some(&vec![]));
+
+    assert_eq!(*map.get_or_insert_with(NodeId(3), || vec![3]), vec![3]);
+    assert_eq!(*map.get_or_insert_with(NodeId(3), || vec![4]), vec![3]);
+    assert_eq!(map.insert(NodeId(3), vec![5]), Some(vec![3]));
+}
+
+#[test]
+fn heap_decrease_key() {
+    let mut heap = AddressableHeap::new(5);
+
+    assert!(heap.push(NodeId(0), 5.0));
+    assert!(heap.push(NodeId(1), 3.0));
+    assert!(heap.push(NodeId(2), 4.0));
+    assert!(heap.push(NodeId(3), 3.0));
+    assert!(!heap.push(NodeId(2), 6.0));
+    assert!(heap.push(NodeId(0), 1.0));
+
+    assert_eq!(heap.len(), 4);
+    assert_eq!(heap.key(NodeId(0)), Some(1.0));
+
+    let order = std::iter::from_fn(|| heap.pop()).collect::<Vec<_>>();
+    assert_eq!(
+        order,
+        vec![
+            (NodeId(0), 1.0),
+            (NodeId(1), 3.0),
+            (NodeId(3), 3.0),
+            (NodeId(2), 4.0)
+        ]
+    );
+    assert!(heap.is_empty());
+    assert_eq!(heap.key(NodeId(0)), None);
+}
+
+#[test]
+fn reused_workspace() {
+    let network = create_network!(
+        0..5,
+        0 => 1; 10.0,
+        0 => 2; 15.0,
+        1 => 3; 12.0,
+        1 => 5; 15.0,
+        2 => 4; 10.0,
+        3 => 4; 2.0,
+        3 => 5; 1.0,
+        5 => 4; 5.0
+    );
+    let mut workspace = DijkstraWorkspace::new(network.nodes().len());
+
+    for node in 0..=5u32 {
+        let forward = network
+            .forward_search(node.into(), &mut workspace)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            forward,
+            network
+                .forward_search(node.into(), &mut DijkstraWorkspace::new(6))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(workspace.distance(node.into()), Some(0.0));
+
+        let backward = network
+            .backward_search(node.into(), &mut workspace)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            backward,
+            network
+                .backward_search(node.into(), &mut DijkstraWorkspace::new(6))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use super::intermediate_network::{IntermediateNetwork, ShortcutLength};
+use graph::NodeId;
+
+/// A node of the network in phase 2, for which a [`ContractionPolicy`] decides whether it is bypassed.
+pub struct Candidate<'a> {
+    network: &'a IntermediateNetwork,
+    node: NodeId,
+}
+
+impl<'a> Candidate<'a> {
+    pub(crate) fn new(network: &'a IntermediateNetwork, node: NodeId) -> Self {
+        Self { network, node }
+    }
+
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
+    pub fn in_degree(&self) -> usize {
+        self.network
+            .in_edges(self.node)
+            .map(|x| x.len())
+            .unwrap_or_default()
+    }
+
+    pub fn out_degree(&self) -> usize {
+        self.network
+            .out_edges(self.node)
+            .map(|x| x.len())
+            .unwrap_or_default()
+    }
+
+    /// The length of every shortcut that bypassing the node adds.
+    pub fn shortcuts(&self) -> impl Iterator<Item = ShortcutLength> + 'a {
+        self.network.bypass_lengths(self.node)
+    }
+}
+
+/// Decides which nodes are bypassed by shortcuts while computing the core network.
+/// The nodes that are not bypassed form the next layer.
+pub trait ContractionPolicy: Send + Sync {
+    fn bypass(&self, candidate: &Candidate) -> bool;
+}
+
+/// Bypass a node when it has fewer possible shortcuts, `in * out`,
+/// than `contraction_factor` times its number of edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContractionFactor {
+    pub contraction_factor: f32,
+}
+
+impl ContractionFactor {
+    pub fn new(contraction_factor: f32) -> Self {
+        Self { contraction_factor }
+    }
+}
+
+impl Default for ContractionFactor {
+    fn default() -> Self {
+        Self::new(2.0)
+    }
+}
+
+impl ContractionPolicy for ContractionFactor {
+    fn bypass(&self, candidate: &Candidate) -> bool {
+        let in_edges = candidate.in_degree() as f32;
+        let out_edges = candidate.out_degree() as f32;
+
+        in_edges * out_edges < (in_edges + out_edges) * self.contraction_factor
+    }
+}
+
+/// Bypass a node when the shortcuts that are added, minus the edges that are removed,
+/// is at most `max_difference`, and no shortcut consists of more than `max_hops` edges of the lower layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgeDifference {
+    pub max_difference: i64,
+    pub max_hops: u32,
+}
+
+impl Default for EdgeDifference {
+    fn default() -> Self {
+        Self {
+            max_difference: 0,
+            max_hops: 10,
+        }
+    }
+}
+
+impl ContractionPolicy for EdgeDifference {
+    fn bypass(&self, candidate: &Candidate) -> bool {
+        let mut shortcuts = 0;
+        for length in candidate.shortcuts() {
+            if length.edges > self.max_hops {
+                return false;
+            }
+            shortcuts += 1;
+        }
+
+        let removed = (candidate.in_degree() + candidate.out_degree()) as i64;
+        shortcuts - removed <= self.max_difference
+    }
+}
+
+/// The [`ContractionFactor`] rule, but keep a node when a shortcut would consist of more than `max_roads` roads.
+/// This limits the work of unpacking a route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnpackLimit {
+    pub contraction_factor: f32,
+    pub max_roads: u32,
+}
+
+impl Default for UnpackLimit {
+    fn default() -> Self {
+        Self {
+            contraction_factor: 2.0,
+            max_roads: 500,
+        }
+    }
+}
+
+impl ContractionPolicy for UnpackLimit {
+    fn bypass(&self, candidate: &Candidate) -> bool {
+        ContractionFactor::new(self.contraction_factor).bypass(candidate)
+            && candidate.shortcuts().all(|x| x.roads <= self.max_roads)
+    }
+}
+
+/// The [`ContractionFactor`] rule, but keep a node when the edge lists of its shortcuts,
+/// the `ShortcutState` of the layer edges, would grow by more than `max_growth` edges together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ListGrowth {
+    pub contraction_factor: f32,
+    pub max_growth: u32,
+}
+
+impl Default for ListGrowth {
+    fn default() -> Self {
+        Self {
+            contraction_factor: 2.0,
+            max_growth: 64,
+        }
+    }
+}
+
+impl ContractionPolicy for ListGrowth {
+    fn bypass(&self, candidate: &Candidate) -> bool {
+        ContractionFactor::new(self.contraction_factor).bypass(candidate)
+            && candidate.shortcuts().map(|x| x.edges).sum::<u32>() <= self.max_growth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Candidate, ContractionFactor, ContractionPolicy, EdgeDifference, ListGrowth, UnpackLimit,
+    };
+    use crate::generation::{
+        core::core_network_with_patch,
+        intermediate_network::{IntermediateData, IntermediateNetwork},
+    };
+    use graph::{DirectedNetworkGraph, EdgeId, NodeId, Progress, ShortcutState};
+
+    /// Node 1 between node 0 and 2, in both directions. The road from 0 to 1 consists of 3 roads.
+    fn create_junction() -> IntermediateNetwork {
+        let mut network = IntermediateNetwork::with_size(3);
+        network.add_lower_edge(
+            NodeId(0),
+            NodeId(1),
+            1.0,
+            0,
+            ShortcutState::Shortcut(vec![10, 11, 12]),
+        );
+        network.add_lower_edge(NodeId(1), NodeId(0), 1.0, 1, ShortcutState::Single(13));
+        network.add_lower_edge(NodeId(1), NodeId(2), 1.0, 2, ShortcutState::Single(14));
+        network.add_lower_edge(NodeId(2), NodeId(1), 1.0, 3, ShortcutState::Single(15));
+
+        network
+    }
+
+    /// Hub 0 and hub 5 with four two way roads between them, over the villages 1 to 4.
+    /// The road from hub 0 to a village consists of 2 roads, from a village to hub 5 of 1 road.
+    fn create_villages() -> IntermediateNetwork {
+        let mut network = IntermediateNetwork::with_size(6);
+        for village in 1..5 {
+            let edge = village * 4;
+            let road = village as usize * 10;
+            let (hub, village) = (NodeId(0), NodeId(village));
+            let roads = ShortcutState::Shortcut(vec![road, road + 1]);
+            network.add_lower_edge(hub, village, 1.0, edge, roads);
+            let roads = ShortcutState::Shortcut(vec![road + 1, road]);
+            network.add_lower_edge(village, hub, 1.0, edge + 1, roads);
+
+            let hub = NodeId(5);
+            network.add_lower_edge(village, hub, 1.0, edge + 2, ShortcutState::Single(road + 2));
+            network.add_lower_edge(hub, village, 1.0, edge + 3, ShortcutState::Single(road + 2));
+        }
+
+        network
+    }
+
+    #[test]
+    fn policies() {
+        let network = create_junction();
+        let candidate = Candidate::new(&network, NodeId(1));
+
+        // Shortcuts 0 -> 2 of 4 roads and 2 -> 0 of 2 roads, both of 2 edges.
+        let mut lengths = candidate
+            .shortcuts()
+            .map(|x| (x.edges, x.roads))
+            .collect::<Vec<_>>();
+        lengths.sort();
+        assert_eq!(lengths, vec![(2, 2), (2, 4)]);
+
+        assert!(ContractionFactor::new(2.0).bypass(&candidate));
+        assert!(!ContractionFactor::new(0.9).bypass(&candidate));
+
+        let edge_difference = EdgeDifference {
+            max_difference: 0,
+            max_hops: 2,
+        };
+        assert!(edge_difference.bypass(&candidate));
+        assert!(!EdgeDifference {
+            max_hops: 1,
+            ..edge_difference
+        }
+        .bypass(&candidate));
+        assert!(!EdgeDifference {
+            max_difference: -3,
+            ..edge_difference
+        }
+        .bypass(&candidate));
+
+        let unpack_limit = UnpackLimit {
+            contraction_factor: 2.0,
+            max_roads: 4,
+        };
+        assert!(unpack_limit.bypass(&candidate));
+        assert!(!UnpackLimit {
+            max_roads: 3,
+            ..unpack_limit
+        }
+        .bypass(&candidate));
+
+        let list_growth = ListGrowth {
+            contraction_factor: 2.0,
+            max_growth: 4,
+        };
+        assert!(list_growth.bypass(&candidate));
+        assert!(!ListGrowth {
+            max_growth: 3,
+            ..list_growth
+        }
+        .bypass(&candidate));
+    }
+
+    #[test]
+    fn policies_keep_other_nodes() {
+        let core = |policy: &dyn ContractionPolicy| {
+            let core = core_network_with_patch(create_villages(), policy, &Progress::default());
+            DirectedNetworkGraph::<IntermediateData>::from(core.unwrap())
+        };
+
+        // The villages have two neighbours and are bypassed, then the hubs are left with one.
+        let layer = core(&ContractionFactor::new(2.0));
+        assert_eq!(layer.nodes().len(), 0);
+
+        // A shortcut over a village consists of 3 roads and 2 edges, a hub has 4 neighbours.
+        let unpack_limit = UnpackLimit {
+            contraction_factor: 2.0,
+            max_roads: 2,
+        };
+        let edge_difference = EdgeDifference {
+            max_difference: 0,
+            max_hops: 1,
+        };
+        for policy in [&unpack_limit as &dyn ContractionPolicy, &edge_difference] {
+            let layer = core(policy);
+            assert_eq!(layer.validate(), Ok(()));
+            assert_eq!(layer.nodes().len(), 6);
+            // Every road to a village is one edge in both directions, stored at both ends.
+            assert_eq!(layer.edges().len(), 16);
+            for edge in (0..layer.edges().len()).map(EdgeId::from) {
+                let lower = layer.data.shortcut_edges(edge);
+                assert!(matches!(lower, ShortcutState::Single(_)));
+            }
+        }
+    }
+}
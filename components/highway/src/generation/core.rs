@@ -1,75 +1,158 @@
-use super::intermediate_network::IntermediateNetwork;
-use graph::NodeId;
-use std::{
-    collections::{HashSet, VecDeque},
-    hash::Hash,
+use super::{
+    contraction::{Candidate, ContractionPolicy},
+    intermediate_network::IntermediateNetwork,
 };
+use graph::{NodeId, Progress};
+use rayon::prelude::*;
 
-pub(crate) fn core_network_with_patch(
+/// Bypass the nodes the policy selects, in rounds.
+/// Every round the policy is asked for all candidates in parallel, then an independent set of the selected nodes is bypassed:
+/// of two neighbouring nodes only the one with the lowest [`priority`] is bypassed, the other is a candidate again in the next round.
+/// The neighbours of bypassed nodes are candidates in the next round as well.
+/// The decisions only depend on the network of the previous round, so the result does not depend on the number of threads.
+/// Returns `None` when `progress` is cancelled.
+pub(crate) fn core_network_with_patch<P: ContractionPolicy + ?Sized>(
     mut intermediate_network: IntermediateNetwork,
-    contraction_factor: f32,
-) -> IntermediateNetwork {
-    let nodes = {
-        let mut n = intermediate_network.nodes();
-        n.sort();
-        n
-    };
+    policy: &P,
+    progress: &Progress,
+) -> Option<IntermediateNetwork> {
+    let mut candidates = intermediate_network.nodes();
+    let mut selected = vec![false; intermediate_network.node_size()];
+
+    // The candidates can grow again, the progress is the part of the nodes that is no candidate.
+    let total = candidates.len();
+    progress.start("Contraction", total);
+
+    while !candidates.is_empty() {
+        if progress.is_cancelled() {
+            return None;
+        }
+        progress.set_done(total.saturating_sub(candidates.len()));
+
+        let network = &intermediate_network;
+        let bypass = candidates
+            .par_iter()
+            .copied()
+            .filter(|node| policy.bypass(&Candidate::new(network, *node)))
+            .collect::<Vec<_>>();
+
+        for node in bypass.iter() {
+            selected[node.0 as usize] = true;
+        }
+
+        let (independent, deferred): (Vec<_>, Vec<_>) =
+            bypass.par_iter().copied().partition(|node| {
+                network
+                    .neighbours(*node)
+                    .filter(|x| x != node && selected[x.0 as usize])
+                    .all(|x| priority(*node) < priority(x))
+            });
+
+        let bypasses = independent
+            .par_iter()
+            .map(|node| network.plan_bypass(*node))
+            .collect::<Vec<_>>();
 
-    let mut queue = HashNodeQueue::<NodeId>::from_vec(&nodes);
-
-    while let Some(node) = queue.pop_front() {
-        let out_edges = &intermediate_network
-            .out_edges(node)
-            .map(|x| x.len() as f32)
-            .unwrap_or_default();
-        let in_edges = &intermediate_network
-            .in_edges(node)
-            .map(|x| x.len() as f32)
-            .unwrap_or_default();
-
-        let short_cuts = (out_edges * in_edges) as f32;
-        let contraction = (out_edges + in_edges) * contraction_factor;
-
-        if short_cuts < contraction {
-            let mut touched = intermediate_network.bypass(node);
-            touched.sort();
-            for touched in touched {
-                queue.push_back(touched);
-            }
+        for node in bypass {
+            selected[node.0 as usize] = false;
         }
+
+        candidates = deferred;
+        for bypass in bypasses {
+            candidates.extend(intermediate_network.apply_bypass(bypass));
+        }
+        candidates.sort();
+        candidates.dedup();
     }
 
-    intermediate_network
+    Some(intermediate_network)
 }
 
-#[derive(Debug, Default, Clone)]
-struct HashNodeQueue<T: Hash + Eq> {
-    queue: VecDeque<T>,
-    seen: HashSet<T>,
+/// The order in which neighbouring nodes are bypassed, a hash of the node to spread the nodes of a round over the network.
+fn priority(node: NodeId) -> (u64, NodeId) {
+    // The finalizer of SplitMix64.
+    let mut x = node.0 as u64;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+    (x ^ (x >> 31), node)
 }
 
-impl<T: Hash + Eq + Copy> HashNodeQueue<T> {
-    fn from_vec(items: &[T]) -> Self {
-        let queue = VecDeque::from_iter(items.iter().cloned());
-        let mut seen = HashSet::with_capacity(items.len());
-        seen.extend(items.iter().cloned());
+#[cfg(test)]
+mod tests {
+    use super::core_network_with_patch;
+    use crate::generation::{
+        contraction::{Candidate, ContractionPolicy},
+        generate_with_threads,
+        intermediate_network::{IntermediateData, IntermediateNetwork},
+    };
+    use graph::{
+        builder::EdgeDirection, DirectedNetworkGraph, EdgeId, NetworkData, NodeId, Progress,
+        ShortcutState,
+    };
 
-        HashNodeQueue { queue, seen }
-    }
-    fn contains(&self, value: &T) -> bool {
-        self.seen.contains(value)
-    }
+    /// Bypass the given nodes.
+    struct Nodes(Vec<NodeId>);
 
-    fn pop_front(&mut self) -> Option<T> {
-        let value = self.queue.pop_front()?;
-        self.seen.remove(&value);
-        Some(value)
+    impl ContractionPolicy for Nodes {
+        fn bypass(&self, candidate: &Candidate) -> bool {
+            self.0.contains(&candidate.node())
+        }
     }
 
-    fn push_back(&mut self, value: T) {
-        if !self.contains(&value) {
-            self.seen.insert(value);
-            self.queue.push_back(value);
+    /// The two way road 0 - 1 - 2 - 3, every edge is a road of its own.
+    fn create_road() -> IntermediateNetwork {
+        let mut network = IntermediateNetwork::with_size(4);
+        for node in 0..3 {
+            let (a, b) = (NodeId(node), NodeId(node + 1));
+            let road = ShortcutState::Single(10 + node as usize);
+            network.add_lower_edge(a, b, 1.0 + node as f32, 2 * node, road.clone());
+            network.add_lower_edge(b, a, 1.0 + node as f32, 2 * node + 1, road);
         }
+
+        network
+    }
+
+    #[test]
+    fn neighbours_are_bypassed_in_turns() {
+        // Node 1 and 2 are selected in the same round, only one of them can be bypassed at a time.
+        let policy = Nodes(vec![NodeId(1), NodeId(2)]);
+
+        let layers = [1, 4].map(|threads| {
+            generate_with_threads(threads, || {
+                let core = core_network_with_patch(create_road(), &policy, &Progress::default());
+                DirectedNetworkGraph::<IntermediateData>::from(core.unwrap())
+            })
+        });
+
+        let [a, b] = &layers;
+        assert_eq!(a.validate(), Ok(()));
+        assert_eq!(a.edges(), b.edges());
+        assert_eq!(a.nodes().len(), 2);
+        assert_eq!(a.data.node_data(NodeId(1)), &NodeId(3));
+
+        // One shortcut over both nodes, with the roads in order from each end.
+        let edges = (0..a.edges().len())
+            .map(EdgeId::from)
+            .map(|id| {
+                let edge = a.edge(id);
+                (edge.direction(), edge.distance(), a.data.edge_road_id(id))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                (
+                    EdgeDirection::Both,
+                    6.0,
+                    ShortcutState::Shortcut(vec![10, 11, 12])
+                ),
+                (
+                    EdgeDirection::Both,
+                    6.0,
+                    ShortcutState::Shortcut(vec![12, 11, 10])
+                ),
+            ]
+        );
     }
 }
@@ -0,0 +1,353 @@
+use super::{
+    hierarchy::{extend_hierarchy, Hierarchy, HierarchyConfig, LevelStatistics},
+    intermediate_network::IntermediateData,
+    ComputedState,
+};
+use graph::{
+    highway_network::HighwayNetwork, DijkstraWorkspace, DirectedNetworkGraph, EdgeId,
+    NeighbourhoodSize, NetworkData, NodeId, Progress, ValidationError,
+};
+use rayon::prelude::*;
+use std::time::Duration;
+
+/// What a new metric changed in one level of a [`Customisation`], level 0 is the base graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelChanges {
+    pub level: usize,
+    /// The number of edges with another weight.
+    pub changed_edges: usize,
+    /// The nodes with other nodes in their forward or backward neighbourhood,
+    /// the layer above was selected with the old neighbourhoods.
+    pub changed_neighbourhoods: Vec<NodeId>,
+    /// The broken invariants of the level, like a two way edge of which the directions got a different weight.
+    pub errors: Vec<ValidationError>,
+}
+
+/// A [`Hierarchy`] with the weights of a new metric.
+/// The layers keep their nodes, edges and shortcuts, only the weights are computed again.
+pub struct Customisation<D: NetworkData> {
+    pub base: DirectedNetworkGraph<D>,
+    pub layers: Vec<DirectedNetworkGraph<IntermediateData>>,
+    /// The neighbourhoods with the new metric.
+    pub radii: Vec<ComputedState>,
+    pub changes: Vec<LevelChanges>,
+}
+
+impl Hierarchy {
+    /// The hierarchy with new weights, `metric` is the weight of every edge of `base`.
+    /// The edges at both ends of a road need the same weight, like a weight per road.
+    /// The weights of the layers are computed bottom up from the shortcuts, no node is contracted again.
+    ///
+    /// The neighbourhoods of every node of every level are still computed again, with searches from every node,
+    /// the levels where they changed are in [`Customisation::changes`].
+    /// Where they did not change, the edges of the layers are still selected with the same neighbourhoods,
+    /// but a large change of the metric can still move the shortest paths off the highways.
+    pub fn customise<D, M>(&self, base: &DirectedNetworkGraph<D>, metric: M) -> Customisation<D>
+    where
+        D: NetworkData + Clone,
+        M: Fn(EdgeId) -> f32,
+    {
+        let new_base = base.with_weights(metric);
+        let mut changes = vec![level_changes(0, base, &new_base, self.radii.first())];
+
+        let mut layers =
+            Vec::<DirectedNetworkGraph<IntermediateData>>::with_capacity(self.layers.len());
+        for (index, layer) in self.layers.iter().enumerate() {
+            let weights = match layers.last() {
+                None => shortcut_weights(layer, &new_base),
+                Some(lower) => shortcut_weights(layer, lower),
+            };
+            let new_layer = layer.with_weights(|edge| weights[layer.edge_data(edge).0 as usize]);
+
+            changes.push(level_changes(
+                index + 1,
+                layer,
+                &new_layer,
+                self.radii.get(index + 1),
+            ));
+            layers.push(new_layer);
+        }
+
+        let radii = self
+            .radii
+            .iter()
+            .enumerate()
+            .map(|(level, computed)| match level {
+                0 => customised_state(computed.size, &new_base),
+                _ => customised_state(computed.size, &layers[level - 1]),
+            })
+            .collect();
+
+        Customisation {
+            base: new_base,
+            layers,
+            radii,
+            changes,
+        }
+    }
+}
+
+impl<D: NetworkData> Customisation<D> {
+    /// The highest level that is still valid, the layers above it have to be built again with [`Self::regenerate`].
+    /// `None` when all levels are valid.
+    pub fn regenerate_from(&self) -> Option<usize> {
+        self.changes
+            .iter()
+            .filter_map(|x| {
+                if !x.errors.is_empty() {
+                    Some(x.level.saturating_sub(1))
+                } else if !x.changed_neighbourhoods.is_empty() {
+                    Some(x.level)
+                } else {
+                    None
+                }
+            })
+            .min()
+    }
+
+    /// Keep the levels up to [`Self::regenerate_from`] and build the layers above them again.
+    pub fn regenerate(self, config: &HierarchyConfig, progress: &Progress) -> Hierarchy {
+        let keep = self.regenerate_from().unwrap_or(self.layers.len());
+
+        let mut layers = self.layers;
+        layers.truncate(keep);
+        let mut radii = self.radii;
+        let next_state = (radii.len() > keep).then(|| radii.swap_remove(keep));
+        radii.truncate(keep);
+
+        // The kept levels were not built again.
+        let statistics = layers
+            .iter()
+            .zip(&radii)
+            .map(|(layer, computed)| LevelStatistics {
+                nodes: layer.nodes().len(),
+                edges: layer.edges().len(),
+                neighbourhood_size: computed.size,
+                duration: Duration::ZERO,
+            })
+            .collect();
+
+        extend_hierarchy(
+            &self.base, layers, radii, statistics, next_state, config, progress,
+        )
+    }
+
+    /// Combine the levels into a [`HighwayNetwork`], like [`Hierarchy::highway_network`].
+    pub fn highway_network(&self) -> HighwayNetwork {
+        HighwayNetwork::new(&self.base, &self.layers, |level, node| {
+            match self.radii.get(level as usize) {
+                Some(computed) => (
+                    computed.forward.radius(node),
+                    computed.backward.radius(node),
+                ),
+                None => (f32::INFINITY, f32::INFINITY),
+            }
+        })
+    }
+}
+
+/// The weight of every shortcut of `layer`, from the weights of `lower`.
+fn shortcut_weights<D: NetworkData>(
+    layer: &DirectedNetworkGraph<IntermediateData>,
+    lower: &DirectedNetworkGraph<D>,
+) -> Vec<f32> {
+    layer
+        .data
+        .arena()
+        .weights(|edge| lower.edge(EdgeId(edge)).distance())
+}
+
+fn customised_state<D: NetworkData>(
+    size: NeighbourhoodSize,
+    network: &DirectedNetworkGraph<D>,
+) -> ComputedState {
+    ComputedState::with_progress(size, network, &Progress::default()).expect("Not cancelled")
+}
+
+fn level_changes<D: NetworkData>(
+    level: usize,
+    old: &DirectedNetworkGraph<D>,
+    new: &DirectedNetworkGraph<D>,
+    computed: Option<&ComputedState>,
+) -> LevelChanges {
+    let changed_edges = old
+        .edges()
+        .iter()
+        .zip(new.edges())
+        .filter(|(a, b)| a.distance() != b.distance())
+        .count();
+
+    // Without edges of another weight every neighbourhood is the same.
+    let changed_neighbourhoods = match computed {
+        Some(computed) if changed_edges > 0 => changed_neighbourhoods(computed.size, old, new),
+        _ => Vec::new(),
+    };
+
+    LevelChanges {
+        level,
+        changed_edges,
+        changed_neighbourhoods,
+        errors: new.validate().err().unwrap_or_default(),
+    }
+}
+
+/// The nodes of which the forward or backward neighbourhood has other nodes in `new` than in `old`.
+fn changed_neighbourhoods<D: NetworkData>(
+    size: NeighbourhoodSize,
+    old: &DirectedNetworkGraph<D>,
+    new: &DirectedNetworkGraph<D>,
+) -> Vec<NodeId> {
+    let node_count = old.nodes().len();
+
+    (0..node_count)
+        .into_par_iter()
+        .map_init(
+            || DijkstraWorkspace::new(node_count),
+            |workspace, node| {
+                let node = NodeId::from(node);
+                let mut members = |network: &DirectedNetworkGraph<D>, forward: bool| {
+                    let mut members = if forward {
+                        size.members(network.forward_search(node, workspace))
+                            .map(|x| x.0)
+                            .collect::<Vec<_>>()
+                    } else {
+                        size.members(network.backward_search(node, workspace))
+                            .map(|x| x.0)
+                            .collect::<Vec<_>>()
+                    };
+                    members.sort_unstable();
+                    members
+                };
+
+                let changed = members(old, true) != members(new, true)
+                    || members(old, false) != members(new, false);
+                changed.then_some(node)
+            },
+        )
+        .flatten_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::{
+        hierarchy::{build_hierarchy, HierarchyConfig},
+        two_way_network,
+    };
+    use graph::{DirectedNetworkGraph, EdgeId, NetworkData, NodeId, Progress, ValidationError};
+
+    fn config() -> HierarchyConfig {
+        HierarchyConfig {
+            neighbourhood_sizes: vec![3.into()],
+            max_levels: 2,
+            max_core_ratio: 1.0,
+            ..Default::default()
+        }
+    }
+
+    /// Five hubs that are all connected over a village, every road is 5 long.
+    /// The layer consists of the hubs, with a shortcut over every village.
+    fn create_villages() -> DirectedNetworkGraph<()> {
+        let mut roads = Vec::new();
+        let mut village = 5;
+        for a in 0..5 {
+            for b in a + 1..5 {
+                roads.push((a, village, 5.0));
+                roads.push((village, b, 5.0));
+                village += 1;
+            }
+        }
+
+        two_way_network(&roads)
+    }
+
+    #[test]
+    fn customise_weights() {
+        let network = create_villages();
+        let hierarchy = build_hierarchy(&network, &config());
+        assert_eq!(hierarchy.layers.len(), 1);
+
+        let same = hierarchy.customise(&network, |edge| network.edge(edge).distance());
+        assert_eq!(same.regenerate_from(), None);
+        for (old, new) in hierarchy.layers.iter().zip(&same.layers) {
+            assert_eq!(old.edges(), new.edges());
+        }
+
+        // The same neighbourhoods and shortest paths, every weight is twice as large.
+        let double = hierarchy.customise(&network, |edge| network.edge(edge).distance() * 2.0);
+        assert_eq!(double.regenerate_from(), None);
+        for (change, (old, new)) in double.changes[1..]
+            .iter()
+            .zip(hierarchy.layers.iter().zip(&double.layers))
+        {
+            assert_eq!(change.changed_edges, old.edges().len());
+            for (a, b) in old.edges().iter().zip(new.edges()) {
+                assert_eq!(a.distance() * 2.0, b.distance());
+            }
+            // The roads of the shortcuts are kept.
+            for edge in (0..old.edges().len()).map(EdgeId::from) {
+                assert_eq!(old.data.edge_road_id(edge), new.data.edge_road_id(edge));
+            }
+        }
+    }
+
+    #[test]
+    fn regenerate_changed_levels() {
+        let network = create_villages();
+        let config = config();
+        let hierarchy = build_hierarchy(&network, &config);
+
+        // The road over village 5 between hub 0 and hub 1 is three times as long,
+        // the detour over another hub is shorter.
+        let village = network.node(NodeId(5));
+        let metric = |edge: EdgeId| {
+            let weight = network.edge(edge).distance();
+            let at_village = (village.start_edge_index..village.last_edge_index).contains(&edge.0);
+            if at_village || network.edge(edge).target() == NodeId(5) {
+                weight * 3.0
+            } else {
+                weight
+            }
+        };
+        let customisation = hierarchy.customise(&network, metric);
+        assert_eq!(customisation.changes[0].changed_edges, 8);
+        assert!(!customisation.changes[0].changed_neighbourhoods.is_empty());
+        assert_eq!(customisation.changes[1].changed_edges, 4);
+        assert_eq!(customisation.regenerate_from(), Some(0));
+
+        // The shortcut is kept with its new weight, although it is no shortest path anymore.
+        let layer = &customisation.layers[0];
+        let (_, shortcut) = layer
+            .out_edges(NodeId(0))
+            .find(|(_, edge)| edge.target() == NodeId(1))
+            .unwrap();
+        assert_eq!(shortcut.distance(), 30.0);
+
+        let regenerated = customisation.regenerate(&config, &Progress::default());
+        let expected = build_hierarchy(&network.with_weights(metric), &config);
+        assert_eq!(
+            bincode::serialize(&regenerated.layers).unwrap(),
+            bincode::serialize(&expected.layers).unwrap()
+        );
+    }
+
+    #[test]
+    fn road_with_two_weights() {
+        let network = create_villages();
+        let hierarchy = build_hierarchy(&network, &config());
+
+        // Edge 0 is the road from hub 0 to village 5, only the end at hub 0 gets another weight.
+        let customisation = hierarchy.customise(&network, |edge| match edge.0 {
+            0 => 6.0,
+            _ => network.edge(edge).distance(),
+        });
+
+        let errors = &customisation.changes[0].errors;
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|x| matches!(
+            x,
+            ValidationError::MissingMirror { node, .. } if [NodeId(0), NodeId(5)].contains(node)
+        )));
+        assert_eq!(customisation.regenerate_from(), Some(0));
+    }
+}
@@ -1,5 +1,7 @@
-use graph::{EdgeId, NodeId};
-use std::collections::{BinaryHeap, HashMap};
+use graph::{AddressableHeap, EdgeId, NodeId, NodeMap};
+
+/// A parent of a node in the DAG, with the edge from the parent and its distance.
+pub type Parent = (NodeId, Option<EdgeId>, f32);
 
 #[derive(Debug, PartialEq, PartialOrd)]
 pub struct ParentEntry {
@@ -9,151 +11,168 @@ pub struct ParentEntry {
     pub active: bool,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct DijkstraNodeState {
-    pub distance: f32,
-    pub current: NodeId,
-    pub parent: ParentEntry,
-}
-
-impl Ord for DijkstraNodeState {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
-    }
-}
-
-impl Eq for DijkstraNodeState {}
-
-impl PartialOrd for DijkstraNodeState {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.distance.partial_cmp(&other.distance) {
-            Some(core::cmp::Ordering::Equal) => {}
-            Some(core::cmp::Ordering::Greater) => return Some(core::cmp::Ordering::Less),
-            Some(core::cmp::Ordering::Less) => return Some(core::cmp::Ordering::Greater),
-            None => return None,
-        }
-
-        match self.current.partial_cmp(&other.current) {
-            Some(core::cmp::Ordering::Equal) => {}
-            ord => return ord,
-        }
-
-        self.parent.partial_cmp(&other.parent)
-    }
-}
-
-#[derive(Debug)]
+/// The state of a node that is reached by the search.
+/// The parents are all nodes through which it is reached with its shortest distance.
+#[derive(Debug, Default)]
 pub struct VisitedState {
     pub border_distance: f32,
     pub reference_distance: f32,
     pub distance: f32,
-    pub parents: HashMap<NodeId, (Option<EdgeId>, f32)>,
+    pub parents: Vec<Parent>,
+    /// Whether the node is settled, otherwise it is queued.
+    pub settled: bool,
+    /// Whether a queued node has an active parent.
+    pub active: bool,
 }
 
+/// The queue and the DAG of the phase 1 search, which is reused between the searches of a thread.
 pub struct HighwayNodeQueue {
-    pub heap: BinaryHeap<DijkstraNodeState>,
-    pub visited: HashMap<NodeId, VisitedState>,
+    heap: AddressableHeap,
+    pub nodes: NodeMap<VisitedState>,
+    /// The settled nodes with their distance, in the order they are settled.
+    pub settled: Vec<(NodeId, f32)>,
+    /// Tentative slacks used to collect the edges of the next level.
+    pub slacks: NodeMap<f32>,
+    /// Number of queued nodes with an active parent.
     active: usize,
 }
 
 pub struct HighwayQueueEntry {
-    pub state: DijkstraNodeState,
-    pub parents: HashMap<NodeId, (Option<EdgeId>, f32)>,
+    pub node: NodeId,
+    pub distance: f32,
     pub border_distance: f32,
     pub reference_distance: f32,
     pub parent_active: bool,
 }
 
 impl HighwayNodeQueue {
-    pub fn new(heap_size: usize, visited_size: usize) -> Self {
+    /// A queue for a network with `size` nodes.
+    pub fn new(size: usize) -> Self {
         Self {
-            heap: BinaryHeap::with_capacity(heap_size),
-            visited: HashMap::with_capacity(visited_size),
+            heap: AddressableHeap::new(size),
+            nodes: NodeMap::new(size),
+            settled: Vec::new(),
+            slacks: NodeMap::new(size),
             active: 0,
         }
     }
-    pub fn push(&mut self, state: DijkstraNodeState) {
-        if state.parent.active {
-            self.active += 1;
-        }
-        self.heap.push(state);
+
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.nodes.clear();
+        self.settled.clear();
+        self.slacks.clear();
+        self.active = 0;
     }
 
-    pub fn pop(&mut self) -> Option<HighwayQueueEntry> {
-        if !self.is_active() {
-            return None;
-        }
-        while let Some(mut state) = self.heap.pop() {
-            if state.parent.active {
-                self.active -= 1;
-            }
-            if self.visited.contains_key(&state.current) {
-                debug_assert!(state.distance > self.visited[&state.current].distance);
-                continue;
+    /// The state of `node` when it is settled.
+    pub fn visited(&self, node: NodeId) -> Option<&VisitedState> {
+        self.nodes.get(node).filter(|x| x.settled)
+    }
+
+    /// Settle `node` with `state`, without queueing it.
+    pub fn settle(&mut self, node: NodeId, state: VisitedState) {
+        self.nodes.insert(
+            node,
+            VisitedState {
+                settled: true,
+                ..state
+            },
+        );
+    }
+
+    /// Reach `node` from a parent, with `distance`.
+    /// A shorter distance replaces the parents of the node, an equal distance adds a parent.
+    pub fn push(&mut self, node: NodeId, distance: f32, parent: ParentEntry) {
+        let entry = (
+            parent.parent,
+            Some(parent.parent_edge),
+            parent.parent_edge_distance,
+        );
+
+        if let Some(state) = self.nodes.get_mut(node) {
+            if state.settled || distance > state.distance {
+                return;
             }
 
-            let mut parent_border_distance = self.visited[&state.parent.parent].border_distance;
-            let mut parent_reference_distance =
-                self.visited[&state.parent.parent].reference_distance;
-            let mut parents = HashMap::from([(
-                state.parent.parent,
-                (
-                    Some(state.parent.parent_edge),
-                    state.parent.parent_edge_distance,
-                ),
-            )]);
-
-            let mut parent_active = state.parent.active;
-
-            while let Some(peek) = self
-                .heap
-                .peek()
-                .filter(|next| next.current == state.current && next.distance == state.distance)
-            {
-                if peek.parent.active {
-                    self.active -= 1;
+            if distance == state.distance {
+                // Of parallel edges, the one with the smallest id is used.
+                match state.parents.iter_mut().find(|x| x.0 == parent.parent) {
+                    Some(existing) if existing.1 > entry.1 => *existing = entry,
+                    Some(_) => {}
+                    None => state.parents.push(entry),
+                }
+
+                if parent.active && !state.active {
+                    state.active = true;
+                    self.active += 1;
                 }
+                return;
+            }
 
-                let visited_parent = &self.visited[&peek.parent.parent];
+            if state.active {
+                self.active -= 1;
+            }
+        }
 
-                parent_border_distance =
-                    f32::max(parent_border_distance, visited_parent.border_distance);
-                parent_reference_distance =
-                    f32::max(parent_reference_distance, visited_parent.reference_distance);
+        let state = self.nodes.reuse(node);
+        state.distance = distance;
+        state.settled = false;
+        state.active = parent.active;
+        state.parents.clear();
+        state.parents.push(entry);
 
-                parents.insert(
-                    peek.parent.parent,
-                    (
-                        Some(peek.parent.parent_edge),
-                        peek.parent.parent_edge_distance,
-                    ),
-                );
+        if parent.active {
+            self.active += 1;
+        }
+        self.heap.push(node, distance);
+    }
 
-                parent_active |= peek.parent.active;
+    /// Settle the closest queued node, as long as there are active nodes.
+    /// Its border and reference distance are the maximum of those of its parents.
+    pub fn pop(&mut self) -> Option<HighwayQueueEntry> {
+        if !self.is_active() {
+            return None;
+        }
 
-                state = self.heap.pop().unwrap();
-            }
+        let (node, distance) = self.heap.pop()?;
 
-            return Some(HighwayQueueEntry {
-                state,
-                border_distance: parent_border_distance,
-                reference_distance: parent_reference_distance,
-                parents,
-                parent_active,
+        let state = self.nodes.get(node).unwrap();
+        let (border_distance, reference_distance) = state
+            .parents
+            .iter()
+            .map(|(parent, _, _)| self.nodes.get(*parent).unwrap())
+            .fold((f32::MIN, f32::MIN), |(border, reference), parent| {
+                (
+                    f32::max(border, parent.border_distance),
+                    f32::max(reference, parent.reference_distance),
+                )
             });
-        }
+        let parent_active = state.active;
 
-        None
-    }
+        let state = self.nodes.get_mut(node).unwrap();
+        state.settled = true;
+        if state.active {
+            state.active = false;
+            self.active -= 1;
+        }
 
-    pub fn visited(&mut self, node: NodeId, state: VisitedState) -> Option<VisitedState> {
-        self.visited.insert(node, state)
+        Some(HighwayQueueEntry {
+            node,
+            distance,
+            border_distance,
+            reference_distance,
+            parent_active,
+        })
     }
 
     pub fn is_active(&self) -> bool {
         debug_assert_eq!(
             self.active,
-            self.heap.iter().filter(|x| x.parent.active).count()
+            self.heap
+                .nodes()
+                .filter(|x| self.nodes.get(*x).unwrap().active)
+                .count()
         );
         self.active > 0
     }
@@ -1,122 +1,93 @@
 use super::ComputedState;
 use graph::{DirectedNetworkGraph, EdgeId, NetworkData, NodeId, F32};
-use std::{
-    cmp::Reverse,
-    collections::{HashMap, VecDeque},
-};
 
 use super::dag::*;
 
+/// Calculate the edges of the next level that are found from `s0`.
+/// The `queue` is cleared, so one queue can be reused for every node.
 pub fn calculate_edges<D: NetworkData>(
     s0: NodeId,
     computed: &ComputedState,
     network: &DirectedNetworkGraph<D>,
+    queue: &mut HighwayNodeQueue,
 ) -> Vec<(NodeId, EdgeId)> {
-    let (sorted_order, dag) = create_directed_acyclic_graph(s0, computed, network);
-    let edges = collect_next_level_edges(s0, sorted_order, dag, computed);
+    create_directed_acyclic_graph(s0, computed, network, queue);
 
-    edges
+    collect_next_level_edges(s0, queue, computed)
 }
 
+/// Grow the DAG of shortest paths from `s0` in `queue`, and stop when there are no more active nodes.
 pub fn create_directed_acyclic_graph<D: NetworkData>(
     s0: NodeId,
     computed: &ComputedState,
     network: &DirectedNetworkGraph<D>,
-) -> (
-    VecDeque<(NodeId, (EdgeId, f32))>,
-    HashMap<NodeId, VisitedState>,
+    queue: &mut HighwayNodeQueue,
 ) {
-    let mut heap = HighwayNodeQueue::new(2000, 3000);
+    queue.clear();
+    initialize_heap(s0, network, queue);
 
-    let mut settled_order = VecDeque::with_capacity(3000);
+    while let Some(entry) = queue.pop() {
+        let parents = &queue.nodes.get(entry.node).unwrap().parents;
+        let border_distance =
+            border_distance(s0, entry.node, parents, computed, entry.border_distance);
+        let reference_distance = reference_distance(&entry, parents, border_distance, queue);
 
-    // println!("Continue growing the DAG, and stop when there are no more active nodes");
+        let state = queue.nodes.get_mut(entry.node).unwrap();
+        state.border_distance = border_distance;
+        state.reference_distance = reference_distance;
 
-    initialize_heap(s0, network, &mut heap);
+        queue.settled.push((entry.node, entry.distance));
 
-    while let Some(entry) = heap.pop() {
-        let border_distance = border_distance(
-            s0,
-            entry.state.current,
-            &entry.parents,
-            computed,
-            entry.border_distance,
-        );
-
-        let reference_distance = reference_distance(&entry, border_distance, &heap.visited);
-        heap.visited(
-            entry.state.current,
-            VisitedState {
-                distance: entry.state.distance,
-                border_distance,
-                reference_distance,
-                parents: entry.parents,
-            },
-        );
-
-        settled_order.push_front((
-            entry.state.current,
-            (entry.state.parent.parent_edge, entry.state.distance),
-        ));
-
-        let should_abort = (reference_distance + computed.backward.radius(entry.state.current))
-            < entry.state.distance;
+        let should_abort =
+            (reference_distance + computed.backward.radius(entry.node)) < entry.distance;
 
         let active = entry.parent_active && !should_abort;
 
-        for (id, child_edge) in network.out_edges(entry.state.current) {
+        for (id, child_edge) in network.out_edges(entry.node) {
             let child = child_edge.target();
-            let next_distance = entry.state.distance + child_edge.distance();
+            let next_distance = entry.distance + child_edge.distance();
 
-            heap.push(DijkstraNodeState {
-                current: child,
-                distance: next_distance,
-                parent: ParentEntry {
-                    parent: entry.state.current,
+            queue.push(
+                child,
+                next_distance,
+                ParentEntry {
+                    parent: entry.node,
                     parent_edge_distance: child_edge.distance(),
-                    parent_edge: id.into(),
+                    parent_edge: id,
                     active,
                 },
-            });
+            );
         }
     }
-
-    (settled_order, heap.visited)
 }
 
+/// Walk the settled nodes from far to near, and collect the edges that leave the neighbourhoods.
 fn collect_next_level_edges(
     s0: NodeId,
-    mut sorted_nodes: VecDeque<(NodeId, (EdgeId, f32))>,
-    nodes: HashMap<NodeId, VisitedState>,
+    queue: &mut HighwayNodeQueue,
     computed: &ComputedState,
 ) -> Vec<(NodeId, EdgeId)> {
     let mut collected_edges = Vec::new();
-    let mut tentative_slacks = HashMap::new();
+    let tentative_slacks = &mut queue.slacks;
 
-    debug_assert!(sorted_nodes
-        .iter()
-        .is_sorted_by_key(|x| Reverse(F32(x.1 .1))));
+    debug_assert!(queue.settled.is_sorted_by_key(|x| F32(x.1)));
 
-    while let Some((node, (_, distance))) = sorted_nodes.pop_front() {
-        if distance < computed.forward.radius(s0) {
-            // return collected_edges;
+    for (node, distance) in queue.settled.iter().rev() {
+        if *distance < computed.forward.radius(s0) {
             continue;
         }
 
-        let slack = *tentative_slacks
-            .entry(node)
-            .or_insert_with(|| computed.backward.radius(node));
+        let slack = *tentative_slacks.get_or_insert_with(*node, || computed.backward.radius(*node));
 
-        for (parent, (edge_id, distance)) in &nodes[&node].parents {
+        for (parent, edge_id, distance) in &queue.nodes.get(*node).unwrap().parents {
             let slack_parent = slack - distance;
 
             if slack_parent < 0.0 {
                 collected_edges.push((*parent, edge_id.unwrap()));
             }
 
-            let tentative_slack_parent = tentative_slacks
-                .entry(*parent)
-                .or_insert_with(|| computed.backward.radius(*parent));
+            let tentative_slack_parent =
+                tentative_slacks.get_or_insert_with(*parent, || computed.backward.radius(*parent));
 
             *tentative_slack_parent = f32::min(*tentative_slack_parent, slack_parent);
         }
@@ -128,40 +99,40 @@ fn collect_next_level_edges(
 fn initialize_heap<D: NetworkData>(
     s0: NodeId,
     network: &DirectedNetworkGraph<D>,
-    heap: &mut HighwayNodeQueue,
+    queue: &mut HighwayNodeQueue,
 ) {
-    heap.visited(
+    queue.settle(
         s0,
         VisitedState {
             border_distance: 0.0,
             reference_distance: f32::INFINITY,
             distance: 0.0,
-            parents: HashMap::from([(s0, (None, 0.0))]),
+            parents: vec![(s0, None, 0.0)],
+            ..Default::default()
         },
     );
     for (id, edge) in network.out_edges(s0) {
-        // assert!(s0 != edge.target());
-        heap.push(DijkstraNodeState {
-            distance: edge.distance(),
-            current: edge.target(),
-            parent: ParentEntry {
+        queue.push(
+            edge.target(),
+            edge.distance(),
+            ParentEntry {
                 parent: s0,
                 parent_edge_distance: edge.distance(),
-                parent_edge: id.into(),
+                parent_edge: id,
                 active: true,
             },
-        });
+        );
     }
 }
 
-fn border_distance<A>(
+fn border_distance(
     s0: NodeId,
     node: NodeId,
-    parents: &HashMap<NodeId, (A, f32)>,
+    parents: &[Parent],
     computed: &ComputedState,
     parent_border_distance: f32,
 ) -> f32 {
-    if let Some((_, distance)) = parents.get(&s0) {
+    if let Some((_, _, distance)) = parents.iter().find(|x| x.0 == s0) {
         *distance + computed.forward.radius(node)
     } else {
         parent_border_distance
@@ -170,17 +141,17 @@ fn border_distance<A>(
 
 fn reference_distance(
     entry: &HighwayQueueEntry,
+    parents: &[Parent],
     border_distance: f32,
-    visited: &HashMap<NodeId, VisitedState>,
+    queue: &HighwayNodeQueue,
 ) -> f32 {
-    let distance = entry.state.distance;
+    let distance = entry.distance;
     let reference_distance = entry.reference_distance;
-    let parents = &entry.parents;
     if reference_distance == f32::INFINITY && distance > border_distance {
         parents
-            .keys()
-            .flat_map(|parent| visited[parent].parents.iter())
-            .map(|sp| visited[sp.0].distance)
+            .iter()
+            .flat_map(|(parent, _, _)| queue.visited(*parent).unwrap().parents.iter())
+            .map(|sp| queue.visited(sp.0).unwrap().distance)
             .max_by(|a, b| a.partial_cmp(b).unwrap())
             .unwrap()
     } else {
@@ -200,6 +171,7 @@ mod tests {
     use crate::generation::dijkstra::collect_next_level_edges;
 
     use super::{create_directed_acyclic_graph, ComputedState};
+    use crate::generation::dag::HighwayNodeQueue;
     // https://www.baeldung.com/wp-content/uploads/2017/01/initial-graph.png
     pub fn create_ref_network_1() -> DirectedNetworkGraph<()> {
         let nodes = vec![
@@ -291,8 +263,9 @@ mod tests {
     fn forward_test() {
         let network = create_ref_network_1();
         let computed = ComputedState::new(3, &network);
+        let mut queue = HighwayNodeQueue::new(network.nodes().len());
 
-        create_directed_acyclic_graph(NodeId(0), &computed, &network);
+        create_directed_acyclic_graph(NodeId(0), &computed, &network, &mut queue);
     }
 
     #[test]
@@ -300,16 +273,17 @@ mod tests {
         let network = create_undirected_network();
         let computed = super::ComputedState::new(4, &network);
         let s0 = NodeId(12);
-        let edges = create_directed_acyclic_graph(s0, &computed, &network);
+        let mut queue = HighwayNodeQueue::new(network.nodes().len());
+        create_directed_acyclic_graph(s0, &computed, &network, &mut queue);
 
         println!("DAG:");
-        for (n, i) in &edges.1 {
-            println!("N: {},\t {:?}", n.0, i);
+        for (n, _) in &queue.settled {
+            println!("N: {},\t {:?}", n.0, queue.visited(*n).unwrap());
         }
 
-        println!("Edges: {:?}", edges.0);
+        println!("Edges: {:?}", queue.settled);
 
-        let next_edges = collect_next_level_edges(s0, edges.0, edges.1, &computed);
+        let next_edges = collect_next_level_edges(s0, &mut queue, &computed);
 
         println!("Added:");
         for (parent, id) in next_edges {
@@ -325,9 +299,15 @@ mod tests {
         let computed = super::ComputedState::new(4, &network);
 
         let mut next_edges = HashSet::new();
+        let mut queue = HighwayNodeQueue::new(network.nodes().len());
 
         for n in 0..=16 {
-            let edges = super::calculate_edges(NodeId(n), &computed, &network);
+            let edges = super::calculate_edges(NodeId(n), &computed, &network, &mut queue);
+
+            // Reusing the queue gives the same edges as a new one.
+            let mut fresh = HighwayNodeQueue::new(network.nodes().len());
+            let fresh_edges = super::calculate_edges(NodeId(n), &computed, &network, &mut fresh);
+            assert_eq!(edges, fresh_edges);
 
             next_edges.extend(edges);
         }
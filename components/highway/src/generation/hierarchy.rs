@@ -0,0 +1,421 @@
+use super::{
+    calculate_layer_with_state,
+    contraction::{ContractionFactor, ContractionPolicy},
+    intermediate_network::IntermediateData,
+    ComputedState,
+};
+use graph::{
+    highway_network::HighwayNetwork, DirectedNetworkGraph, NeighbourhoodSize, NetworkData, Progress,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// How the levels of a [`Hierarchy`] are built.
+pub struct HierarchyConfig {
+    /// The neighbourhood size of every level, the last size is used for the levels beyond.
+    pub neighbourhood_sizes: Vec<NeighbourhoodSize>,
+    pub policy: Box<dyn ContractionPolicy>,
+    /// The maximum number of layers above the base graph.
+    pub max_levels: usize,
+    /// Stop after a layer with fewer nodes than this.
+    pub min_core_nodes: usize,
+    /// Stop when a layer keeps more than this fraction of the nodes of the level below,
+    /// the layer is not added as it hardly shrinks the network.
+    pub max_core_ratio: f32,
+}
+
+impl HierarchyConfig {
+    /// The neighbourhood size of `level`, level 0 is the base graph.
+    pub fn neighbourhood_size(&self, level: usize) -> NeighbourhoodSize {
+        self.neighbourhood_sizes
+            .get(level)
+            .or(self.neighbourhood_sizes.last())
+            .copied()
+            .expect("No neighbourhood size")
+    }
+}
+
+impl Default for HierarchyConfig {
+    fn default() -> Self {
+        Self {
+            neighbourhood_sizes: vec![NeighbourhoodSize::Nodes(30)],
+            policy: Box::new(ContractionFactor::default()),
+            max_levels: 6,
+            min_core_nodes: 2,
+            max_core_ratio: 0.95,
+        }
+    }
+}
+
+/// Why no more levels were added to a [`Hierarchy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopReason {
+    MaxLevels,
+    CoreTooSmall,
+    CoreNotShrinking,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelStatistics {
+    pub nodes: usize,
+    pub edges: usize,
+    pub neighbourhood_size: NeighbourhoodSize,
+    /// The time to compute the neighbourhoods of the level below and contract it to this level.
+    pub duration: Duration,
+}
+
+/// The layers of a base graph, `layers[i]` is level `i + 1`.
+pub struct Hierarchy {
+    pub layers: Vec<DirectedNetworkGraph<IntermediateData>>,
+    /// The neighbourhoods of every level that has a layer above it, `radii[0]` is of the base graph.
+    pub radii: Vec<ComputedState>,
+    pub statistics: Vec<LevelStatistics>,
+    pub stop_reason: StopReason,
+}
+
+impl Hierarchy {
+    /// Combine the levels into a [`HighwayNetwork`] over the nodes of `base`.
+    /// The top level has no neighbourhood, its radius is infinite.
+    pub fn highway_network<D: NetworkData>(
+        &self,
+        base: &DirectedNetworkGraph<D>,
+    ) -> HighwayNetwork {
+        HighwayNetwork::new(base, &self.layers, |level, node| {
+            match self.radii.get(level as usize) {
+                Some(computed) => (
+                    computed.forward.radius(node),
+                    computed.backward.radius(node),
+                ),
+                None => (f32::INFINITY, f32::INFINITY),
+            }
+        })
+    }
+}
+
+/// Build the layers on top of `base` until one of the stop criteria of `config` is met.
+pub fn build_hierarchy<D: NetworkData>(
+    base: &DirectedNetworkGraph<D>,
+    config: &HierarchyConfig,
+) -> Hierarchy {
+    build_hierarchy_with_progress(base, config, &Progress::default())
+}
+
+/// Like [`build_hierarchy`], reports into `progress`.
+/// When `progress` is cancelled, the levels that are finished are returned.
+pub fn build_hierarchy_with_progress<D: NetworkData>(
+    base: &DirectedNetworkGraph<D>,
+    config: &HierarchyConfig,
+    progress: &Progress,
+) -> Hierarchy {
+    build_hierarchy_with_state(base, None, config, progress)
+}
+
+/// Like [`build_hierarchy_with_progress`], with the neighbourhoods of `base` that are already computed,
+/// for example by an earlier hierarchy with other contraction settings.
+/// They are computed again when their size is not the size of level 0 in `config`,
+/// or they are of another network, like the same network with other weights.
+pub fn build_hierarchy_with_state<D: NetworkData>(
+    base: &DirectedNetworkGraph<D>,
+    mut base_state: Option<ComputedState>,
+    config: &HierarchyConfig,
+    progress: &Progress,
+) -> Hierarchy {
+    if base_state
+        .as_ref()
+        .is_some_and(|x| x.size != config.neighbourhood_size(0) || x.network != base.fingerprint())
+    {
+        println!("The neighbourhoods are not of the base graph and size, computing them again");
+        base_state = None;
+    }
+
+    extend_hierarchy(
+        base,
+        Vec::new(),
+        Vec::new(),
+        Vec::new(),
+        base_state,
+        config,
+        progress,
+    )
+}
+
+/// Add levels on top of `layers`, `next_state` are the neighbourhoods of the top level when they are already computed.
+pub(crate) fn extend_hierarchy<D: NetworkData>(
+    base: &DirectedNetworkGraph<D>,
+    mut layers: Vec<DirectedNetworkGraph<IntermediateData>>,
+    mut radii: Vec<ComputedState>,
+    mut statistics: Vec<LevelStatistics>,
+    mut next_state: Option<ComputedState>,
+    config: &HierarchyConfig,
+    progress: &Progress,
+) -> Hierarchy {
+    let stop_reason = loop {
+        let level = layers.len();
+        if level >= config.max_levels {
+            break StopReason::MaxLevels;
+        }
+
+        progress.set_level(level + 1);
+
+        let size = config.neighbourhood_size(level);
+        let start = Instant::now();
+        let computed = next_state.take().filter(|x| x.size == size);
+        let next = match layers.last() {
+            None => next_level(size, base, computed, config, progress),
+            Some(lower) => next_level(size, lower, computed, config, progress),
+        };
+        let duration = start.elapsed();
+
+        let Some((computed, layer, lower_nodes)) = next else {
+            break StopReason::Cancelled;
+        };
+
+        let nodes = layer.nodes().len();
+        println!(
+            "Level {} - n: {}/{}, e: {}, {}ms",
+            level + 1,
+            nodes,
+            lower_nodes,
+            layer.edges().len(),
+            duration.as_millis()
+        );
+
+        if nodes == 0 || nodes as f32 > lower_nodes as f32 * config.max_core_ratio {
+            break StopReason::CoreNotShrinking;
+        }
+
+        statistics.push(LevelStatistics {
+            nodes,
+            edges: layer.edges().len(),
+            neighbourhood_size: size,
+            duration,
+        });
+        radii.push(computed);
+        layers.push(layer);
+
+        if nodes < config.min_core_nodes {
+            break StopReason::CoreTooSmall;
+        }
+    };
+
+    Hierarchy {
+        layers,
+        radii,
+        statistics,
+        stop_reason,
+    }
+}
+
+fn next_level<D: NetworkData>(
+    size: NeighbourhoodSize,
+    lower: &DirectedNetworkGraph<D>,
+    computed: Option<ComputedState>,
+    config: &HierarchyConfig,
+    progress: &Progress,
+) -> Option<(ComputedState, DirectedNetworkGraph<IntermediateData>, usize)> {
+    let computed = match computed {
+        Some(computed) => computed,
+        None => ComputedState::with_progress(size, lower, progress)?,
+    };
+    let layer = calculate_layer_with_state(&computed, lower, config.policy.as_ref(), progress)?;
+
+    Some((computed, layer, lower.nodes().len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_hierarchy, build_hierarchy_with_progress, build_hierarchy_with_state, Hierarchy,
+        HierarchyConfig, StopReason,
+    };
+    use crate::generation::{
+        contraction::ContractionFactor, grid_network, two_way_network, ComputedState,
+    };
+    use graph::{DirectedNetworkGraph, NeighbourhoodSize, NodeId, Progress};
+
+    /// Five hubs that are all connected by a highway, with a street of three nodes at every hub.
+    /// The first layer consists of the hubs, which are too few for another layer.
+    fn create_hubs() -> DirectedNetworkGraph<()> {
+        let mut roads = Vec::new();
+        for a in 0..5 {
+            for b in a + 1..5 {
+                roads.push((a, b, 10.0));
+            }
+        }
+        for hub in 0..5 {
+            let street = 5 + 3 * hub;
+            roads.push((hub, street, 1.0));
+            roads.push((street, street + 1, 1.0));
+            roads.push((street + 1, street + 2, 1.0));
+        }
+
+        two_way_network(&roads)
+    }
+
+    #[test]
+    fn hierarchy_levels() {
+        let network = create_hubs();
+        let config = HierarchyConfig {
+            neighbourhood_sizes: vec![3.into(), 2.into()],
+            policy: Box::new(ContractionFactor::new(2.0)),
+            max_levels: 4,
+            min_core_nodes: 2,
+            max_core_ratio: 1.0,
+        };
+        let hierarchy = build_hierarchy(&network, &config);
+
+        // The hubs are contracted in the second layer, which is not added.
+        assert_eq!(hierarchy.stop_reason, StopReason::CoreNotShrinking);
+        assert_eq!(hierarchy.layers.len(), 1);
+        assert_eq!(hierarchy.radii.len(), 1);
+        assert_eq!(hierarchy.statistics[0].nodes, 5);
+        assert_eq!(
+            hierarchy.statistics[0].neighbourhood_size,
+            NeighbourhoodSize::Nodes(3)
+        );
+        assert_eq!(config.neighbourhood_size(1), NeighbourhoodSize::Nodes(2));
+        assert_eq!(config.neighbourhood_size(3), NeighbourhoodSize::Nodes(2));
+
+        let highway = hierarchy.highway_network(&network);
+        assert_eq!(highway.max_level, 1);
+
+        for base in 0..network.nodes().len() {
+            let base = NodeId::from(base);
+            let level = highway.node_level(base, 0).unwrap();
+            assert_eq!(
+                level.forward_radius,
+                hierarchy.radii[0].forward.radius(base)
+            );
+            assert_eq!(
+                highway.out_edges(base, 0).unwrap().count(),
+                network.out_edges(base).count()
+            );
+        }
+
+        // Every hub is in the top layer, with the highways to the other hubs.
+        let top = &hierarchy.layers[0];
+        for node in (0..top.nodes().len()).map(NodeId::from) {
+            let base = *top.node_data(node);
+            assert_eq!(base, node);
+
+            let level = highway.node_level(base, 1).unwrap();
+            assert_eq!(level.forward_radius, f32::INFINITY);
+            assert!(highway.node_level(base, 2).is_none());
+
+            let edges = highway
+                .out_edges(base, 1)
+                .unwrap()
+                .map(|(_, edge)| (edge.edge_id, edge.target(), edge.distance()))
+                .collect::<Vec<_>>();
+            let expected = top
+                .out_edges(node)
+                .map(|(id, edge)| (id.0, *top.node_data(edge.target()), edge.distance()))
+                .collect::<Vec<_>>();
+            assert_eq!(edges.len(), 4);
+            assert_eq!(edges, expected);
+        }
+    }
+
+    #[test]
+    fn stop_reasons() {
+        let network = create_hubs();
+        let levels = |config: HierarchyConfig| {
+            let hierarchy = build_hierarchy(&network, &config);
+            assert_eq!(hierarchy.radii.len(), hierarchy.layers.len());
+            (hierarchy.stop_reason, hierarchy.layers.len())
+        };
+        let config = || HierarchyConfig {
+            neighbourhood_sizes: vec![3.into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            levels(HierarchyConfig {
+                max_levels: 1,
+                ..config()
+            }),
+            (StopReason::MaxLevels, 1)
+        );
+        // The 5 hubs are fewer than the minimum, the layer is still added.
+        assert_eq!(
+            levels(HierarchyConfig {
+                min_core_nodes: 6,
+                ..config()
+            }),
+            (StopReason::CoreTooSmall, 1)
+        );
+        // The hubs are a quarter of the 20 nodes.
+        assert_eq!(
+            levels(HierarchyConfig {
+                max_core_ratio: 0.2,
+                ..config()
+            }),
+            (StopReason::CoreNotShrinking, 0)
+        );
+        assert_eq!(levels(config()), (StopReason::CoreNotShrinking, 1));
+    }
+
+    #[test]
+    fn cancelled_hierarchy() {
+        let network = grid_network(8);
+        let progress = Progress::new();
+        progress.cancel();
+
+        let hierarchy = build_hierarchy_with_progress(&network, &Default::default(), &progress);
+        assert_eq!(hierarchy.stop_reason, StopReason::Cancelled);
+        assert!(hierarchy.layers.is_empty());
+        assert_eq!(progress.report().unwrap().level, 1);
+    }
+
+    #[test]
+    fn reuses_base_state() {
+        let network = grid_network(12);
+        let config = HierarchyConfig {
+            neighbourhood_sizes: vec![3.into()],
+            max_levels: 2,
+            max_core_ratio: 1.0,
+            ..Default::default()
+        };
+        let expected = build_hierarchy(&network, &config);
+        let bytes = |hierarchy: &Hierarchy| bincode::serialize(&hierarchy.layers).unwrap();
+
+        let progress = Progress::new();
+        let state = ComputedState::new(3, &network);
+        let hierarchy = build_hierarchy_with_state(&network, Some(state), &config, &progress);
+        assert_eq!(bytes(&hierarchy), bytes(&expected));
+
+        // A state of another size is ignored.
+        let state = ComputedState::new(5, &network);
+        let hierarchy = build_hierarchy_with_state(&network, Some(state), &config, &progress);
+        assert_eq!(bytes(&hierarchy), bytes(&expected));
+        assert_eq!(hierarchy.radii[0].size, NeighbourhoodSize::Nodes(3));
+
+        // A state of the same nodes and edges with other weights is ignored.
+        let heavier = network.with_weights(|edge| network.edge(edge).distance() * 3.0);
+        let state = ComputedState::new(3, &heavier);
+        let hierarchy = build_hierarchy_with_state(&network, Some(state), &config, &progress);
+        assert_eq!(bytes(&hierarchy), bytes(&expected));
+        assert_eq!(hierarchy.radii[0].network, network.fingerprint());
+    }
+
+    #[test]
+    fn distance_bounded_hierarchy() {
+        let network = grid_network(12);
+        let config = HierarchyConfig {
+            neighbourhood_sizes: vec![NeighbourhoodSize::Bounded {
+                nodes: 30,
+                distance: 4.0,
+            }],
+            max_levels: 1,
+            max_core_ratio: 1.0,
+            ..Default::default()
+        };
+        let hierarchy = build_hierarchy(&network, &config);
+
+        assert_eq!(hierarchy.layers.len(), 1);
+        for node in (0..network.nodes().len()).map(NodeId::from) {
+            assert!(hierarchy.radii[0].forward.radius(node) <= 4.0);
+        }
+    }
+}
@@ -1,283 +1,829 @@
 use graph::{
-    builder::{DirectedNetworkBuilder, EdgeBuilder, EdgeDirection, NodeBuilder},
-    DirectedNetworkGraph, EdgeId, NetworkData, NodeId, ShortcutState,
+    builder::EdgeDirection, DirectedNetworkGraph, EdgeId, NetworkData, NetworkEdge, NetworkNode,
+    NodeId, Permutation, ShortcutState,
 };
-use rayon::iter::{FromParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub struct IntermediateNode(pub NodeId);
+/// A shortcut in the [`ShortcutArena`] of a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ShortcutId(pub u32);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum ShortcutEntry {
+    /// An edge of the lower layer, with its roads at `roads[start..start + len]`.
+    Edge {
+        edge: u32,
+        start: u32,
+        len: u32,
+        single: bool,
+    },
+    /// Two shortcuts after each other.
+    Pair(ShortcutId, ShortcutId),
+}
 
-#[derive(Debug, Clone)]
-pub struct IntermediateEdge {
-    data: ShortcutState<u32>,
-    road_id: ShortcutState<usize>,
-    direction: EdgeDirection,
-    source: NodeId,
-    target: NodeId,
-    weight: f32,
+/// The number of edges of the lower layer and the number of roads that a shortcut consists of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShortcutLength {
+    pub edges: u32,
+    pub roads: u32,
 }
 
-impl NodeBuilder for IntermediateNode {
-    type Data = NodeId;
+impl std::ops::Add for ShortcutLength {
+    type Output = ShortcutLength;
 
-    fn id(&self) -> u32 {
-        self.0 .0
+    fn add(self, other: ShortcutLength) -> ShortcutLength {
+        ShortcutLength {
+            edges: self.edges + other.edges,
+            roads: self.roads + other.roads,
+        }
     }
+}
 
-    fn data(&self) -> Self::Data {
-        self.0
-    }
+/// The edges of the lower layer and the roads that the edges of a layer consist of.
+/// A shortcut is stored as the pair of shortcuts it bypasses, so shortcuts share their parts.
+/// Equal edges and pairs are stored once.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ShortcutArena {
+    entries: Vec<ShortcutEntry>,
+    lengths: Vec<ShortcutLength>,
+    roads: Vec<usize>,
+    #[serde(skip)]
+    edges: HashMap<u32, ShortcutId>,
+    #[serde(skip)]
+    pairs: HashMap<(ShortcutId, ShortcutId), ShortcutId>,
 }
 
-impl EdgeBuilder for IntermediateEdge {
-    type Data = ShortcutState<u32>; // Points to road_id
+impl ShortcutArena {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
 
-    fn data(&self) -> Self::Data {
-        self.data.clone()
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
 
-    fn source(&self) -> NodeId {
-        self.source
+    /// The shortcut of a single edge of the lower layer.
+    pub fn edge(&mut self, edge: u32, road_ids: ShortcutState<usize>) -> ShortcutId {
+        if let Some(id) = self.edges.get(&edge) {
+            return *id;
+        }
+
+        let single = matches!(road_ids, ShortcutState::Single(_));
+        let start = self.roads.len() as u32;
+        self.roads.extend(Vec::from(road_ids));
+
+        let len = self.roads.len() as u32 - start;
+        let length = ShortcutLength {
+            edges: 1,
+            roads: len,
+        };
+        let id = self.push(
+            ShortcutEntry::Edge {
+                edge,
+                start,
+                len,
+                single,
+            },
+            length,
+        );
+        self.edges.insert(edge, id);
+
+        id
+    }
+
+    /// The shortcut of `first` followed by `second`.
+    pub fn concat(&mut self, first: ShortcutId, second: ShortcutId) -> ShortcutId {
+        if let Some(id) = self.pairs.get(&(first, second)) {
+            return *id;
+        }
+
+        let length = self.length(first) + self.length(second);
+        let id = self.push(ShortcutEntry::Pair(first, second), length);
+        self.pairs.insert((first, second), id);
+
+        id
+    }
+
+    pub fn length(&self, id: ShortcutId) -> ShortcutLength {
+        self.lengths[id.0 as usize]
+    }
+
+    /// The weight of every shortcut, the sum of the weights of its edges of the lower layer.
+    /// A shortcut is added after its parts, so the weights are computed bottom up in one pass.
+    pub fn weights<F: Fn(u32) -> f32>(&self, lower: F) -> Vec<f32> {
+        let mut weights = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            let weight = match entry {
+                ShortcutEntry::Edge { edge, .. } => lower(*edge),
+                ShortcutEntry::Pair(first, second) => {
+                    weights[first.0 as usize] + weights[second.0 as usize]
+                }
+            };
+            weights.push(weight);
+        }
+
+        weights
+    }
+
+    /// The edges of the lower layer that are part of a shortcut.
+    pub fn lower_edges(&self) -> impl Iterator<Item = u32> + '_ {
+        self.entries.iter().filter_map(|entry| match entry {
+            ShortcutEntry::Edge { edge, .. } => Some(*edge),
+            ShortcutEntry::Pair(..) => None,
+        })
+    }
+
+    /// The number of bytes of the stored shortcuts, without the lookup maps that are only used while building.
+    pub fn memory_size(&self) -> usize {
+        self.entries.len() * std::mem::size_of::<ShortcutEntry>()
+            + self.lengths.len() * std::mem::size_of::<ShortcutLength>()
+            + self.roads.len() * std::mem::size_of::<usize>()
+    }
+
+    fn push(&mut self, entry: ShortcutEntry, length: ShortcutLength) -> ShortcutId {
+        self.entries.push(entry);
+        self.lengths.push(length);
+        ShortcutId(self.entries.len() as u32 - 1)
+    }
+
+    /// Visit the edges of the lower layer of `id` in order, as (edge, roads, single road).
+    fn unpack<F>(&self, id: ShortcutId, mut visit: F)
+    where
+        F: FnMut(u32, &[usize], bool),
+    {
+        // Shortcuts can be deeply nested, unpack without recursion.
+        let mut stack = vec![id];
+
+        while let Some(id) = stack.pop() {
+            match self.entries[id.0 as usize] {
+                ShortcutEntry::Edge {
+                    edge,
+                    start,
+                    len,
+                    single,
+                } => {
+                    let roads = &self.roads[start as usize..(start + len) as usize];
+                    visit(edge, roads, single);
+                }
+                ShortcutEntry::Pair(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+            }
+        }
     }
 
-    fn target(&self) -> NodeId {
-        self.target
+    /// Whether `second` consists of the roads of `first` in reverse order,
+    /// like the two directions of a two way road.
+    pub fn is_reverse(&self, first: ShortcutId, second: ShortcutId) -> bool {
+        if self.length(first).roads != self.length(second).roads {
+            return false;
+        }
+
+        let mut roads = Vec::new();
+        self.unpack(first, |_, x, _| roads.extend_from_slice(x));
+        let mut reverse = Vec::new();
+        self.unpack(second, |_, x, _| reverse.extend_from_slice(x));
+        reverse.reverse();
+
+        roads == reverse
     }
 
-    fn weight(&self) -> f32 {
-        self.weight
+    /// The edges of the lower layer that `id` consists of.
+    pub fn edges(&self, id: ShortcutId) -> ShortcutState<u32> {
+        match self.entries[id.0 as usize] {
+            ShortcutEntry::Edge { edge, .. } => ShortcutState::Single(edge),
+            ShortcutEntry::Pair(..) => {
+                let mut edges = Vec::new();
+                self.unpack(id, |edge, _, _| edges.push(edge));
+                ShortcutState::Shortcut(edges)
+            }
+        }
     }
 
-    fn direction(&self) -> EdgeDirection {
-        self.direction
+    /// The roads that `id` consists of.
+    pub fn road_ids(&self, id: ShortcutId) -> ShortcutState<usize> {
+        match self.entries[id.0 as usize] {
+            ShortcutEntry::Edge { .. } => {
+                let mut road_ids = None;
+                self.unpack(id, |_, roads, single| {
+                    road_ids = Some(if single {
+                        ShortcutState::Single(roads[0])
+                    } else {
+                        ShortcutState::Shortcut(roads.to_vec())
+                    })
+                });
+                road_ids.unwrap()
+            }
+            ShortcutEntry::Pair(..) => {
+                let mut road_ids = Vec::new();
+                self.unpack(id, |_, roads, _| road_ids.extend_from_slice(roads));
+                ShortcutState::Shortcut(road_ids)
+            }
+        }
     }
 
-    fn road_id(&self) -> ShortcutState<usize> {
-        self.road_id.clone()
+    /// Update the edges of the lower layer, after it was renumbered.
+    pub fn remap(&mut self, permutation: &Permutation) {
+        self.edges.clear();
+
+        for (id, entry) in self.entries.iter_mut().enumerate() {
+            if let ShortcutEntry::Edge { edge, .. } = entry {
+                *edge = permutation.edge(EdgeId(*edge)).0;
+                self.edges.insert(*edge, ShortcutId(id as u32));
+            }
+        }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct IntermediateEdge {
+    shortcut: ShortcutId,
+    source: NodeId,
+    target: NodeId,
+    weight: f32,
+}
+
 impl IntermediateEdge {
-    pub fn new(
-        source: NodeId,
-        target: NodeId,
-        weight: f32,
-        data: ShortcutState<u32>,
-        road_id: ShortcutState<usize>,
-        direction: EdgeDirection,
-    ) -> Self {
+    pub fn new(source: NodeId, target: NodeId, weight: f32, shortcut: ShortcutId) -> Self {
         Self {
             source,
             target,
             weight,
-            data,
-            direction,
-            road_id,
+            shortcut,
         }
     }
 }
 
+/// The network of a layer while it is generated, with the node ids of the lower layer.
+/// The edges are directed, there is at most one edge from a source to a target, adding another one replaces it.
+/// The edges are stored once, the nodes keep the indices of their outgoing and incoming edges.
+/// A node without a list never had an edge in that direction, or is removed.
 #[derive(Debug, Default)]
 pub(crate) struct IntermediateNetwork {
-    out_edges: HashMap<NodeId, HashMap<NodeId, IntermediateEdge>>,
-    in_edges: HashMap<NodeId, HashMap<NodeId, IntermediateEdge>>,
+    edges: Vec<IntermediateEdge>,
+    /// Indices of removed edges, which are reused.
+    free: Vec<u32>,
+    out_edges: Vec<Option<Vec<u32>>>,
+    in_edges: Vec<Option<Vec<u32>>>,
+    shortcuts: ShortcutArena,
 }
 
 impl IntermediateNetwork {
+    pub fn with_size(node_size: usize) -> Self {
+        Self {
+            out_edges: vec![None; node_size],
+            in_edges: vec![None; node_size],
+            ..Default::default()
+        }
+    }
+
+    /// The number of nodes of the lower layer.
+    pub fn node_size(&self) -> usize {
+        self.out_edges.len()
+    }
+
+    /// The nodes with outgoing edges, in order of their id.
     pub fn nodes(&self) -> Vec<NodeId> {
-        self.out_edges.keys().cloned().collect::<Vec<_>>()
+        (0..self.out_edges.len())
+            .filter(|node| self.out_edges[*node].is_some())
+            .map(NodeId::from)
+            .collect()
     }
-    pub fn out_edges(&self, node: NodeId) -> Option<&HashMap<NodeId, IntermediateEdge>> {
-        self.out_edges.get(&node)
+
+    pub fn out_edges(&self, node: NodeId) -> Option<&[u32]> {
+        self.out_edges.get(node.0 as usize)?.as_deref()
     }
 
-    pub fn in_edges(&self, node: NodeId) -> Option<&HashMap<NodeId, IntermediateEdge>> {
-        self.in_edges.get(&node)
+    pub fn in_edges(&self, node: NodeId) -> Option<&[u32]> {
+        self.in_edges.get(node.0 as usize)?.as_deref()
     }
-}
-impl IntermediateNetwork {
+
+    /// Add an edge of the lower layer, `edge` is its id and `road_ids` its roads.
+    pub fn add_lower_edge(
+        &mut self,
+        source: NodeId,
+        target: NodeId,
+        weight: f32,
+        edge: u32,
+        road_ids: ShortcutState<usize>,
+    ) {
+        let shortcut = self.shortcuts.edge(edge, road_ids);
+        self.add_edge(IntermediateEdge::new(source, target, weight, shortcut));
+    }
+
     pub fn add_edge(&mut self, edge: IntermediateEdge) {
-        let source = edge.source;
-        let target = edge.target;
-        self.out_edges
-            .entry(source)
-            .or_default()
-            .insert(target, edge.clone());
+        let source = edge.source.0 as usize;
+        let target = edge.target.0 as usize;
+
+        let size = usize::max(source, target) + 1;
+        if self.out_edges.len() < size {
+            self.out_edges.resize(size, None);
+            self.in_edges.resize(size, None);
+        }
+
+        let outs = self.out_edges[source].get_or_insert_with(Vec::new);
+        if let Some(index) = outs
+            .iter()
+            .find(|index| self.edges[**index as usize].target == edge.target)
+        {
+            // The incoming list of the target already has this index.
+            self.edges[*index as usize] = edge;
+            return;
+        }
 
-        self.in_edges
-            .entry(target)
-            .or_default()
-            .insert(source, edge.clone());
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.edges[index as usize] = edge;
+                index
+            }
+            None => {
+                self.edges.push(edge);
+                self.edges.len() as u32 - 1
+            }
+        };
+
+        outs.push(index);
+        self.in_edges[target]
+            .get_or_insert_with(Vec::new)
+            .push(index);
     }
 
     pub fn remove_node(&mut self, node: NodeId) {
-        if let Some(outs) = self.out_edges.get(&node) {
-            for edge in outs.values() {
-                self.in_edges.entry(edge.target).and_modify(|x| {
-                    x.remove(&node);
-                });
+        let node = node.0 as usize;
+
+        if let Some(outs) = self.out_edges.get_mut(node).and_then(Option::take) {
+            for index in outs {
+                let target = self.edges[index as usize].target.0 as usize;
+                if let Some(ins) = &mut self.in_edges[target] {
+                    ins.retain(|x| *x != index);
+                }
+                self.free.push(index);
             }
         }
 
-        if let Some(ins) = self.in_edges.get(&node) {
-            for edge in ins.values() {
-                self.out_edges.entry(edge.source).and_modify(|x| {
-                    x.remove(&node);
-                });
+        if let Some(ins) = self.in_edges.get_mut(node).and_then(Option::take) {
+            for index in ins {
+                let source = self.edges[index as usize].source.0 as usize;
+                if let Some(outs) = &mut self.out_edges[source] {
+                    outs.retain(|x| *x != index);
+                }
+                self.free.push(index);
             }
         }
+    }
+
+    /// The sources of the incoming and the targets of the outgoing edges of `node`.
+    pub fn neighbours(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let in_edges = self.in_edges(node).unwrap_or_default();
+        let out_edges = self.out_edges(node).unwrap_or_default();
 
-        self.out_edges.remove(&node);
-        self.in_edges.remove(&node);
+        in_edges
+            .iter()
+            .map(|x| self.edges[*x as usize].source)
+            .chain(out_edges.iter().map(|x| self.edges[*x as usize].target))
     }
 
-    pub fn bypass(&mut self, node: NodeId) -> Vec<NodeId> {
-        let parents = if let Some(parents) = self.in_edges(node) {
-            parents
-        } else {
-            self.remove_node(node);
-            return vec![];
-        };
-        let children = if let Some(children) = self.out_edges(node) {
-            children
-        } else {
-            self.remove_node(node);
-            return vec![];
-        };
+    /// The length of the shortcuts that bypassing `node` would add.
+    pub fn bypass_lengths(&self, node: NodeId) -> impl Iterator<Item = ShortcutLength> + '_ {
+        let in_edges = self.in_edges(node).unwrap_or_default();
+        let out_edges = self.out_edges(node).unwrap_or_default();
+
+        in_edges.iter().flat_map(move |parent| {
+            let parent_edge = &self.edges[*parent as usize];
+            out_edges
+                .iter()
+                .map(move |child| &self.edges[*child as usize])
+                .filter(move |child_edge| parent_edge.source != child_edge.target)
+                .map(move |child_edge| {
+                    self.shortcuts.length(parent_edge.shortcut)
+                        + self.shortcuts.length(child_edge.shortcut)
+                })
+        })
+    }
+
+    /// The edge in the opposite direction of `edge` with the same weight, over the same roads.
+    fn reverse(&self, edge: &IntermediateEdge) -> Option<&IntermediateEdge> {
+        self.out_edges(edge.target)?
+            .iter()
+            .map(|index| &self.edges[*index as usize])
+            .find(|x| {
+                x.target == edge.source
+                    && x.weight == edge.weight
+                    && self.shortcuts.is_reverse(edge.shortcut, x.shortcut)
+            })
+    }
 
-        let mut collects = Vec::new();
-
-        for (parent, parent_edge) in parents {
-            debug_assert_eq!(parent, &parent_edge.source);
-            for (child, child_edge) in children {
-                debug_assert_eq!(child, &child_edge.target);
-                if parent.0 != child.0 {
-                    let distance = parent_edge.weight + child_edge.weight;
-                    let (state, road_ids) = collect_shortcut_data_edges(parent_edge, child_edge);
-                    let shortcut = IntermediateEdge::new(
-                        *parent,
-                        *child,
-                        distance,
-                        ShortcutState::Shortcut(state),
-                        ShortcutState::Shortcut(road_ids),
-                        EdgeDirection::Forward,
-                    );
-
-                    collects.push(shortcut);
+    /// The shortcuts that bypass `node`, which only reads the network.
+    pub fn plan_bypass(&self, node: NodeId) -> Bypass {
+        let parents = self.in_edges(node).unwrap_or_default();
+        let children = self.out_edges(node).unwrap_or_default();
+
+        let mut pairs = Vec::new();
+        for parent_edge in parents.iter().map(|x| &self.edges[*x as usize]) {
+            for child_edge in children.iter().map(|x| &self.edges[*x as usize]) {
+                if parent_edge.source != child_edge.target {
+                    pairs.push((parent_edge.clone(), child_edge.clone()));
                 }
             }
         }
 
-        let touched = parents.keys().chain(children.keys()).cloned().collect();
+        let touched = self.neighbours(node).collect();
 
-        self.remove_node(node);
+        Bypass {
+            node,
+            pairs,
+            touched,
+        }
+    }
 
-        for shortcut in collects {
-            self.add_edge(shortcut)
+    /// Remove the node of `bypass` and add its shortcuts, returns the neighbours of the node.
+    pub fn apply_bypass(&mut self, bypass: Bypass) -> Vec<NodeId> {
+        self.remove_node(bypass.node);
+
+        for (parent_edge, child_edge) in bypass.pairs {
+            let shortcut = self
+                .shortcuts
+                .concat(parent_edge.shortcut, child_edge.shortcut);
+
+            self.add_edge(IntermediateEdge::new(
+                parent_edge.source,
+                child_edge.target,
+                parent_edge.weight + child_edge.weight,
+                shortcut,
+            ));
         }
 
-        touched
+        bypass.touched
     }
 }
 
-fn collect_shortcut_data_edges(
-    parent_edge: &IntermediateEdge,
-    child_edge: &IntermediateEdge,
-) -> (Vec<u32>, Vec<usize>) {
-    let mut edge_id_a = Vec::from(parent_edge.data.clone());
-    let mut road_id_a = Vec::from(parent_edge.road_id.clone());
-
-    edge_id_a.extend(Vec::from(child_edge.data.clone()));
-    road_id_a.extend(Vec::from(child_edge.road_id.clone()));
-
-    (edge_id_a, road_id_a)
+/// The shortcuts that replace a node, a pair of an incoming and an outgoing edge for each.
+#[derive(Debug)]
+pub(crate) struct Bypass {
+    node: NodeId,
+    pairs: Vec<(IntermediateEdge, IntermediateEdge)>,
+    /// The neighbours of the node.
+    touched: Vec<NodeId>,
 }
 
-impl FromIterator<IntermediateEdge> for IntermediateNetwork {
-    fn from_iter<T: IntoIterator<Item = IntermediateEdge>>(iter: T) -> Self {
-        let mut intermediate = IntermediateNetwork::default();
-        for edge in iter.into_iter() {
-            intermediate.add_edge(edge);
-        }
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct IntermediateData {
+    /// The node of the lower layer of every node.
+    references: Vec<NodeId>,
+    shortcuts: Vec<ShortcutId>,
+    arena: ShortcutArena,
+}
 
-        intermediate
+impl IntermediateData {
+    pub fn arena(&self) -> &ShortcutArena {
+        &self.arena
     }
-}
 
-impl FromParallelIterator<IntermediateEdge> for IntermediateNetwork {
-    fn from_par_iter<I>(par_iter: I) -> Self
-    where
-        I: rayon::iter::IntoParallelIterator<Item = IntermediateEdge>,
-    {
-        let mut intermediate = IntermediateNetwork::default();
-        let edges = par_iter.into_par_iter().collect::<Vec<_>>();
-        println!("Collected");
-        for edge in edges {
-            intermediate.add_edge(edge);
-        }
+    /// The number of bytes of the node references, the edge shortcuts and the arena.
+    pub fn memory_size(&self) -> usize {
+        self.references.len() * std::mem::size_of::<NodeId>()
+            + self.shortcuts.len() * std::mem::size_of::<ShortcutId>()
+            + self.arena.memory_size()
+    }
 
-        intermediate
+    /// The edges of the lower layer that `edge` consists of.
+    pub fn shortcut_edges(&self, edge: EdgeId) -> ShortcutState<u32> {
+        self.arena.edges(self.shortcuts[edge.0 as usize])
     }
-}
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct IntermediateData {
-    references: HashMap<NodeId, NodeId>,
-    shortcuts: HashMap<EdgeId, ShortcutState<u32>>,
-    road_ids: HashMap<EdgeId, ShortcutState<usize>>,
+    /// Update the references to the network this layer was calculated from,
+    /// after that network was renumbered with [`DirectedNetworkGraph::reorder`].
+    pub fn remap(&mut self, permutation: &Permutation) {
+        for node in self.references.iter_mut() {
+            *node = permutation.node(*node);
+        }
+        self.arena.remap(permutation);
+    }
 }
 
 impl NetworkData for IntermediateData {
     type NodeData = NodeId;
-    type EdgeData = ShortcutState<u32>;
+    type EdgeData = ShortcutId;
 
     fn node_data(&self, node: NodeId) -> &Self::NodeData {
-        &self.references[&node]
+        &self.references[node.0 as usize]
     }
 
     fn edge_data(&self, edge: EdgeId) -> &Self::EdgeData {
-        &self.shortcuts[&edge]
+        &self.shortcuts[edge.0 as usize]
     }
 
     fn with_size(node_size: usize, edge_size: usize) -> Self {
         Self {
-            references: HashMap::with_capacity(node_size),
-            shortcuts: HashMap::with_capacity(edge_size),
-            road_ids: HashMap::with_capacity(edge_size),
+            references: Vec::with_capacity(node_size),
+            shortcuts: Vec::with_capacity(edge_size),
+            arena: ShortcutArena::default(),
+        }
+    }
+
+    fn derive_with_size(&self, node_size: usize, edge_size: usize) -> Self {
+        Self {
+            arena: self.arena.clone(),
+            ..Self::with_size(node_size, edge_size)
         }
     }
 
-    fn add_node(&mut self, _: NodeId, _: Self::NodeData) {}
+    fn add_node(&mut self, node: NodeId, data: Self::NodeData) {
+        debug_assert_eq!(node.0 as usize, self.references.len());
+        self.references.push(data);
+    }
 
-    fn add_edge(&mut self, edge: EdgeId, data: Self::EdgeData, road_id: ShortcutState<usize>) {
-        self.shortcuts.insert(edge, data);
-        self.road_ids.insert(edge, road_id);
+    /// The roads are stored in the arena, with the shortcut.
+    fn add_edge(&mut self, edge: EdgeId, data: Self::EdgeData, _: ShortcutState<usize>) {
+        debug_assert_eq!(edge.0 as usize, self.shortcuts.len());
+        self.shortcuts.push(data);
     }
 
     fn edge_road_id(&self, edge: EdgeId) -> ShortcutState<usize> {
-        self.road_ids[&edge].clone()
+        self.arena.road_ids(self.shortcuts[edge.0 as usize])
     }
 }
 
+/// The nodes with an edge are numbered in order of their id in the lower layer.
+/// The edges of a node are sorted by direction and target, like the builder does.
+/// Two edges in opposite directions with the same weight and the same roads in reverse order,
+/// like both directions of a two way road or of a shortcut over it, become one `Both` edge.
+/// Its data at each end is the shortcut that leaves that node.
 impl From<IntermediateNetwork> for DirectedNetworkGraph<IntermediateData> {
     fn from(val: IntermediateNetwork) -> Self {
-        let mut builder = DirectedNetworkBuilder::<IntermediateNode, IntermediateEdge>::new();
+        let size = val.out_edges.len();
+        let has_edges = |node: usize| {
+            val.out_edges[node].as_ref().is_some_and(|x| !x.is_empty())
+                || val.in_edges[node].as_ref().is_some_and(|x| !x.is_empty())
+        };
 
-        for node in val.nodes() {
-            for (_, edge) in val.out_edges(node).unwrap() {
-                let n1 = builder.add_node(IntermediateNode(edge.source));
-                let n2 = builder.add_node(IntermediateNode(edge.target));
+        let mut new_ids = vec![NodeId(u32::MAX); size];
+        let mut references = Vec::new();
+        for node in (0..size).filter(|x| has_edges(*x)) {
+            new_ids[node] = NodeId::from(references.len());
+            references.push(NodeId::from(node));
+        }
 
-                builder.add_edge(IntermediateEdge {
-                    source: n1,
-                    target: n2,
-                    ..edge.clone()
+        let mut nodes = Vec::with_capacity(references.len());
+        let mut edges = Vec::new();
+        let mut shortcuts = Vec::new();
+
+        for reference in &references {
+            let node = reference.0 as usize;
+
+            let outs = val.out_edges[node].iter().flatten().map(|index| {
+                let edge = &val.edges[*index as usize];
+                let direction = if val.reverse(edge).is_some() {
+                    EdgeDirection::Both
+                } else {
+                    EdgeDirection::Forward
+                };
+                (direction, new_ids[edge.target.0 as usize], edge)
+            });
+            // Edges with a reverse are already added as `Both`, this includes loops.
+            let ins = val.in_edges[node]
+                .iter()
+                .flatten()
+                .map(|index| &val.edges[*index as usize])
+                .filter(|edge| val.reverse(edge).is_none())
+                .map(|edge| {
+                    let source = new_ids[edge.source.0 as usize];
+                    (EdgeDirection::Backward, source, edge)
                 });
+
+            let mut node_edges = outs.chain(ins).collect::<Vec<_>>();
+            node_edges.sort_by_key(|(direction, target, _)| (*direction, *target));
+
+            let start_edge_index = edges.len() as u32;
+            for (direction, target, edge) in node_edges {
+                shortcuts.push(edge.shortcut);
+                edges.push(NetworkEdge::new(
+                    edges.len() as u32,
+                    target,
+                    edge.weight,
+                    direction,
+                ));
+            }
+
+            nodes.push(NetworkNode::new(start_edge_index, edges.len() as u32));
+        }
+
+        let data = IntermediateData {
+            references,
+            shortcuts,
+            arena: val.shortcuts,
+        };
+
+        DirectedNetworkGraph::new(nodes, edges, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IntermediateData, IntermediateNetwork, ShortcutLength};
+    use crate::generation::{calculate_layer, contraction::ContractionFactor, grid_network};
+    use graph::{
+        builder::EdgeDirection, create_network, DirectedNetworkGraph, EdgeId, NetworkData, NodeId,
+        ShortcutState,
+    };
+
+    #[test]
+    fn equal_reverse_edges_become_both() {
+        let mut intermediate = IntermediateNetwork::with_size(3);
+        intermediate.add_lower_edge(NodeId(0), NodeId(1), 2.0, 0, ShortcutState::Single(10));
+        intermediate.add_lower_edge(NodeId(1), NodeId(0), 2.0, 1, ShortcutState::Single(10));
+        intermediate.add_lower_edge(NodeId(1), NodeId(2), 3.0, 2, ShortcutState::Single(11));
+        intermediate.add_lower_edge(NodeId(2), NodeId(1), 4.0, 3, ShortcutState::Single(12));
+
+        let layer = DirectedNetworkGraph::<IntermediateData>::from(intermediate);
+        assert_eq!(layer.validate(), Ok(()));
+        assert_eq!(layer.edges().len(), 6);
+
+        let edges = |node: u32| {
+            let node = layer.node(NodeId(node));
+            (node.start_edge_index..node.last_edge_index)
+                .map(EdgeId::from)
+                .map(|id| {
+                    let edge = layer.edge(id);
+                    let lower = layer.data.shortcut_edges(id);
+                    (edge.direction(), edge.target().0, edge.distance(), lower)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The data of a `Both` edge is the shortcut that leaves the node.
+        assert_eq!(
+            edges(0),
+            vec![(EdgeDirection::Both, 1, 2.0, ShortcutState::Single(0))]
+        );
+        assert_eq!(
+            edges(1),
+            vec![
+                (EdgeDirection::Forward, 2, 3.0, ShortcutState::Single(2)),
+                (EdgeDirection::Both, 0, 2.0, ShortcutState::Single(1)),
+                (EdgeDirection::Backward, 2, 4.0, ShortcutState::Single(3)),
+            ]
+        );
+        assert_eq!(
+            edges(2),
+            vec![
+                (EdgeDirection::Forward, 1, 4.0, ShortcutState::Single(3)),
+                (EdgeDirection::Backward, 1, 3.0, ShortcutState::Single(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn reverse_edges_over_other_roads_stay_apart() {
+        // 0 => 1 => 2 and 2 => 3 => 0 are one way roads, 0 - 4 is a two way road.
+        let mut intermediate = IntermediateNetwork::with_size(5);
+        intermediate.add_lower_edge(NodeId(0), NodeId(1), 1.0, 0, ShortcutState::Single(10));
+        intermediate.add_lower_edge(NodeId(1), NodeId(2), 1.0, 1, ShortcutState::Single(11));
+        intermediate.add_lower_edge(NodeId(2), NodeId(3), 1.0, 2, ShortcutState::Single(12));
+        intermediate.add_lower_edge(NodeId(3), NodeId(0), 1.0, 3, ShortcutState::Single(13));
+        let roads = ShortcutState::Shortcut(vec![14, 15]);
+        intermediate.add_lower_edge(NodeId(0), NodeId(4), 5.0, 4, roads);
+        let roads = ShortcutState::Shortcut(vec![15, 14]);
+        intermediate.add_lower_edge(NodeId(4), NodeId(0), 5.0, 5, roads);
+        // The shortcuts 0 => 2 and 2 => 0 that bypass 1 and 3 have the same weight.
+        for node in [NodeId(1), NodeId(3)] {
+            let bypass = intermediate.plan_bypass(node);
+            intermediate.apply_bypass(bypass);
+        }
+
+        let layer = DirectedNetworkGraph::<IntermediateData>::from(intermediate);
+        assert_eq!(layer.validate(), Ok(()));
+
+        let node = layer.node(NodeId(0));
+        let edges = (node.start_edge_index..node.last_edge_index)
+            .map(EdgeId::from)
+            .map(|id| {
+                let edge = layer.edge(id);
+                let roads = layer.data.edge_road_id(id);
+                (edge.direction(), edge.target().0, edge.distance(), roads)
+            })
+            .collect::<Vec<_>>();
+        // Node 2 is now 1 and node 4 is 2.
+        assert_eq!(
+            edges,
+            vec![
+                (
+                    EdgeDirection::Forward,
+                    1,
+                    2.0,
+                    ShortcutState::Shortcut(vec![10, 11])
+                ),
+                (
+                    EdgeDirection::Both,
+                    2,
+                    5.0,
+                    ShortcutState::Shortcut(vec![14, 15])
+                ),
+                (
+                    EdgeDirection::Backward,
+                    1,
+                    2.0,
+                    ShortcutState::Shortcut(vec![12, 13])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn layer_edges_unpack_to_paths() {
+        let network = grid_network(12);
+        let layer = calculate_layer(3, &network, &ContractionFactor::new(2.0));
+
+        assert!(!layer.edges().is_empty());
+        // The roads of `()` are its edge ids, the directions of a street are different roads.
+        assert!(layer
+            .edges()
+            .iter()
+            .all(|x| x.direction() != EdgeDirection::Both));
+        for source in 0..layer.nodes().len() {
+            let source = NodeId::from(source);
+            for (id, edge) in layer.out_edges(source) {
+                let lower = Vec::from(layer.data.shortcut_edges(id));
+
+                // The lower edges are a path from the source to the target.
+                let mut node = *layer.data.node_data(source);
+                let mut weight = 0.0;
+                for lower_edge in lower.iter().map(|x| network.edge(EdgeId(*x))) {
+                    assert_eq!(lower_edge.direction(), EdgeDirection::Forward);
+                    assert!(network
+                        .out_edges(node)
+                        .any(|(_, x)| std::ptr::eq(x, lower_edge)));
+                    node = lower_edge.target();
+                    weight += lower_edge.distance();
+                }
+
+                assert_eq!(node, *layer.data.node_data(edge.target()));
+                assert_eq!(weight, edge.distance());
+                // The roads of the base graph `()` are its edge ids.
+                let roads = lower.iter().map(|x| *x as usize).collect::<Vec<_>>();
+                assert_eq!(Vec::from(layer.data.edge_road_id(id)), roads);
             }
         }
+    }
+
+    #[test]
+    fn remap_after_reorder() {
+        let network = create_network!(
+            0..2,
+            0 => 1; 1.0,
+            1 => 2; 2.0,
+            2 => 0; 3.0
+        );
+        let (reordered, permutation) = network.reorder(&[NodeId(2), NodeId(0), NodeId(1)]);
+
+        let mut data = IntermediateData::default();
+        let single = data.arena.edge(0, ShortcutState::Single(0));
+        let first = data.arena.edge(2, ShortcutState::Single(1));
+        let second = data.arena.edge(4, ShortcutState::Single(2));
+        let pair = data.arena.concat(first, second);
+        data.add_edge(EdgeId(0), single, ShortcutState::Single(0));
+        data.add_edge(EdgeId(1), pair, ShortcutState::Shortcut(vec![1, 2]));
+        data.remap(&permutation);
+
+        let moved = |old: u32| permutation.edge(EdgeId(old)).0;
+        assert_eq!(
+            data.shortcut_edges(EdgeId(0)),
+            ShortcutState::Single(moved(0))
+        );
+        assert_eq!(
+            data.shortcut_edges(EdgeId(1)),
+            ShortcutState::Shortcut(vec![moved(2), moved(4)])
+        );
+        assert_eq!(
+            reordered.edge(EdgeId(moved(2))).target(),
+            permutation.node(network.edge(EdgeId(2)).target())
+        );
+    }
 
-        builder.build()
+    #[test]
+    fn shortcut_arena() {
+        let mut data = IntermediateData::default();
+        let arena = &mut data.arena;
+
+        let a = arena.edge(3, ShortcutState::Single(30));
+        let b = arena.edge(5, ShortcutState::Shortcut(vec![50, 51]));
+        assert_eq!(arena.edge(3, ShortcutState::Single(30)), a);
+
+        let ab = arena.concat(a, b);
+        let aba = arena.concat(ab, a);
+        assert_eq!(arena.concat(a, b), ab);
+        assert_eq!(arena.len(), 4);
+        assert_eq!(arena.length(aba), ShortcutLength { edges: 3, roads: 4 });
+
+        assert_eq!(arena.edges(a), ShortcutState::Single(3));
+        assert_eq!(arena.road_ids(a), ShortcutState::Single(30));
+        assert_eq!(arena.road_ids(b), ShortcutState::Shortcut(vec![50, 51]));
+        assert_eq!(arena.edges(aba), ShortcutState::Shortcut(vec![3, 5, 3]));
+        assert_eq!(
+            arena.road_ids(aba),
+            ShortcutState::Shortcut(vec![30, 50, 51, 30])
+        );
     }
 }
@@ -1,15 +1,21 @@
+use self::contraction::ContractionPolicy;
+use self::dag::HighwayNodeQueue;
 use self::intermediate_network::{IntermediateData, IntermediateNetwork};
-use crate::generation::intermediate_network::IntermediateEdge;
 use graph::{
-    BackwardNeighbourhood, DirectedNetworkGraph, ForwardNeighbourhood, NetworkData, ShortcutState,
+    BackwardNeighbourhood, DirectedNetworkGraph, ForwardNeighbourhood, NeighbourhoodSize,
+    NetworkData, Progress,
 };
 use rayon::prelude::*;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
 
+pub mod contraction;
 pub mod core;
+pub mod customisation;
 pub mod dag;
 pub mod dijkstra;
+pub mod hierarchy;
 pub mod intermediate_network;
+pub mod statistics;
 
 macro_rules! stopwatch {
     ($x:expr) => {{
@@ -44,24 +50,11 @@ macro_rules! stopwatch {
     }};
 }
 
-pub fn calculate_layer<D: NetworkData>(
+pub fn calculate_layer<D: NetworkData, P: ContractionPolicy + ?Sized>(
     size: usize,
     network: &DirectedNetworkGraph<D>,
-    contraction_factor: f32,
+    policy: &P,
 ) -> DirectedNetworkGraph<IntermediateData> {
-    let (duration, intermediate) = stopwatch!(phase_1(size, network));
-
-    println!("Finished phase 1 {}ms", duration.as_millis());
-
-    let (duration, intermediate) = stopwatch!(phase_2(intermediate, contraction_factor));
-    println!("Finished phase 2 {}ms", duration.as_millis());
-
-    DirectedNetworkGraph::from(intermediate)
-}
-pub(crate) fn phase_1<D: NetworkData>(
-    size: usize,
-    network: &DirectedNetworkGraph<D>,
-) -> IntermediateNetwork {
     println!("Start computing (forward backward)");
 
     let (duration, computed) = stopwatch!(ComputedState::new(size, network));
@@ -70,62 +63,311 @@ pub(crate) fn phase_1<D: NetworkData>(
         "Finished computing (forward backward) {}ms",
         duration.as_millis()
     );
+
+    calculate_layer_with_state(&computed, network, policy, &Progress::default())
+        .expect("Not cancelled")
+}
+
+/// Like [`calculate_layer`], with the neighbourhoods of `network` that are already computed.
+/// Phase 1 and 2 report into `progress`, returns `None` when it is cancelled.
+/// Panics when `computed` was computed for another network.
+pub fn calculate_layer_with_state<D: NetworkData, P: ContractionPolicy + ?Sized>(
+    computed: &ComputedState,
+    network: &DirectedNetworkGraph<D>,
+    policy: &P,
+    progress: &Progress,
+) -> Option<DirectedNetworkGraph<IntermediateData>> {
+    assert_eq!(
+        computed.network,
+        network.fingerprint(),
+        "The neighbourhoods are computed for another network"
+    );
+
+    let (duration, intermediate) = stopwatch!(phase_1(computed, network, progress));
+
+    println!("Finished phase 1 {}ms", duration.as_millis());
+
+    let (duration, intermediate) = stopwatch!(phase_2(intermediate?, policy, progress));
+    println!("Finished phase 2 {}ms", duration.as_millis());
+
+    let layer = DirectedNetworkGraph::from(intermediate?);
+    layer.debug_validate();
+
+    Some(layer)
+}
+
+pub(crate) fn phase_1<D: NetworkData>(
+    computed: &ComputedState,
+    network: &DirectedNetworkGraph<D>,
+    progress: &Progress,
+) -> Option<IntermediateNetwork> {
+    progress.start("Edges", network.nodes().len());
     println!(
         "Start computing (nodes: {}, edges collections: {})",
         network.nodes().len(),
         network.edges().len()
     );
 
-    let (duration, edges) = stopwatch!(network
+    let (duration, mut edges) = stopwatch!(network
         .nodes()
         .par_iter()
         .enumerate()
-        .flat_map_iter(|(id, _)| dijkstra::calculate_edges(id.into(), &computed, network))
-        .collect::<HashSet<_>>());
+        .map_init(
+            || HighwayNodeQueue::new(network.nodes().len()),
+            |queue, (id, _)| {
+                if progress.is_cancelled() {
+                    return Vec::new();
+                }
+
+                let edges = dijkstra::calculate_edges(id.into(), computed, network, queue);
+                progress.advance(1);
+                edges
+            }
+        )
+        .flatten_iter()
+        .collect::<Vec<_>>());
 
     println!(
         "Finished computing (edges collections) {}ms",
         duration.as_millis()
     );
 
-    let edges = edges
-        .into_iter()
-        .map(|(source, edge_id)| {
-            let edge = network.edge(edge_id);
-            IntermediateEdge::new(
-                source,
-                edge.target(),
-                edge.distance(),
-                ShortcutState::Single(edge.edge_id),
-                network.data.edge_road_id(edge_id),
-                graph::builder::EdgeDirection::Forward,
-            )
-        })
-        .collect();
+    if progress.is_cancelled() {
+        return None;
+    }
+
+    // Sorted, so the same edge wins when there are parallel edges,
+    // and the edges are added in the same order for every number of threads.
+    edges.sort_unstable();
+    edges.dedup();
+
+    let mut intermediate = IntermediateNetwork::with_size(network.nodes().len());
+    for (source, edge_id) in edges {
+        let edge = network.edge(edge_id);
+        intermediate.add_lower_edge(
+            source,
+            edge.target(),
+            edge.distance(),
+            edge.edge_id,
+            network.data.edge_road_id(edge_id),
+        );
+    }
     println!("Finished computing (edges collections)");
 
-    edges
+    Some(intermediate)
 }
 
 /**
  * Calculate the core network
  */
-fn phase_2(intermediate: IntermediateNetwork, contraction_factor: f32) -> IntermediateNetwork {
-    core::core_network_with_patch(intermediate, contraction_factor)
+fn phase_2<P: ContractionPolicy + ?Sized>(
+    intermediate: IntermediateNetwork,
+    policy: &P,
+    progress: &Progress,
+) -> Option<IntermediateNetwork> {
+    core::core_network_with_patch(intermediate, policy, progress)
 }
 
+/// The neighbourhoods of a network, they only depend on the network and the size.
+/// It can be written to a file and used again for phase 1 with other contraction settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComputedState {
+    pub size: NeighbourhoodSize,
+    /// The [`DirectedNetworkGraph::fingerprint`] of the network.
+    pub network: u64,
     pub forward: ForwardNeighbourhood,
     pub backward: BackwardNeighbourhood,
 }
 
 impl ComputedState {
     pub fn new<D: NetworkData>(size: usize, network: &DirectedNetworkGraph<D>) -> Self {
+        Self::with_progress(size.into(), network, &Progress::default()).expect("Not cancelled")
+    }
+
+    /// Like [`Self::new`], reports into `progress` and returns `None` when it is cancelled.
+    pub fn with_progress<D: NetworkData>(
+        size: NeighbourhoodSize,
+        network: &DirectedNetworkGraph<D>,
+        progress: &Progress,
+    ) -> Option<Self> {
+        progress.start("Neighbourhoods", network.nodes().len() * 2);
+
         let (forward, backward) = rayon::join(
-            || ForwardNeighbourhood::from_network(size, network),
-            || BackwardNeighbourhood::from_network(size, network),
+            || ForwardNeighbourhood::with_progress(size, network, progress),
+            || BackwardNeighbourhood::with_progress(size, network, progress),
         );
 
-        ComputedState { forward, backward }
+        Some(ComputedState {
+            size,
+            network: network.fingerprint(),
+            forward: forward?,
+            backward: backward?,
+        })
+    }
+}
+
+/// Run `generate` in a thread pool with `threads` threads,
+/// to check that the generated layers do not depend on the number of threads.
+#[cfg(test)]
+pub(crate) fn generate_with_threads<T, F>(threads: usize, generate: F) -> T
+where
+    T: Send,
+    F: FnOnce() -> T + Send,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("Could not build thread pool");
+
+    pool.install(generate)
+}
+
+/// A grid of two way streets with varying lengths, with `size * size` nodes.
+#[cfg(test)]
+pub(crate) fn grid_network(size: usize) -> DirectedNetworkGraph<()> {
+    use graph::builder::{DefaultEdgeBuilder, DirectedNetworkBuilder};
+
+    let mut builder = DirectedNetworkBuilder::<usize, DefaultEdgeBuilder>::new();
+    for y in 0..size {
+        for x in 0..size {
+            let node = builder.add_node(y * size + x);
+            for (other, weight) in [(x + 1, y), (x, y + 1)]
+                .into_iter()
+                .filter(|(x, y)| *x < size && *y < size)
+                .map(|(x, y)| (y * size + x, 1.0 + ((x * 7 + y * 3) % 5) as f32))
+            {
+                let other = builder.add_node(other);
+                builder.add_edge(DefaultEdgeBuilder::forward(node, other, 0, weight));
+                builder.add_edge(DefaultEdgeBuilder::forward(other, node, 0, weight));
+            }
+        }
+    }
+
+    builder.build()
+}
+
+/// A network of two way roads `(a, b, weight)`, the nodes are numbered from 0.
+#[cfg(test)]
+pub(crate) fn two_way_network(roads: &[(usize, usize, f32)]) -> DirectedNetworkGraph<()> {
+    use graph::builder::{DefaultEdgeBuilder, DirectedNetworkBuilder};
+
+    let mut builder = DirectedNetworkBuilder::<usize, DefaultEdgeBuilder>::new();
+    let size = roads.iter().map(|(a, b, _)| a.max(b) + 1).max();
+    for node in 0..size.unwrap_or_default() {
+        builder.add_node(node);
+    }
+    for (a, b, weight) in roads {
+        let a = builder.add_node(*a);
+        let b = builder.add_node(*b);
+        builder.add_edge(DefaultEdgeBuilder::forward(a, b, 0, *weight));
+        builder.add_edge(DefaultEdgeBuilder::forward(b, a, 0, *weight));
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        calculate_layer, calculate_layer_with_state,
+        contraction::{ContractionFactor, EdgeDifference},
+        generate_with_threads, grid_network, two_way_network, ComputedState,
+    };
+    use graph::{create_network, DirectedNetworkGraph, NeighbourhoodSize, NodeId, Progress};
+
+    /// Five hubs that are all connected over a village, with a street of two nodes at every hub.
+    /// Every road is equally long, so the neighbourhoods and shortest paths are full of ties.
+    fn create_ties() -> DirectedNetworkGraph<()> {
+        let mut roads = Vec::new();
+        let mut village = 5;
+        for a in 0..5 {
+            for b in a + 1..5 {
+                roads.push((a, village, 1.0));
+                roads.push((village, b, 1.0));
+                village += 1;
+            }
+        }
+        for hub in 0..5 {
+            let street = 15 + 2 * hub;
+            roads.push((hub, street, 1.0));
+            roads.push((street, street + 1, 1.0));
+        }
+
+        two_way_network(&roads)
+    }
+
+    #[test]
+    fn layers_are_reproducible() {
+        let generate = |threads| {
+            generate_with_threads(threads, || {
+                let policy = ContractionFactor::new(2.0);
+                let network = create_ties();
+                let first = calculate_layer(3, &network, &policy);
+                assert_eq!(first.nodes().len(), 5);
+                let second = calculate_layer(2, &first, &policy);
+
+                bincode::serialize(&(network, first, second)).unwrap()
+            })
+        };
+
+        let bytes = generate(1);
+        assert_eq!(bytes, generate(1));
+        assert_eq!(bytes, generate(4));
+        assert_eq!(bytes, generate(7));
+    }
+
+    #[test]
+    fn cached_state() {
+        let network = grid_network(12);
+        let computed = ComputedState::new(3, &network);
+        let cached: ComputedState =
+            bincode::deserialize(&bincode::serialize(&computed).unwrap()).unwrap();
+
+        assert_eq!(cached.size, NeighbourhoodSize::Nodes(3));
+        for node in (0..network.nodes().len()).map(NodeId::from) {
+            assert_eq!(cached.forward.radius(node), computed.forward.radius(node));
+            assert_eq!(cached.backward.radius(node), computed.backward.radius(node));
+        }
+
+        let progress = Progress::default();
+        for layer in [
+            calculate_layer_with_state(&cached, &network, &ContractionFactor::new(2.0), &progress),
+            calculate_layer_with_state(&cached, &network, &EdgeDifference::default(), &progress),
+        ] {
+            assert_eq!(layer.unwrap().validate(), Ok(()));
+        }
+        let layer =
+            calculate_layer_with_state(&cached, &network, &ContractionFactor::new(2.0), &progress);
+        assert_eq!(
+            bincode::serialize(&layer.unwrap()).unwrap(),
+            bincode::serialize(&calculate_layer(3, &network, &ContractionFactor::new(2.0)))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The neighbourhoods are computed for another network")]
+    fn state_of_another_network() {
+        let network = create_network!(
+            0..2,
+            0 => 1; 1.0,
+            1 => 2; 1.0,
+            2 => 0; 1.0
+        );
+        // The same nodes and edges, with a longer edge.
+        let other = create_network!(
+            0..2,
+            0 => 1; 1.0,
+            1 => 2; 5.0,
+            2 => 0; 1.0
+        );
+        let computed = ComputedState::new(2, &other);
+
+        calculate_layer_with_state(
+            &computed,
+            &network,
+            &ContractionFactor::new(2.0),
+            &Progress::default(),
+        );
     }
 }
@@ -0,0 +1,375 @@
+use super::{
+    hierarchy::{Hierarchy, StopReason},
+    intermediate_network::IntermediateData,
+    ComputedState,
+};
+use graph::{
+    DirectedNetworkGraph, EdgeId, NeighbourhoodSize, NetworkData, NetworkEdge, NetworkNode, NodeId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Write},
+};
+
+/// The minimum, mean, median and maximum of a set of values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub min: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub max: f32,
+}
+
+impl Summary {
+    /// The summary of the finite values, `None` when there are none.
+    pub fn of(values: impl Iterator<Item = f32>) -> Option<Self> {
+        let mut values = values.filter(|x| x.is_finite()).collect::<Vec<_>>();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f32::total_cmp);
+
+        Some(Self {
+            min: values[0],
+            mean: values.iter().sum::<f32>() / values.len() as f32,
+            median: values[values.len() / 2],
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+/// The statistics of one level of a [`Hierarchy`], level 0 is the base graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelReport {
+    pub level: usize,
+    pub nodes: usize,
+    /// The entries in the edge list, every edge is stored at both nodes.
+    pub edges: usize,
+    /// The edges that consist of more than one edge of the level below.
+    pub shortcuts: usize,
+    /// The nodes of the highway network of the level below that were bypassed in phase 2.
+    pub bypassed_nodes: usize,
+    /// The number of nodes for every degree, `degrees[d]` nodes have `d` edges.
+    pub degrees: Vec<usize>,
+    /// The neighbourhood size of the radii, `None` for the top level.
+    pub neighbourhood_size: Option<NeighbourhoodSize>,
+    pub forward_radius: Option<Summary>,
+    pub backward_radius: Option<Summary>,
+    /// The number of roads an edge unpacks to.
+    pub unpack_mean: f32,
+    pub unpack_max: usize,
+    /// The number of bytes of the nodes and edges, and of the shortcuts for the levels above the base graph.
+    pub memory: usize,
+    /// The time to build the level, `None` for the base graph.
+    pub duration_ms: Option<u64>,
+}
+
+impl LevelReport {
+    pub fn mean_degree(&self) -> f32 {
+        let nodes = self.degrees.iter().sum::<usize>();
+        let edges = self
+            .degrees
+            .iter()
+            .enumerate()
+            .map(|(degree, count)| degree * count)
+            .sum::<usize>();
+
+        edges as f32 / nodes.max(1) as f32
+    }
+
+    pub fn max_degree(&self) -> usize {
+        self.degrees.len().saturating_sub(1)
+    }
+}
+
+/// The statistics of all levels of a [`Hierarchy`].
+/// Written as JSON, the reports of runs with different configurations can be compared with [`comparison_table`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HierarchyReport {
+    pub levels: Vec<LevelReport>,
+    pub stop_reason: StopReason,
+}
+
+impl HierarchyReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Could not serialize")
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+impl Hierarchy {
+    /// The statistics of `base` and the layers of the hierarchy on top of it.
+    pub fn report<D: NetworkData>(&self, base: &DirectedNetworkGraph<D>) -> HierarchyReport {
+        let mut levels = vec![level_report(0, base, self.radii.first(), |edge| {
+            Vec::from(base.data.edge_road_id(edge)).len()
+        })];
+        levels[0].neighbourhood_size = self.statistics.first().map(|x| x.neighbourhood_size);
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            let level = index + 1;
+            let arena = layer.data.arena();
+            let mut report = level_report(level, layer, self.radii.get(level), |edge| {
+                arena.length(*layer.edge_data(edge)).roads as usize
+            });
+
+            report.shortcuts = (0..layer.edges().len())
+                .filter(|x| arena.length(*layer.edge_data(EdgeId::from(*x))).edges > 1)
+                .count();
+            report.bypassed_nodes = match index {
+                0 => highway_nodes(base, &layer.data),
+                _ => highway_nodes(&self.layers[index - 1], &layer.data),
+            }
+            .saturating_sub(layer.nodes().len());
+            report.memory += layer.data.memory_size();
+            report.neighbourhood_size = self.statistics.get(level).map(|x| x.neighbourhood_size);
+            report.duration_ms = Some(self.statistics[index].duration.as_millis() as u64);
+
+            levels.push(report);
+        }
+
+        HierarchyReport {
+            levels,
+            stop_reason: self.stop_reason,
+        }
+    }
+}
+
+fn level_report<D: NetworkData, F: Fn(EdgeId) -> usize>(
+    level: usize,
+    network: &DirectedNetworkGraph<D>,
+    radii: Option<&ComputedState>,
+    unpack_length: F,
+) -> LevelReport {
+    let mut degrees = Vec::new();
+    for node in network.nodes() {
+        let degree = (node.last_edge_index - node.start_edge_index) as usize;
+        if degrees.len() <= degree {
+            degrees.resize(degree + 1, 0);
+        }
+        degrees[degree] += 1;
+    }
+
+    let nodes = (0..network.nodes().len()).map(NodeId::from);
+    let forward_radius =
+        radii.and_then(|x| Summary::of(nodes.clone().map(|node| x.forward.radius(node))));
+    let backward_radius =
+        radii.and_then(|x| Summary::of(nodes.map(|node| x.backward.radius(node))));
+
+    let unpack_lengths = (0..network.edges().len())
+        .map(|x| unpack_length(EdgeId::from(x)))
+        .collect::<Vec<_>>();
+
+    LevelReport {
+        level,
+        nodes: network.nodes().len(),
+        edges: network.edges().len(),
+        shortcuts: 0,
+        bypassed_nodes: 0,
+        degrees,
+        neighbourhood_size: None,
+        forward_radius,
+        backward_radius,
+        unpack_mean: unpack_lengths.iter().sum::<usize>() as f32
+            / unpack_lengths.len().max(1) as f32,
+        unpack_max: unpack_lengths.iter().copied().max().unwrap_or_default(),
+        memory: network.nodes().len() * std::mem::size_of::<NetworkNode>()
+            + network.edges().len() * std::mem::size_of::<NetworkEdge>(),
+        duration_ms: None,
+    }
+}
+
+/// The number of nodes of `lower` with an edge that phase 1 selected for `layer`.
+fn highway_nodes<D: NetworkData>(
+    lower: &DirectedNetworkGraph<D>,
+    layer: &IntermediateData,
+) -> usize {
+    let edges = layer.arena().lower_edges().collect::<HashSet<_>>();
+
+    let mut nodes = vec![false; lower.nodes().len()];
+    for (id, node) in lower.nodes().iter().enumerate() {
+        for edge in &lower.edges()[node.start_edge_index as usize..node.last_edge_index as usize] {
+            if edges.contains(&edge.edge_id) {
+                nodes[id] = true;
+                nodes[edge.target().0 as usize] = true;
+            }
+        }
+    }
+
+    nodes.into_iter().filter(|x| *x).count()
+}
+
+fn radius(summary: Option<Summary>) -> String {
+    summary
+        .map(|x| format!("{:.0}/{:.0}", x.median, x.max))
+        .unwrap_or_else(|| String::from("-"))
+}
+
+/// A markdown table with a row for every level.
+impl Display for HierarchyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "| Level | Nodes | Edges | Shortcuts | Bypassed | Degree (mean/max) | N | Forward radius (median/max) | Backward radius (median/max) | Unpack (mean/max) | Memory (KiB) | ms |"
+        )?;
+        writeln!(f, "|{}", " --- |".repeat(12))?;
+
+        for level in &self.levels {
+            writeln!(
+                f,
+                "| {} | {} | {} | {} | {} | {:.2}/{} | {} | {} | {} | {:.1}/{} | {} | {} |",
+                level.level,
+                level.nodes,
+                level.edges,
+                level.shortcuts,
+                level.bypassed_nodes,
+                level.mean_degree(),
+                level.max_degree(),
+                level
+                    .neighbourhood_size
+                    .map_or_else(|| String::from("-"), |x| x.to_string()),
+                radius(level.forward_radius),
+                radius(level.backward_radius),
+                level.unpack_mean,
+                level.unpack_max,
+                level.memory / 1024,
+                level
+                    .duration_ms
+                    .map_or_else(|| String::from("-"), |x| x.to_string()),
+            )?;
+        }
+
+        write!(f, "Stopped: {:?}", self.stop_reason)
+    }
+}
+
+/// A markdown table with the nodes, edges and build time of every level for every named run.
+pub fn comparison_table(runs: &[(String, HierarchyReport)]) -> String {
+    let mut table = String::from("| Level |");
+    for (name, _) in runs {
+        write!(table, " {0} n | {0} e | {0} ms |", name).unwrap();
+    }
+    writeln!(table, "\n|{}", " --- |".repeat(runs.len() * 3 + 1)).unwrap();
+
+    let levels = runs
+        .iter()
+        .map(|x| x.1.levels.len())
+        .max()
+        .unwrap_or_default();
+    for level in 0..levels {
+        write!(table, "| {} |", level).unwrap();
+        for (_, report) in runs {
+            match report.levels.get(level) {
+                Some(x) => write!(
+                    table,
+                    " {} | {} | {} |",
+                    x.nodes,
+                    x.edges,
+                    x.duration_ms
+                        .map_or_else(|| String::from("-"), |x| x.to_string())
+                )
+                .unwrap(),
+                None => table.push_str(" - | - | - |"),
+            }
+        }
+        table.push('\n');
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{comparison_table, HierarchyReport, Summary};
+    use crate::generation::{
+        hierarchy::{build_hierarchy, HierarchyConfig},
+        two_way_network,
+    };
+    use graph::{DirectedNetworkGraph, NeighbourhoodSize};
+
+    /// Five hubs that are all connected over a village, every road is 5 long.
+    /// The villages are bypassed, the layer consists of the hubs.
+    fn create_villages() -> DirectedNetworkGraph<()> {
+        let mut roads = Vec::new();
+        let mut village = 5;
+        for a in 0..5 {
+            for b in a + 1..5 {
+                roads.push((a, village, 5.0));
+                roads.push((village, b, 5.0));
+                village += 1;
+            }
+        }
+
+        two_way_network(&roads)
+    }
+
+    #[test]
+    fn hierarchy_report() {
+        let network = create_villages();
+        let config = HierarchyConfig {
+            neighbourhood_sizes: vec![3.into()],
+            max_levels: 2,
+            max_core_ratio: 1.0,
+            ..Default::default()
+        };
+        let hierarchy = build_hierarchy(&network, &config);
+        let report = hierarchy.report(&network);
+
+        assert_eq!(hierarchy.layers.len(), 1);
+        assert_eq!(report.levels.len(), 2);
+
+        let base = &report.levels[0];
+        assert_eq!(base.nodes, 15);
+        assert_eq!(base.edges, 80);
+        assert_eq!(base.shortcuts, 0);
+        assert_eq!(base.bypassed_nodes, 0);
+        assert_eq!(base.unpack_max, 1);
+        // The villages have two neighbours and the hubs four, with an edge in each direction.
+        assert_eq!(base.degrees.iter().sum::<usize>(), 15);
+        assert_eq!(base.degrees[4], 10);
+        assert_eq!(base.degrees[8], 5);
+        assert_eq!(base.max_degree(), 8);
+        assert_eq!(base.neighbourhood_size, Some(NeighbourhoodSize::Nodes(3)));
+        // The neighbourhood of 3 nodes reaches the two nearest neighbours.
+        assert_eq!(base.forward_radius.unwrap().max, 5.0);
+        assert_eq!(base.duration_ms, None);
+
+        // Every edge of the layer is a shortcut over a village.
+        let layer = &report.levels[1];
+        assert_eq!(layer.nodes, 5);
+        assert_eq!(layer.edges, 40);
+        assert_eq!(layer.shortcuts, 40);
+        assert_eq!(layer.bypassed_nodes, 10);
+        assert_eq!(layer.unpack_mean, 2.0);
+        assert_eq!(layer.unpack_max, 2);
+        assert_eq!(layer.degrees[8], 5);
+        assert!(layer.memory > 0);
+        assert!(layer.duration_ms.is_some());
+        // The top level has no neighbourhood.
+        assert_eq!(layer.forward_radius, None);
+
+        let json = report.to_json();
+        assert_eq!(HierarchyReport::from_json(&json).unwrap(), report);
+
+        let table = report.to_string();
+        assert_eq!(table.lines().count(), report.levels.len() + 3);
+
+        let table = comparison_table(&[
+            (String::from("a"), report.clone()),
+            (String::from("b"), report),
+        ]);
+        assert!(table.starts_with("| Level | a n | a e | a ms | b n |"));
+    }
+
+    #[test]
+    fn summary() {
+        let summary = Summary::of([4.0, 1.0, f32::INFINITY, 2.0].into_iter()).unwrap();
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.median, 2.0);
+        assert_eq!(summary.max, 4.0);
+        assert!(Summary::of(std::iter::empty()).is_none());
+    }
+}
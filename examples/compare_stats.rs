@@ -0,0 +1,22 @@
+use highway::generation::statistics::{comparison_table, HierarchyReport};
+use std::path::Path;
+
+/// `cargo run --example compare_stats -- <a.json> <b.json>...`
+/// Prints the nodes, edges and build time of every level of the reports that `examples/layer.rs` wrote, side by side.
+fn main() {
+    let runs = std::env::args()
+        .skip(1)
+        .map(|path| {
+            let json = std::fs::read_to_string(&path).expect("Could not read");
+            let report = HierarchyReport::from_json(&json).expect("Not a hierarchy report");
+            let name = Path::new(&path)
+                .file_stem()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or(path);
+
+            (name, report)
+        })
+        .collect::<Vec<_>>();
+
+    println!("{}", comparison_table(&runs));
+}
@@ -1,64 +1,39 @@
-use bevy_dutch_road_highway_node_network::{nwb::NWBNetworkData, read_file, write_file};
-use highway::generation::calculate_layer;
-use graph::{DirectedNetworkGraph, NetworkData};
-
+use bevy_dutch_road_highway_node_network::{
+    nwb::NWBNetworkData, read_file, versioned_path, write_file,
+};
+use graph::{DirectedNetworkGraph, NeighbourhoodSize};
+use highway::generation::hierarchy::{build_hierarchy, HierarchyConfig};
+
+/// `cargo run --release --example layer -- [output.json] [neighbourhood sizes...]`
+/// Prints the statistics of every level and writes them as JSON, default to `data/hierarchy_stats.json`.
+/// Compare the JSON files of runs with `cargo run --example compare_stats -- <a.json> <b.json>...`.
 fn main() {
-    let network: DirectedNetworkGraph<NWBNetworkData> =
-        read_file("data/directed_graph.graph").unwrap();
-
-    println!(
-        "Layer: 0 - n: {}, e: {} ",
-        network.nodes().len(),
-        network.edges().len()
-    );
-
-    let mut layers = vec![calculate_layer(30, &network, 2.0)];
-
-    if let Some(x) = layers.first() {
-        write_file(x, "data/0.graph").expect("Could not write");
-    }
+    let mut args = std::env::args().skip(1);
+    let output = args
+        .next()
+        .unwrap_or_else(|| String::from("data/hierarchy_stats.json"));
+    let neighbourhood_sizes = args
+        .map(|x| NeighbourhoodSize::Nodes(x.parse().expect("Not a neighbourhood size")))
+        .collect::<Vec<_>>();
 
-    for i in 1..7 {
-        let prev_layer = layers.last().unwrap();
-        println!(
-            "Layer: {} - n: {}, e: {} ",
-            i,
-            prev_layer.nodes().len(),
-            prev_layer.edges().len()
-        );
-        let next = calculate_layer(30, prev_layer, 3.0);
-
-        write_file(&next, format!("data/{}.graph", i)).expect("Could not write");
-        layers.push(next);
+    let network: DirectedNetworkGraph<NWBNetworkData> =
+        read_file(versioned_path("data/directed_graph.graph")).unwrap();
+    network.debug_validate();
+
+    let mut config = HierarchyConfig {
+        max_levels: 7,
+        ..Default::default()
+    };
+    if !neighbourhood_sizes.is_empty() {
+        config.neighbourhood_sizes = neighbourhood_sizes;
     }
+    let hierarchy = build_hierarchy(&network, &config);
 
-    data(0, 1, &network, &layers[0]);
-
-    let mut counter = 0;
-
-    for layer in layers.windows(2) {
-        counter += 1;
-        let l1 = &layer[0];
-        let l2 = &layer[1];
-
-        data(counter, counter + 1, l1, l2);
+    for (i, layer) in hierarchy.layers.iter().enumerate() {
+        write_file(layer, format!("data/{}.graph", i)).expect("Could not write");
     }
-}
 
-fn data<A: NetworkData, B: NetworkData>(
-    level_a: usize,
-    level_b: usize,
-    a: &DirectedNetworkGraph<A>,
-    b: &DirectedNetworkGraph<B>,
-) {
-    println!("From: {} to {}", level_a, level_b);
-    let nodes_a = a.nodes().len();
-    let nodes_b = b.nodes().len();
-    let percentage = nodes_b as f32 / nodes_a as f32 - 1.0;
-    println!(
-        "Nodes: {} -> {} {:02}%",
-        nodes_a,
-        nodes_b,
-        percentage * 100.0
-    );
+    let report = hierarchy.report(&network);
+    println!("{}", report);
+    std::fs::write(&output, report.to_json()).expect("Could not write");
 }
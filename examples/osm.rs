@@ -0,0 +1,38 @@
+use bevy_dutch_road_highway_node_network::{osm, write_file};
+use highway::generation::hierarchy::{build_hierarchy, HierarchyConfig};
+
+/// Import an OSM PBF extract and build the first layers of the highway hierarchy.
+/// Usage: cargo run --release --example osm -- <extract.osm.pbf>
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("Usage: osm <extract.osm.pbf>");
+
+    let road_map = osm::load_osm(&path).expect("Could not read OSM extract");
+    let network = osm::preprocess_osm(&road_map);
+
+    write_file(&road_map, "data/osm_road_map.data").expect("Could not write");
+    write_file(&network, "data/osm_directed_graph.graph").expect("Could not write");
+
+    println!(
+        "Layer: 0 - n: {}, e: {} ",
+        network.nodes().len(),
+        network.edges().len()
+    );
+
+    let config = HierarchyConfig {
+        max_levels: 7,
+        ..Default::default()
+    };
+    let hierarchy = build_hierarchy(&network, &config);
+
+    for (i, layer) in hierarchy.layers.iter().enumerate() {
+        println!(
+            "Layer: {} - n: {}, e: {} ",
+            i + 1,
+            layer.nodes().len(),
+            layer.edges().len()
+        );
+        write_file(layer, format!("data/osm_{}.graph", i + 1)).expect("Could not write");
+    }
+}
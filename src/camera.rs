@@ -1,6 +1,9 @@
-use bevy::prelude::*;
+use bevy::{math::DVec2, prelude::*};
 
-use crate::geo_coords::{RijkDriehoekCoordinate, WGS84};
+use crate::{
+    geo_coords::{RijkDriehoekCoordinate, WGS84},
+    world::RenderOrigin,
+};
 
 #[derive(Component)]
 pub struct MainCamera;
@@ -36,12 +39,13 @@ fn load_camera(mut commands: Commands) {
         longitude: 5.1134345,
     };
     let utrecht = RijkDriehoekCoordinate::from(utrecht);
-    let utrecht = Vec2::from(utrecht);
+    commands.insert_resource(RenderOrigin(DVec2::from(utrecht)));
 
+    // The camera starts at the render origin, see `RenderOrigin`.
     commands
         .spawn(Camera3dBundle {
-            transform: Transform::from_translation(utrecht.extend(10.0))
-                .looking_at(utrecht.extend(0.0), Vec3::Y),
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0))
+                .looking_at(Vec3::ZERO, Vec3::Y),
             projection: Projection::Orthographic(Default::default()),
             camera: Camera {
                 hdr: true,
@@ -1,187 +1,468 @@
-use bevy::math::{Vec2, Vec3};
+//! Conversion between Rijksdriehoek (RD, EPSG:28992) and ETRS89/WGS84 coordinates.
+//!
+//! Follows the RDNAPTRANS™2018 procedure: a similarity transformation between the ETRS89
+//! and the RD Bessel datum, followed by the oblique stereographic RD projection.
+//! The RD correction grid (rdcorr2018) is not applied, the tests check that the deviation from the
+//! official transformation stays below 0.1 m at the reference points. Round trips are exact to well below a millimetre.
+use bevy::math::{DVec2, Vec2, Vec3};
+use rayon::prelude::*;
+use std::{
+    f64::consts::{FRAC_PI_2, FRAC_PI_4},
+    sync::OnceLock,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RijkDriehoekCoordinate(pub f64, pub f64);
+
+/// ETRS89 coordinate in degrees, the datum RDNAPTRANS is defined on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Etrs89 {
+    pub longitude: f64,
+    pub latitude: f64,
+}
 
-pub struct RijkDriehoekCoordinate(pub f32, pub f32);
+/// WGS84 coordinate in degrees, as used by GPS and OpenStreetMap.
+/// WGS84 follows the ITRF, which drifts away from ETRS89 with the Eurasian plate (about 2.5 cm a year).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WGS84 {
+    pub longitude: f64,
+    pub latitude: f64,
+}
 
-impl From<RijkDriehoekCoordinate> for Vec2 {
-    fn from(rdc: RijkDriehoekCoordinate) -> Self {
-        Vec2::new(rdc.0, rdc.1)
+/// Epoch used for the plain `From` conversions between WGS84 and the other coordinate systems.
+pub const DEFAULT_EPOCH: f64 = 2024.0;
+
+struct Ellipsoid {
+    a: f64,
+    inverse_flattening: f64,
+}
+
+impl Ellipsoid {
+    fn e_squared(&self) -> f64 {
+        let f = 1.0 / self.inverse_flattening;
+        f * (2.0 - f)
+    }
+
+    fn to_cartesian(&self, latitude: f64, longitude: f64, height: f64) -> [f64; 3] {
+        let e2 = self.e_squared();
+        let (sin_phi, cos_phi) = latitude.sin_cos();
+        let n = self.a / (1.0 - e2 * sin_phi * sin_phi).sqrt();
+
+        [
+            (n + height) * cos_phi * longitude.cos(),
+            (n + height) * cos_phi * longitude.sin(),
+            (n * (1.0 - e2) + height) * sin_phi,
+        ]
+    }
+
+    /// Returns latitude and longitude in radians.
+    fn to_geographic(&self, [x, y, z]: [f64; 3]) -> (f64, f64) {
+        let e2 = self.e_squared();
+        let p = (x * x + y * y).sqrt();
+        let mut latitude = (z / (p * (1.0 - e2))).atan();
+
+        for _ in 0..10 {
+            let sin_phi = latitude.sin();
+            let n = self.a / (1.0 - e2 * sin_phi * sin_phi).sqrt();
+            latitude = ((z + e2 * n * sin_phi) / p).atan();
+        }
+
+        (latitude, y.atan2(x))
     }
 }
 
-impl From<RijkDriehoekCoordinate> for Vec3 {
-    fn from(rdc: RijkDriehoekCoordinate) -> Self {
-        Vec3::new(rdc.0, rdc.1, 0.0)
+const GRS80: Ellipsoid = Ellipsoid {
+    a: 6_378_137.0,
+    inverse_flattening: 298.257_222_101,
+};
+
+const BESSEL: Ellipsoid = Ellipsoid {
+    a: 6_377_397.155,
+    inverse_flattening: 299.152_812_8,
+};
+
+/// Ellipsoidal ETRS89 height used for the 2D transformation, roughly the height of the geoid in the Netherlands.
+const ETRS89_HEIGHT: f64 = 43.0;
+/// Bessel height used for the 2D transformation, the Bessel ellipsoid lies close to NAP level.
+const RD_BESSEL_HEIGHT: f64 = 0.0;
+
+/// Similarity transformation with translation (m), rotation (rad) and scale difference.
+struct Helmert {
+    translation: [f64; 3],
+    rotation: [f64; 3],
+    scale: f64,
+}
+
+impl Helmert {
+    fn apply(&self, [x, y, z]: [f64; 3]) -> [f64; 3] {
+        let [tx, ty, tz] = self.translation;
+        let [rx, ry, rz] = self.rotation;
+        let s = 1.0 + self.scale;
+
+        [
+            tx + s * (x + rz * y - ry * z),
+            ty + s * (-rz * x + y + rx * z),
+            tz + s * (ry * x - rx * y + z),
+        ]
     }
 }
 
-impl From<RijkDriehoekCoordinate> for WGS84 {
-    fn from(rdc: RijkDriehoekCoordinate) -> Self {
-        // Amersfoort
-        let reference_rd_x = 155000.0;
-        let reference_rd_y = 463000.0;
-
-        let dx = (rdc.0 - reference_rd_x) * 0.00001;
-        let dy = (rdc.1 - reference_rd_y) * 0.00001;
-
-        let reference_wgs84_x = 52.15517;
-        let reference_wgs84_y = 5.387206;
-
-        let sum_n = (3_235.653_8 * dy)
-            + (-32.58297 * dx.powi(2))
-            + (-0.2475 * dy.powi(2))
-            + (-0.84978 * dx.powi(2) * dy)
-            + (-0.0655 * dy.powi(3))
-            + (-0.01709 * dx.powi(2) * dy.powi(2))
-            + (-0.00738 * dx)
-            + (0.0053 * dx.powi(4))
-            + (-0.00039 * dx.powi(2) * dy.powi(3))
-            + (0.00033 * dx.powi(4) * dy)
-            + (-0.00012 * dx * dy);
-
-        let sum_e = (5_260.529_3 * dx)
-            + (105.94684 * dx * dy)
-            + (2.45656 * dx * dy.powi(2))
-            + (-0.81885 * dx.powi(3))
-            + (0.05594 * dx * dy.powi(3))
-            + (-0.05607 * dx.powi(3) * dy)
-            + (0.01199 * dy)
-            + (-0.00256 * dx.powi(3) * dy.powi(2))
-            + (0.00128 * dx * dy.powi(4))
-            + (0.00022 * dy.powi(2))
-            + (-0.00022 * dx.powi(2))
-            + (0.00026 * dx.powi(5));
-
-        let longitude = reference_wgs84_y + (sum_e / 3600.0);
-        let latitude = reference_wgs84_x + (sum_n / 3600.0);
+const ETRS89_TO_RD_BESSEL: Helmert = Helmert {
+    translation: [-565.7346, -50.4058, -465.2895],
+    rotation: [-1.91513e-6, 1.60365e-6, -9.09546e-6],
+    scale: -4.07242e-6,
+};
+
+const RD_BESSEL_TO_ETRS89: Helmert = Helmert {
+    translation: [565.7381, 50.4018, 465.2904],
+    rotation: [1.91514e-6, -1.60363e-6, 9.09546e-6],
+    scale: 4.07244e-6,
+};
+
+/// Oblique stereographic projection of the RD system on the Bessel ellipsoid.
+struct Stereographic {
+    e: f64,
+    n: f64,
+    c: f64,
+    chi_0: f64,
+    lambda_0: f64,
+    r_k: f64,
+}
+
+const RD_LATITUDE: f64 = 52.0 + 9.0 / 60.0 + 22.178 / 3600.0;
+const RD_LONGITUDE: f64 = 5.0 + 23.0 / 60.0 + 15.500 / 3600.0;
+const RD_SCALE: f64 = 0.9999079;
+const RD_FALSE_EASTING: f64 = 155_000.0;
+const RD_FALSE_NORTHING: f64 = 463_000.0;
+
+impl Stereographic {
+    /// The constants of the RD projection, computed once.
+    fn rd() -> &'static Self {
+        static RD: OnceLock<Stereographic> = OnceLock::new();
+        RD.get_or_init(Self::new_rd)
+    }
+
+    fn new_rd() -> Self {
+        let e2 = BESSEL.e_squared();
+        let e = e2.sqrt();
+        let phi_0 = RD_LATITUDE.to_radians();
+        let sin_phi_0 = phi_0.sin();
+
+        let rho_0 = BESSEL.a * (1.0 - e2) / (1.0 - e2 * sin_phi_0.powi(2)).powf(1.5);
+        let nu_0 = BESSEL.a / (1.0 - e2 * sin_phi_0.powi(2)).sqrt();
+        let r = (rho_0 * nu_0).sqrt();
+        let n = (1.0 + e2 * phi_0.cos().powi(4) / (1.0 - e2)).sqrt();
+
+        let s1 = (1.0 + sin_phi_0) / (1.0 - sin_phi_0);
+        let s2 = (1.0 - e * sin_phi_0) / (1.0 + e * sin_phi_0);
+        let w1 = (s1 * s2.powf(e)).powf(n);
+        let sin_chi = (w1 - 1.0) / (w1 + 1.0);
+        let c = (n + sin_phi_0) * (1.0 - sin_chi) / ((n - sin_phi_0) * (1.0 + sin_chi));
+        let w2 = c * w1;
 
         Self {
-            longitude,
-            latitude,
+            e,
+            n,
+            c,
+            chi_0: ((w2 - 1.0) / (w2 + 1.0)).asin(),
+            lambda_0: RD_LONGITUDE.to_radians(),
+            r_k: r * RD_SCALE,
         }
     }
+
+    fn project(&self, latitude: f64, longitude: f64) -> (f64, f64) {
+        let e = self.e;
+        let sin_phi = latitude.sin();
+        let lambda = self.n * (longitude - self.lambda_0);
+
+        let sa = (1.0 + sin_phi) / (1.0 - sin_phi);
+        let sb = (1.0 - e * sin_phi) / (1.0 + e * sin_phi);
+        let w = self.c * (sa * sb.powf(e)).powf(self.n);
+        let chi = ((w - 1.0) / (w + 1.0)).asin();
+
+        let b = 1.0 + chi.sin() * self.chi_0.sin() + chi.cos() * self.chi_0.cos() * lambda.cos();
+        let x = 2.0 * self.r_k * chi.cos() * lambda.sin() / b;
+        let y = 2.0
+            * self.r_k
+            * (chi.sin() * self.chi_0.cos() - chi.cos() * self.chi_0.sin() * lambda.cos())
+            / b;
+
+        (x + RD_FALSE_EASTING, y + RD_FALSE_NORTHING)
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        let e = self.e;
+        let dx = x - RD_FALSE_EASTING;
+        let dy = y - RD_FALSE_NORTHING;
+
+        let g = 2.0 * self.r_k * (FRAC_PI_4 - self.chi_0 / 2.0).tan();
+        let h = 4.0 * self.r_k * self.chi_0.tan() + g;
+        let i = (dx / (h + dy)).atan();
+        let j = (dx / (g - dy)).atan() - i;
+
+        let chi = self.chi_0 + 2.0 * ((dy - dx * (j / 2.0).tan()) / (2.0 * self.r_k)).atan();
+        let lambda = j + 2.0 * i;
+
+        let longitude = lambda / self.n + self.lambda_0;
+        let psi = 0.5 * ((1.0 + chi.sin()) / (self.c * (1.0 - chi.sin()))).ln() / self.n;
+
+        let mut latitude = 2.0 * psi.exp().atan() - FRAC_PI_2;
+        for _ in 0..10 {
+            let sin_phi = latitude.sin();
+            let psi_i = ((latitude / 2.0 + FRAC_PI_4).tan()
+                * ((1.0 - e * sin_phi) / (1.0 + e * sin_phi)).powf(e / 2.0))
+            .ln();
+            latitude -=
+                (psi_i - psi) * latitude.cos() * (1.0 - e * e * sin_phi * sin_phi) / (1.0 - e * e);
+        }
+
+        (latitude, longitude)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct WGS84 {
-    pub longitude: f32,
-    pub latitude: f32,
+impl From<Etrs89> for RijkDriehoekCoordinate {
+    fn from(etrs: Etrs89) -> Self {
+        let cartesian = GRS80.to_cartesian(
+            etrs.latitude.to_radians(),
+            etrs.longitude.to_radians(),
+            ETRS89_HEIGHT,
+        );
+        let (latitude, longitude) = BESSEL.to_geographic(ETRS89_TO_RD_BESSEL.apply(cartesian));
+        let (x, y) = Stereographic::rd().project(latitude, longitude);
+
+        RijkDriehoekCoordinate(x, y)
+    }
+}
+
+impl From<RijkDriehoekCoordinate> for Etrs89 {
+    fn from(rdc: RijkDriehoekCoordinate) -> Self {
+        let (latitude, longitude) = Stereographic::rd().unproject(rdc.0, rdc.1);
+        let cartesian = BESSEL.to_cartesian(latitude, longitude, RD_BESSEL_HEIGHT);
+        let (latitude, longitude) = GRS80.to_geographic(RD_BESSEL_TO_ETRS89.apply(cartesian));
+
+        Etrs89 {
+            longitude: longitude.to_degrees(),
+            latitude: latitude.to_degrees(),
+        }
+    }
+}
+
+/// Rotation rates of the Eurasian plate in the ITRF (ETRF2014), in milliarcseconds per year.
+const EURASIA_ROTATION: [f64; 3] = [0.085, 0.531, -0.770];
+const ETRS89_EPOCH: f64 = 1989.0;
+
+/// Displacement in ETRS89 minus ITRF at `epoch` for a geocentric position.
+fn plate_motion([x, y, z]: [f64; 3], epoch: f64) -> [f64; 3] {
+    let mas = (1.0f64 / 3_600_000.0).to_radians();
+    let [rx, ry, rz] = EURASIA_ROTATION.map(|r| r * mas * (epoch - ETRS89_EPOCH));
+
+    [ry * z - rz * y, rz * x - rx * z, rx * y - ry * x]
+}
+
+impl WGS84 {
+    /// Convert a WGS84 coordinate measured at `epoch` (decimal year) to ETRS89.
+    pub fn to_etrs89(&self, epoch: f64) -> Etrs89 {
+        let [x, y, z] =
+            GRS80.to_cartesian(self.latitude.to_radians(), self.longitude.to_radians(), 0.0);
+        let [dx, dy, dz] = plate_motion([x, y, z], epoch);
+        let (latitude, longitude) = GRS80.to_geographic([x + dx, y + dy, z + dz]);
+
+        Etrs89 {
+            longitude: longitude.to_degrees(),
+            latitude: latitude.to_degrees(),
+        }
+    }
+}
+
+impl Etrs89 {
+    /// Convert to the WGS84 coordinate of this point at `epoch` (decimal year).
+    pub fn to_wgs84(&self, epoch: f64) -> WGS84 {
+        let [x, y, z] =
+            GRS80.to_cartesian(self.latitude.to_radians(), self.longitude.to_radians(), 0.0);
+        let [dx, dy, dz] = plate_motion([x, y, z], epoch);
+        let (latitude, longitude) = GRS80.to_geographic([x - dx, y - dy, z - dz]);
+
+        WGS84 {
+            longitude: longitude.to_degrees(),
+            latitude: latitude.to_degrees(),
+        }
+    }
 }
 
 impl From<WGS84> for RijkDriehoekCoordinate {
-    fn from(ltlt: WGS84) -> Self {
-        let reference_rd_x = 155000.0;
-        let reference_rd_y = 463000.0;
-
-        let reference_wgs84_x = 52.15517;
-        let reference_wgs84_y = 5.387206;
-
-        let d_lattitude = 0.36 * (ltlt.latitude - reference_wgs84_x);
-        let d_longitude = 0.36 * (ltlt.longitude - reference_wgs84_y);
-
-        // let r_matrix = [
-        //     [0.0, -0.705, 0.0, 0.0],
-        //     [190094.945, -11832.228, -114.221, 0.0],
-        //     [0.0; 4],
-        //     [-32.391, -2.340, 0.0, 0.0],
-        //     [0.0; 4],
-        // ];
-
-        let gen = |p, q| d_lattitude.powi(p) * d_longitude.powi(q);
-
-        let x_transformations = [
-            190_094.94, -114.221, -11832.228, -0.705, -32.391, -2.340, -0.008, -0.608, 0.148,
-        ];
-
-        let x_values = [
-            gen(0, 1),
-            gen(1, 1),
-            gen(2, 1),
-            gen(0, 3),
-            gen(1, 0),
-            gen(3, 1),
-            gen(0, 2),
-            gen(1, 3),
-            gen(2, 3),
-        ];
-
-        let y_transformations = [
-            0.433, 3638.893, 0.092, 309_056.53, 73.077, -157.984, 59.788, -6.439, -0.032, -0.054,
-        ];
-
-        let y_values = [
-            gen(0, 1),
-            gen(0, 2),
-            gen(0, 4),
-            gen(1, 0),
-            gen(2, 0),
-            gen(1, 2),
-            gen(3, 0),
-            gen(2, 2),
-            gen(1, 1),
-            gen(1, 4),
-        ];
-
-        let calc_latt: f32 = x_transformations
-            .into_iter()
-            .zip(x_values)
-            .map(|(a, b)| a * b)
-            .sum();
-        let calc_long: f32 = y_transformations
-            .into_iter()
-            .zip(y_values)
-            .map(|(a, b)| a * b)
-            .sum();
-
-        let rd_x_coordinate = reference_rd_x + calc_latt;
-        let rd_y_coordinate = reference_rd_y + calc_long;
-
-        RijkDriehoekCoordinate(rd_x_coordinate, rd_y_coordinate)
+    fn from(wgs: WGS84) -> Self {
+        RijkDriehoekCoordinate::from(wgs.to_etrs89(DEFAULT_EPOCH))
+    }
+}
+
+impl From<RijkDriehoekCoordinate> for WGS84 {
+    fn from(rdc: RijkDriehoekCoordinate) -> Self {
+        Etrs89::from(rdc).to_wgs84(DEFAULT_EPOCH)
+    }
+}
+
+impl From<RijkDriehoekCoordinate> for Vec2 {
+    fn from(rdc: RijkDriehoekCoordinate) -> Self {
+        Vec2::new(rdc.0 as f32, rdc.1 as f32)
+    }
+}
+
+impl From<RijkDriehoekCoordinate> for DVec2 {
+    fn from(rdc: RijkDriehoekCoordinate) -> Self {
+        DVec2::new(rdc.0, rdc.1)
+    }
+}
+
+impl From<RijkDriehoekCoordinate> for Vec3 {
+    fn from(rdc: RijkDriehoekCoordinate) -> Self {
+        Vec3::new(rdc.0 as f32, rdc.1 as f32, 0.0)
     }
 }
 
+impl From<Vec2> for RijkDriehoekCoordinate {
+    fn from(point: Vec2) -> Self {
+        RijkDriehoekCoordinate(point.x as f64, point.y as f64)
+    }
+}
+
+impl From<DVec2> for RijkDriehoekCoordinate {
+    fn from(point: DVec2) -> Self {
+        RijkDriehoekCoordinate(point.x, point.y)
+    }
+}
+
+/// Longitude in x and latitude in y, the same axis order as GeoJSON.
 impl From<WGS84> for Vec2 {
     fn from(wgs: WGS84) -> Self {
-        Vec2::new(wgs.latitude, wgs.longitude)
+        Vec2::new(wgs.longitude as f32, wgs.latitude as f32)
     }
 }
 
+/// Convert a slice of coordinates in parallel, for example `&[WGS84]` to `Vec<RijkDriehoekCoordinate>`.
+pub fn convert_all<A, B>(points: &[A]) -> Vec<B>
+where
+    A: Copy + Into<B> + Sync,
+    B: Send,
+{
+    points.par_iter().map(|point| (*point).into()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use bevy::math::Vec2;
 
-    use super::{RijkDriehoekCoordinate, WGS84};
+    use super::{convert_all, Etrs89, RijkDriehoekCoordinate, WGS84};
+
+    /// Published RDNAPTRANS™2018 validation points: ETRS89 latitude, longitude and RD x, y.
+    /// The official values include the correction grid, which is not applied here.
+    const REFERENCE_POINTS: [(f64, f64, f64, f64); 3] = [
+        // Texel
+        (53.160753042, 4.824761912, 117_380.12, 575_040.34),
+        // Noord-Groningen
+        (53.419482050, 6.776726674, 247_380.56, 604_580.78),
+        // Amersfoort
+        (52.155172897, 5.387203658, 155_000.00, 463_000.00),
+    ];
+
+    /// Maximum distance to the official values, in meters.
+    const REFERENCE_TOLERANCE: f64 = 0.1;
+
+    fn distance(a: RijkDriehoekCoordinate, b: RijkDriehoekCoordinate) -> f64 {
+        ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+    }
 
     #[test]
-    fn long_lat_to_rijk() {
-        let rijk = RijkDriehoekCoordinate(136239.0, 456184.0);
-        let ll = WGS84 {
-            longitude: 5.113435,
-            latitude: 52.093597,
-        };
+    fn etrs89_to_rijk() {
+        for (latitude, longitude, x, y) in REFERENCE_POINTS {
+            let rdc = RijkDriehoekCoordinate::from(Etrs89 {
+                longitude,
+                latitude,
+            });
+
+            let error = distance(rdc, RijkDriehoekCoordinate(x, y));
+            assert!(error < REFERENCE_TOLERANCE, "{:?}: {} m", rdc, error);
+        }
+    }
+
+    #[test]
+    fn rijk_to_etrs89() {
+        for (latitude, longitude, x, y) in REFERENCE_POINTS {
+            let etrs = Etrs89::from(RijkDriehoekCoordinate(x, y));
+
+            // One microdegree is about 11 cm of latitude and 7 cm of longitude.
+            assert!((etrs.latitude - latitude).abs() < 1e-6, "{:?}", etrs);
+            assert!((etrs.longitude - longitude).abs() < 1.5e-6, "{:?}", etrs);
+        }
+    }
 
-        let rijk_c = RijkDriehoekCoordinate::from(ll);
+    #[test]
+    fn round_trip() {
+        for x in (0..=300_000).step_by(50_000) {
+            for y in (300_000..=625_000).step_by(25_000) {
+                let rdc = RijkDriehoekCoordinate(x as f64 + 0.123, y as f64 + 0.456);
 
-        let a = Vec2::from(rijk);
-        let b = Vec2::from(rijk_c);
+                let via_etrs = RijkDriehoekCoordinate::from(Etrs89::from(rdc));
+                let via_wgs = RijkDriehoekCoordinate::from(WGS84::from(rdc));
 
-        assert!(dbg!(a.distance(b)) <= 30.0);
+                assert!(distance(rdc, via_etrs) < 0.001, "{:?}", via_etrs);
+                assert!(distance(rdc, via_wgs) < 0.001, "{:?}", via_wgs);
+            }
+        }
     }
 
     #[test]
-    fn rijk_to_long_lat() {
-        let rijk = RijkDriehoekCoordinate(136239.0, 456184.0);
-        let ll = WGS84 {
-            longitude: 5.113435,
-            latitude: 52.093597,
+    fn wgs84_drift() {
+        let etrs = Etrs89 {
+            longitude: 5.387203658,
+            latitude: 52.155172897,
         };
 
-        let ll_c = WGS84::from(rijk);
+        // Both systems coincide at the ETRS89 epoch.
+        let wgs = etrs.to_wgs84(1989.0);
+        assert!((wgs.latitude - etrs.latitude).abs() < 1e-12);
+        assert!((wgs.longitude - etrs.longitude).abs() < 1e-12);
+
+        // The Eurasian plate moves north east, about 80 cm between 1989 and 2024.
+        let wgs = etrs.to_wgs84(2024.0);
+        let shift = distance(
+            RijkDriehoekCoordinate::from(etrs),
+            RijkDriehoekCoordinate::from(Etrs89 {
+                longitude: wgs.longitude,
+                latitude: wgs.latitude,
+            }),
+        );
+        assert!(wgs.latitude > etrs.latitude && wgs.longitude > etrs.longitude);
+        assert!(shift > 0.7 && shift < 1.0, "shift: {}", shift);
+
+        let back = wgs.to_etrs89(2024.0);
+        assert!((back.latitude - etrs.latitude).abs() < 1e-10);
+        assert!((back.longitude - etrs.longitude).abs() < 1e-10);
+    }
 
-        let a = Vec2::from(ll);
-        let b = Vec2::from(ll_c);
+    #[test]
+    fn wgs84_vec2_axis_order() {
+        let wgs = WGS84 {
+            longitude: 5.113435,
+            latitude: 52.093597,
+        };
 
-        // println!("Expected: {:?} -> Got: {:?}", ll, ll_c);
+        assert_eq!(Vec2::from(wgs), Vec2::new(5.113435, 52.093597));
+    }
 
-        assert!(dbg!(a.distance(b)) <= 0.00001);
+    #[test]
+    fn batch_conversion() {
+        let points = REFERENCE_POINTS
+            .map(|(latitude, longitude, _, _)| Etrs89 {
+                longitude,
+                latitude,
+            })
+            .to_vec();
+
+        let converted: Vec<RijkDriehoekCoordinate> = convert_all(&points);
+
+        assert_eq!(converted.len(), points.len());
+        for (point, rdc) in points.into_iter().zip(converted) {
+            assert_eq!(rdc, RijkDriehoekCoordinate::from(point));
+        }
     }
 }
@@ -0,0 +1,29 @@
+use bevy::math::DVec2;
+use bevy_shapefile::{RoadAttributes, RoadId, RoadMap};
+
+/// Roads further away than this from the queried position are not considered a match.
+const MAX_GEOCODE_DISTANCE: f64 = 50.0;
+
+/// The result of a reverse geocode query.
+#[derive(Debug, Clone)]
+pub struct GeocodeResult<'a> {
+    pub road_id: RoadId,
+    /// Distance from the queried position to the road section.
+    pub distance: f64,
+    pub address: &'a RoadAttributes,
+}
+
+/// Find the address of the road section closest to `position`.
+pub fn reverse_geocode(road_map: &RoadMap, position: DVec2) -> Option<GeocodeResult<'_>> {
+    let (road_id, distance) = road_map.nearest_road(position)?;
+
+    if distance > MAX_GEOCODE_DISTANCE {
+        return None;
+    }
+
+    Some(GeocodeResult {
+        road_id,
+        distance,
+        address: &road_map.roads[&road_id].attributes,
+    })
+}
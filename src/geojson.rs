@@ -0,0 +1,500 @@
+use crate::geo_coords::{RijkDriehoekCoordinate, WGS84};
+use bevy::math::DVec2;
+use bevy_shapefile::{
+    DrivingDirection, JunctionId, RoadAttributes, RoadId, RoadMap, RoadSection, AABB,
+};
+use graph::{builder::EdgeDirection, DirectedNetworkGraph, NetworkData, NodeId};
+use serde_json::{json, Map, Value};
+use std::{collections::HashMap, fs::File, path::Path};
+
+/// Coordinate system of the GeoJSON coordinates.
+/// The road map itself always uses Rijksdriehoek coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    /// Rijksdriehoek coordinates (EPSG:28992), written as is.
+    RijksDriehoek,
+    /// WGS84 longitude and latitude (EPSG:4326), the default of GeoJSON.
+    Wgs84,
+}
+
+impl Projection {
+    fn project(&self, point: DVec2) -> Value {
+        match self {
+            Projection::RijksDriehoek => json!([point.x, point.y]),
+            Projection::Wgs84 => {
+                let wgs = WGS84::from(RijkDriehoekCoordinate::from(point));
+                json!([wgs.longitude, wgs.latitude])
+            }
+        }
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> DVec2 {
+        match self {
+            Projection::RijksDriehoek => DVec2::new(x, y),
+            Projection::Wgs84 => DVec2::from(RijkDriehoekCoordinate::from(WGS84 {
+                longitude: x,
+                latitude: y,
+            })),
+        }
+    }
+}
+
+fn geometry(parts: &[&[DVec2]], projection: Projection) -> Value {
+    let lines = parts
+        .iter()
+        .map(|part| {
+            part.iter()
+                .map(|point| projection.project(*point))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    match lines.as_slice() {
+        [line] => json!({ "type": "LineString", "coordinates": line }),
+        lines => json!({ "type": "MultiLineString", "coordinates": lines }),
+    }
+}
+
+fn feature(geometry: Value, properties: Value) -> Value {
+    json!({ "type": "Feature", "geometry": geometry, "properties": properties })
+}
+
+fn feature_collection(features: Vec<Value>) -> Value {
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+fn section_geometry(section: &RoadSection, projection: Projection) -> Value {
+    let parts = section
+        .parts
+        .iter()
+        .map(|x| x.as_slice())
+        .collect::<Vec<_>>();
+    geometry(&parts, projection)
+}
+
+/// Export the road sections that intersect `bbox`, with the road attributes as properties.
+pub fn road_map_features(road_map: &RoadMap, bbox: &AABB, projection: Projection) -> Value {
+    let mut ids = road_map
+        .road_spatial
+        .locate_in_envelope_intersecting(bbox)
+        .map(|x| x.id)
+        .collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.num());
+
+    let features = ids
+        .into_iter()
+        .map(|id| {
+            let section = &road_map.roads[&id];
+            let mut properties =
+                serde_json::to_value(&section.attributes).expect("Could not serialize");
+            properties["road_id"] = json!(id.num());
+
+            feature(section_geometry(section, projection), properties)
+        })
+        .collect();
+
+    feature_collection(features)
+}
+
+/// Export the edges of a graph layer. Every edge is a feature with the geometry of
+/// the road sections it covers and the `level`, `weight` and `direction` as properties.
+/// Edges in both directions are only exported once.
+pub fn layer_features<D: NetworkData>(
+    road_map: &RoadMap,
+    network: &DirectedNetworkGraph<D>,
+    level: u8,
+    projection: Projection,
+) -> Value {
+    let mut features = Vec::new();
+
+    for source in 0..network.nodes().len() {
+        let source = NodeId::from(source);
+
+        for (edge_id, edge) in network.out_edges(source) {
+            let direction = edge.direction();
+            if direction == EdgeDirection::Both && edge.target().0 < source.0 {
+                continue;
+            }
+
+            let road_ids = Vec::from(network.data.edge_road_id(edge_id));
+            let parts = road_ids
+                .iter()
+                .filter_map(|id| road_map.roads.get(&RoadId::from(*id)))
+                .flat_map(|section| section.parts.iter().map(|x| x.as_slice()))
+                .collect::<Vec<_>>();
+
+            let properties = json!({
+                "level": level,
+                "weight": edge.distance(),
+                "direction": direction,
+                "source": source.0,
+                "target": edge.target().0,
+                "roads": road_ids,
+            });
+
+            features.push(feature(geometry(&parts, projection), properties));
+        }
+    }
+
+    feature_collection(features)
+}
+
+/// Export a route, one feature per road section in driving order.
+pub fn route_features(road_map: &RoadMap, route: &[RoadId], projection: Projection) -> Value {
+    let features = route
+        .iter()
+        .enumerate()
+        .map(|(order, id)| {
+            let section = &road_map.roads[id];
+            let properties = json!({
+                "order": order,
+                "road_id": id.num(),
+                "length": section.length(),
+                "street_name": section.attributes.street_name,
+            });
+
+            feature(section_geometry(section, projection), properties)
+        })
+        .collect();
+
+    feature_collection(features)
+}
+
+pub fn write_geojson<P: AsRef<Path>>(
+    value: &Value,
+    path: P,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Started writing file: {:?}", path.as_ref());
+
+    let file = File::create(&path)?;
+    serde_json::to_writer(file, value)?;
+
+    println!("Finished writing file: {:?}", path.as_ref());
+
+    Ok(())
+}
+
+/// Load the LineString and MultiLineString features of a GeoJSON file as road sections.
+/// The properties use the names of `RoadAttributes`, missing properties get a default value.
+/// Features without junction ids get junctions at their end points, shared between features
+/// with the same end point coordinates.
+pub fn load_geojson<P: AsRef<Path>>(
+    path: P,
+    projection: Projection,
+) -> Result<RoadMap, Box<dyn std::error::Error>> {
+    println!("Start read of GeoJSON");
+    let value: Value = serde_json::from_reader(File::open(path)?)?;
+
+    let features = value["features"]
+        .as_array()
+        .ok_or("Expected a FeatureCollection")?;
+
+    let mut lines = Vec::new();
+    for feature in features {
+        let coordinates = &feature["geometry"]["coordinates"];
+        let parts = match feature["geometry"]["type"].as_str() {
+            Some("LineString") => vec![parse_line(coordinates, projection)?],
+            Some("MultiLineString") => coordinates
+                .as_array()
+                .ok_or("Invalid MultiLineString")?
+                .iter()
+                .map(|line| parse_line(line, projection))
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => continue,
+        };
+
+        if parts.iter().all(|part| part.is_empty()) {
+            continue;
+        }
+
+        let properties = feature["properties"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        lines.push((parts, properties));
+    }
+
+    // Derived junction ids start after the largest id in the file.
+    let mut next_junction = lines
+        .iter()
+        .flat_map(|(_, properties)| {
+            [
+                properties.get("junction_start"),
+                properties.get("junction_end"),
+            ]
+        })
+        .filter_map(|x| x.and_then(|x| x.as_u64()))
+        .max()
+        .map_or(0, |x| x as usize + 1);
+    let mut junctions = HashMap::new();
+    let mut junction_at = |point: DVec2| {
+        *junctions
+            .entry((point.x.to_bits(), point.y.to_bits()))
+            .or_insert_with(|| {
+                next_junction += 1;
+                next_junction - 1
+            })
+    };
+
+    let mut roads = HashMap::new();
+    for (parts, properties) in lines {
+        let id = RoadId::from(roads.len());
+        let start = parts.iter().flatten().next().cloned().unwrap();
+        let end = parts.iter().flatten().last().cloned().unwrap();
+
+        let defaults = default_attributes(junction_at(start), junction_at(end));
+        let attributes = road_attributes(defaults, properties)?;
+
+        let section = RoadSection::new(id, parts, attributes).expect("Line has points");
+        roads.insert(id, section);
+    }
+
+    println!("Finish loading {} roads", roads.len());
+
+    Ok(RoadMap::from_sections(roads))
+}
+
+fn parse_line(
+    value: &Value,
+    projection: Projection,
+) -> Result<Vec<DVec2>, Box<dyn std::error::Error>> {
+    value
+        .as_array()
+        .ok_or("Invalid LineString")?
+        .iter()
+        .map(|point| match (point[0].as_f64(), point[1].as_f64()) {
+            (Some(x), Some(y)) => Ok(projection.unproject(x, y)),
+            _ => Err("Invalid coordinate".into()),
+        })
+        .collect()
+}
+
+fn default_attributes(junction_start: usize, junction_end: usize) -> RoadAttributes {
+    RoadAttributes {
+        wegvak_id: 0,
+        junction_start: JunctionId::from(junction_start),
+        junction_end: JunctionId::from(junction_end),
+        road_type: None,
+        road_number: None,
+        street_name: None,
+        alternative_name: None,
+        municipality: None,
+        residence: None,
+        authority: None,
+        authority_type: None,
+        route_numbers: Vec::new(),
+        frc: None,
+        fow: None,
+        direction: DrivingDirection::Both,
+        relative_height: 0,
+        house_numbers_left: None,
+        house_numbers_right: None,
+        speed_limit: None,
+    }
+}
+
+/// Overwrite the defaults with the properties that match a `RoadAttributes` field.
+fn road_attributes(
+    defaults: RoadAttributes,
+    properties: Map<String, Value>,
+) -> Result<RoadAttributes, serde_json::Error> {
+    let mut attributes = serde_json::to_value(defaults)?;
+    let fields = attributes.as_object_mut().unwrap();
+
+    for (key, value) in properties {
+        if let Some(field) = fields.get_mut(&key) {
+            *field = value;
+        }
+    }
+
+    serde_json::from_value(attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nwb::{self, NWBNetworkData};
+
+    fn road_map() -> RoadMap {
+        let section = |id: usize, parts: Vec<Vec<DVec2>>, start: usize, end: usize| {
+            let mut attributes = default_attributes(start, end);
+            attributes.street_name = Some(format!("Straat {}", id));
+            if id == 1 {
+                attributes.direction = DrivingDirection::Forward;
+            }
+            (
+                RoadId::from(id),
+                RoadSection::new(RoadId::from(id), parts, attributes).unwrap(),
+            )
+        };
+
+        RoadMap::from_sections(HashMap::from([
+            section(
+                0,
+                vec![vec![DVec2::new(0.0, 0.0), DVec2::new(100.0, 0.0)]],
+                0,
+                1,
+            ),
+            section(
+                1,
+                vec![vec![DVec2::new(100.0, 0.0), DVec2::new(100.0, 100.0)]],
+                1,
+                2,
+            ),
+            section(
+                2,
+                vec![
+                    vec![DVec2::new(1000.0, 0.0), DVec2::new(1100.0, 0.0)],
+                    vec![DVec2::new(1200.0, 0.0), DVec2::new(1300.0, 0.0)],
+                ],
+                3,
+                4,
+            ),
+        ]))
+    }
+
+    #[test]
+    fn export_road_map_bbox() {
+        let road_map = road_map();
+        let bbox = AABB::from_corners([-10.0, -10.0], [200.0, 200.0]);
+
+        let value = road_map_features(&road_map, &bbox, Projection::RijksDriehoek);
+        let features = value["features"].as_array().unwrap();
+
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["geometry"]["type"], "LineString");
+        assert_eq!(
+            features[0]["geometry"]["coordinates"],
+            json!([[0.0, 0.0], [100.0, 0.0]])
+        );
+        assert_eq!(features[0]["properties"]["street_name"], "Straat 0");
+        assert_eq!(features[1]["properties"]["direction"], "Forward");
+    }
+
+    #[test]
+    fn export_multi_part_section() {
+        let road_map = road_map();
+        let bbox = AABB::from_corners([900.0, -10.0], [1400.0, 10.0]);
+
+        let value = road_map_features(&road_map, &bbox, Projection::RijksDriehoek);
+
+        assert_eq!(value["features"][0]["geometry"]["type"], "MultiLineString");
+        assert_eq!(
+            value["features"][0]["geometry"]["coordinates"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn export_wgs84() {
+        let road_map = road_map();
+        let bbox = AABB::from_corners([-10.0, -10.0], [200.0, 200.0]);
+
+        let value = road_map_features(&road_map, &bbox, Projection::Wgs84);
+        let point = &value["features"][0]["geometry"]["coordinates"][0];
+
+        // Rijksdriehoek (0, 0) lies south west of the Netherlands, in the North Sea near France.
+        let longitude = point[0].as_f64().unwrap();
+        let latitude = point[1].as_f64().unwrap();
+        assert!(
+            longitude > 3.0 && longitude < 3.5,
+            "longitude: {}",
+            longitude
+        );
+        assert!(latitude > 47.9 && latitude < 48.0, "latitude: {}", latitude);
+    }
+
+    #[test]
+    fn export_layer() {
+        let road_map = road_map();
+        let network: DirectedNetworkGraph<NWBNetworkData> = nwb::preprocess_roadmap(&road_map);
+
+        let value = layer_features(&road_map, &network, 0, Projection::RijksDriehoek);
+        let features = value["features"].as_array().unwrap();
+
+        // Every road section once, even though two way edges are stored at both nodes.
+        assert_eq!(features.len(), 3);
+        for feature in features {
+            assert_eq!(feature["properties"]["level"], 0);
+        }
+
+        let forward = features
+            .iter()
+            .find(|x| x["properties"]["roads"] == json!([1]))
+            .unwrap();
+        assert_eq!(forward["properties"]["direction"], "Forward");
+        assert_eq!(forward["properties"]["weight"], 100.0);
+    }
+
+    #[test]
+    fn export_route() {
+        let road_map = road_map();
+
+        let value = route_features(
+            &road_map,
+            &[RoadId::from(0), RoadId::from(1)],
+            Projection::RijksDriehoek,
+        );
+        let features = value["features"].as_array().unwrap();
+
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[1]["properties"]["order"], 1);
+        assert_eq!(features[1]["properties"]["road_id"], 1);
+        assert_eq!(features[1]["properties"]["length"], 100.0);
+    }
+
+    #[test]
+    fn import_round_trip() {
+        let road_map = road_map();
+        let bbox = AABB::from_corners([-10.0, -10.0], [2000.0, 200.0]);
+        let path = std::env::temp_dir().join("geojson_import_round_trip.geojson");
+
+        write_geojson(
+            &road_map_features(&road_map, &bbox, Projection::RijksDriehoek),
+            &path,
+        )
+        .unwrap();
+        let imported = load_geojson(&path, Projection::RijksDriehoek).unwrap();
+
+        assert_eq!(imported.roads.len(), road_map.roads.len());
+        for (id, section) in &road_map.roads {
+            assert_eq!(imported.roads[id].parts, section.parts);
+            assert_eq!(imported.roads[id].attributes, section.attributes);
+        }
+    }
+
+    #[test]
+    fn import_without_properties() {
+        let path = std::env::temp_dir().join("geojson_import_without_properties.geojson");
+        let value = feature_collection(vec![
+            feature(
+                json!({ "type": "LineString", "coordinates": [[0.0, 0.0], [10.0, 0.0]] }),
+                Value::Null,
+            ),
+            feature(
+                json!({ "type": "LineString", "coordinates": [[10.0, 0.0], [10.0, 10.0]] }),
+                json!({ "direction": "Backward", "speed_limit": 50.0 }),
+            ),
+            feature(
+                json!({ "type": "Point", "coordinates": [5.0, 5.0] }),
+                Value::Null,
+            ),
+        ]);
+        write_geojson(&value, &path).unwrap();
+
+        let imported = load_geojson(&path, Projection::RijksDriehoek).unwrap();
+        let first = &imported.roads[&RoadId::from(0)].attributes;
+        let second = &imported.roads[&RoadId::from(1)].attributes;
+
+        assert_eq!(imported.roads.len(), 2);
+        // The shared end point becomes a single junction.
+        assert_eq!(first.junction_end, second.junction_start);
+        assert_ne!(first.junction_start, second.junction_end);
+        assert_eq!(first.direction, DrivingDirection::Both);
+        assert_eq!(second.direction, DrivingDirection::Backward);
+        assert_eq!(second.speed_limit, Some(50.0));
+    }
+}
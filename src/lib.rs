@@ -2,15 +2,32 @@
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     io::{Cursor, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 pub mod camera;
 pub mod geo_coords;
+pub mod geocoding;
+pub mod geojson;
 pub mod nwb;
+pub mod osm;
 pub mod ui;
 pub mod world;
 
+/// The version of the cached road map and graph, it is part of their file names.
+/// Increase it when the stored data changes, the caches of an older version are then built again.
+/// Version 2 stores the road geometry as f64.
+pub const CACHE_VERSION: u32 = 2;
+
+/// `path` with the [`CACHE_VERSION`] before its extension, `data/road_map.data` becomes `data/road_map.v2.data`.
+pub fn versioned_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    match path.extension().and_then(|x| x.to_str()) {
+        Some(extension) => path.with_extension(format!("v{}.{}", CACHE_VERSION, extension)),
+        None => path.with_extension(format!("v{}", CACHE_VERSION)),
+    }
+}
+
 pub fn write_file<T: Serialize, P: AsRef<Path>>(
     value: &T,
     path: P,
@@ -10,6 +10,7 @@ use bevy_dutch_road_highway_node_network::{
     world::{WorldConfig, WorldPlugin},
 };
 use bevy_polyline::PolylinePlugin;
+use bevy_shapefile::JunctionRepairConfig;
 
 fn main() {
     App::new()
@@ -22,10 +23,13 @@ fn main() {
         // .add_plugins(FrameTimeDiagnosticsPlugin::default())
         .add_plugins(WorldPlugin {
             config: WorldConfig {
-                database_path: "data/database.db".into(),
                 shapefile_path: "data/01-05-2024/Wegvakken/Wegvakken.shp".into(),
+                speed_limit_path: "data/01-05-2024/Snelheden.shp".into(),
                 road_map_path: "data/road_map.data".into(),
                 directed_graph_path: "data/directed_graph.graph".into(),
+                junction_repair_report_path: "data/junction_repair.txt".into(),
+                junction_repair: JunctionRepairConfig::default(),
+                keep_largest_component: false,
 
                 selected_colour: Color::GREEN,
                 normal_colour: Color::WHITE,
@@ -1,24 +1,20 @@
-use bevy::math::Vec2;
-use bevy_shapefile::{JunctionId, RoadId, RoadMap};
+use bevy::math::DVec2;
+use bevy_shapefile::{DrivingDirection, JunctionId, RoadId, RoadMap};
 use graph::{
     builder::{DirectedNetworkBuilder, EdgeBuilder, EdgeDirection, NodeBuilder},
     DirectedNetworkGraph, EdgeId, NetworkData, NodeId, ShortcutState,
 };
-use rusqlite::{
-    types::{FromSql, FromSqlError},
-    Connection,
-};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, hash::Hash, path::Path};
+use std::hash::Hash;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct NWBNetworkData {
-    pub node_junctions: Vec<(JunctionId, Vec2)>,
+    pub node_junctions: Vec<(JunctionId, DVec2)>,
     edge_id: Vec<RoadId>, // for sql, not nwb road_id
 }
 
 impl NetworkData for NWBNetworkData {
-    type NodeData = (JunctionId, Vec2);
+    type NodeData = (JunctionId, DVec2);
     type EdgeData = RoadId;
 
     fn node_data(&self, node: NodeId) -> &Self::NodeData {
@@ -31,7 +27,7 @@ impl NetworkData for NWBNetworkData {
 
     fn with_size(node_size: usize, edge_size: usize) -> Self {
         NWBNetworkData {
-            node_junctions: vec![(0.into(), Vec2::ZERO); node_size],
+            node_junctions: vec![(0.into(), DVec2::ZERO); node_size],
             edge_id: vec![0.into(); edge_size],
         }
     }
@@ -52,7 +48,7 @@ impl NetworkData for NWBNetworkData {
 #[derive(Debug)]
 pub struct JunctionNode {
     pub junction_id: JunctionId,
-    pub location: Vec2,
+    pub location: DVec2,
 }
 
 impl PartialEq for JunctionNode {
@@ -70,7 +66,7 @@ impl Hash for JunctionNode {
 }
 
 impl NodeBuilder for JunctionNode {
-    type Data = (JunctionId, Vec2);
+    type Data = (JunctionId, DVec2);
 
     fn data(&self) -> Self::Data {
         (self.junction_id, self.location)
@@ -118,76 +114,57 @@ impl EdgeBuilder for RoadEdge {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct RijRichting(EdgeDirection);
-
-impl FromSql for RijRichting {
-    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
-        let rij_richting = String::column_result(value)?;
-        let rij_richting = rij_richting
-            .chars()
-            .next()
-            .ok_or(FromSqlError::InvalidType)?;
-        match rij_richting {
-            'H' => Ok(RijRichting(EdgeDirection::Forward)),
-            'T' => Ok(RijRichting(EdgeDirection::Backward)),
-            'B' => Ok(RijRichting(EdgeDirection::Both)),
-            'O' => Ok(RijRichting(EdgeDirection::Both)),
-            _ => Err(FromSqlError::InvalidType),
-        }
+/// Convert the NWB driving direction (RIJRICHTNG) to an edge direction.
+/// Unknown directions are treated as two-way roads.
+pub fn edge_direction(direction: DrivingDirection) -> EdgeDirection {
+    match direction {
+        DrivingDirection::Forward => EdgeDirection::Forward,
+        DrivingDirection::Backward => EdgeDirection::Backward,
+        DrivingDirection::Both | DrivingDirection::Unknown => EdgeDirection::Both,
     }
 }
 
-pub fn preprocess_roadmap<P: AsRef<Path>>(
-    roadmap: &RoadMap,
-    database: P,
-) -> DirectedNetworkGraph<NWBNetworkData> {
-    let database = Connection::open(database).expect("Could not open database");
-
+/// Build the directed graph of a road map, with one node per junction and one edge per road section.
+/// Works for every road map source, both the NWB shapefile and the OSM importer.
+/// The nodes are numbered along a Hilbert curve, so junctions that are close to each other
+/// are also close in memory, which keeps the searches over the graph cache friendly.
+pub fn preprocess_roadmap<D>(roadmap: &RoadMap) -> DirectedNetworkGraph<D>
+where
+    D: NetworkData<NodeData = (JunctionId, DVec2), EdgeData = RoadId>,
+{
     let mut builder: DirectedNetworkBuilder<JunctionNode, RoadEdge> = DirectedNetworkBuilder::new();
-    let roads = &roadmap.roads;
-
-    let statement = database
-        .prepare("SELECT id,junction_id_begin, junction_id_end, rij_richting FROM wegvakken")
-        .expect("Could not prepare statement")
-        .query_map([], |f| {
-            let id: usize = f.get(0)?;
-            let junction_start: usize = f.get(1)?;
-            let junction_end: usize = f.get(2)?;
-            let rij_richting: RijRichting = f.get(3)?;
-
-            let id = RoadId::from(id);
-            let junction_start = JunctionId::from(junction_start);
-            let junction_end = JunctionId::from(junction_end);
-
-            Ok((id, (junction_start, junction_end, rij_richting)))
-        })
-        .expect("Could not")
-        .map(|x| x.unwrap())
-        .collect::<HashMap<RoadId, (JunctionId, JunctionId, RijRichting)>>();
+    // In order of road id, so the nodes are numbered the same every time.
+    let mut roads = roadmap.roads.iter().collect::<Vec<_>>();
+    roads.sort_by_key(|(road_id, _)| road_id.num());
 
     for (&road_id, section) in roads {
-        let (road_id_start, road_id_end, rij_richting) = statement[&road_id];
+        let attributes = &section.attributes;
 
         let source = builder.add_node(JunctionNode {
-            junction_id: road_id_start,
-            location: *section.points.first().unwrap(),
+            junction_id: attributes.junction_start,
+            location: section.start(),
         });
         let target = builder.add_node(JunctionNode {
-            junction_id: road_id_end,
-            location: *section.points.last().unwrap(),
+            junction_id: attributes.junction_end,
+            location: section.end(),
         });
 
-        let distance = section.points.windows(2).map(|w| w[0].distance(w[1])).sum();
+        let distance = section.length() as f32;
 
         builder.add_edge(RoadEdge {
             source,
             target,
-            direction: rij_richting.0,
+            direction: edge_direction(attributes.direction),
             distance,
             sql_id: road_id,
         });
     }
 
-    builder.build()
+    let network: DirectedNetworkGraph<D> = builder.build();
+    let order = network.hilbert_order(|node| {
+        let location = network.node_data(node).1;
+        [location.x, location.y]
+    });
+
+    network.reorder(&order).0
 }
@@ -0,0 +1,474 @@
+use crate::{
+    geo_coords::{RijkDriehoekCoordinate, WGS84},
+    nwb,
+};
+use bevy::math::DVec2;
+use bevy_shapefile::{DrivingDirection, JunctionId, RoadAttributes, RoadId, RoadMap, RoadSection};
+use graph::DirectedNetworkGraph;
+use osmpbf::{Element, ElementReader};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+/// Network data for a graph created from an OSM extract, the same as for the NWB road map.
+/// The junction ids are the OSM node ids, the edges point to the road sections in the `RoadMap`.
+pub type OsmNetworkData = nwb::NWBNetworkData;
+
+/// A way with a `highway` tag that can be driven by car.
+#[derive(Debug)]
+struct OsmWay {
+    id: i64,
+    refs: Vec<i64>,
+    tags: HashMap<String, String>,
+}
+
+/// Load the roads of an OSM PBF extract into a `RoadMap`.
+/// Ways are split at every node they share with another way, so every road section
+/// runs from junction to junction. The coordinates are projected to Rijksdriehoek coordinates.
+pub fn load_osm<P: AsRef<Path>>(path: P) -> Result<RoadMap, osmpbf::Error> {
+    println!("Start read of OSM ways");
+    let mut ways = Vec::new();
+
+    ElementReader::from_path(&path)?.for_each(|element| {
+        if let Element::Way(way) = element {
+            let tags = way
+                .tags()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>();
+
+            if tags.get("highway").is_some_and(|x| is_road(x)) {
+                ways.push(OsmWay {
+                    id: way.id(),
+                    refs: way.refs().collect(),
+                    tags,
+                });
+            }
+        }
+    })?;
+
+    let used_nodes = ways
+        .iter()
+        .flat_map(|way| way.refs.iter().cloned())
+        .collect::<HashSet<_>>();
+
+    println!("Start read of OSM nodes");
+    let mut locations = HashMap::new();
+
+    ElementReader::from_path(&path)?.for_each(|element| {
+        let (id, latitude, longitude) = match element {
+            Element::Node(node) => (node.id(), node.lat(), node.lon()),
+            Element::DenseNode(node) => (node.id(), node.lat(), node.lon()),
+            _ => return,
+        };
+
+        if used_nodes.contains(&id) {
+            let wgs = WGS84 {
+                longitude,
+                latitude,
+            };
+            locations.insert(id, DVec2::from(RijkDriehoekCoordinate::from(wgs)));
+        }
+    })?;
+
+    println!("Loading roads");
+    let roads = load_road_sections(&ways, &locations);
+    println!("Finish loading roads");
+
+    Ok(RoadMap::from_sections(roads))
+}
+
+/// Build the directed graph of a road map that was loaded with `load_osm`.
+pub fn preprocess_osm(road_map: &RoadMap) -> DirectedNetworkGraph<OsmNetworkData> {
+    nwb::preprocess_roadmap(road_map)
+}
+
+fn load_road_sections(
+    ways: &[OsmWay],
+    locations: &HashMap<i64, DVec2>,
+) -> HashMap<RoadId, RoadSection> {
+    // A node is a junction when it is the end of a way or used more than once.
+    let mut usage = HashMap::<i64, usize>::new();
+    for way in ways {
+        for node in &way.refs {
+            *usage.entry(*node).or_default() += 1;
+        }
+        for node in [way.refs.first(), way.refs.last()].into_iter().flatten() {
+            *usage.entry(*node).or_default() += 1;
+        }
+    }
+
+    let mut roads = HashMap::new();
+
+    for way in ways {
+        // Nodes outside the extract are missing, the way is cut into the parts that are available.
+        for available in way
+            .refs
+            .split(|node| !locations.contains_key(node))
+            .filter(|nodes| nodes.len() >= 2)
+        {
+            let mut start = 0;
+
+            for end in 1..available.len() {
+                let is_last = end == available.len() - 1;
+                if !is_last && usage[&available[end]] < 2 {
+                    continue;
+                }
+
+                let nodes = &available[start..=end];
+                start = end;
+
+                // A loop that leaves and returns at the same junction, like a roundabout with one exit,
+                // is split at its middle node so it does not become an edge from a node to itself.
+                if nodes.first() == nodes.last() {
+                    if nodes.len() < 3 {
+                        continue;
+                    }
+                    let middle = nodes.len() / 2;
+                    add_section(&mut roads, way, locations, &nodes[..=middle]);
+                    add_section(&mut roads, way, locations, &nodes[middle..]);
+                } else {
+                    add_section(&mut roads, way, locations, nodes);
+                }
+            }
+        }
+    }
+
+    roads
+}
+
+fn add_section(
+    roads: &mut HashMap<RoadId, RoadSection>,
+    way: &OsmWay,
+    locations: &HashMap<i64, DVec2>,
+    nodes: &[i64],
+) {
+    let id = RoadId::from(roads.len());
+    let points = nodes.iter().map(|node| locations[node]).collect();
+    let attributes = road_attributes(way, nodes[0], nodes[nodes.len() - 1]);
+
+    let section = RoadSection::new(id, vec![points], attributes).expect("Way has nodes");
+    roads.insert(id, section);
+}
+
+fn road_attributes(way: &OsmWay, start: i64, end: i64) -> RoadAttributes {
+    let tag = |key: &str| way.tags.get(key).cloned();
+    let highway = tag("highway").unwrap_or_default();
+
+    let route_numbers = way
+        .tags
+        .get("ref")
+        .map(|x| x.split(';').map(|x| x.replace(' ', "")).collect())
+        .unwrap_or_default();
+
+    let speed_limit = way
+        .tags
+        .get("maxspeed")
+        .and_then(|x| parse_max_speed(x))
+        .or_else(|| default_speed(&highway));
+
+    RoadAttributes {
+        wegvak_id: way.id as usize,
+        junction_start: JunctionId::from(start as usize),
+        junction_end: JunctionId::from(end as usize),
+        road_type: Some(highway),
+        road_number: tag("ref"),
+        street_name: tag("name"),
+        alternative_name: tag("alt_name"),
+        municipality: None,
+        residence: tag("addr:city"),
+        authority: tag("operator"),
+        authority_type: None,
+        route_numbers,
+        frc: None,
+        fow: None,
+        direction: driving_direction(&way.tags),
+        relative_height: tag("layer")
+            .and_then(|x| x.parse().ok())
+            .unwrap_or_default(),
+        house_numbers_left: None,
+        house_numbers_right: None,
+        speed_limit,
+    }
+}
+
+/// The `highway` values of roads that can be driven by car.
+fn is_road(highway: &str) -> bool {
+    matches!(
+        highway,
+        "motorway"
+            | "motorway_link"
+            | "trunk"
+            | "trunk_link"
+            | "primary"
+            | "primary_link"
+            | "secondary"
+            | "secondary_link"
+            | "tertiary"
+            | "tertiary_link"
+            | "unclassified"
+            | "residential"
+            | "living_street"
+            | "service"
+            | "road"
+    )
+}
+
+/// Driving direction from the `oneway` tag. Motorways and roundabouts are one way unless tagged otherwise.
+fn driving_direction(tags: &HashMap<String, String>) -> DrivingDirection {
+    match tags.get("oneway").map(|x| x.as_str()) {
+        Some("yes" | "true" | "1") => DrivingDirection::Forward,
+        Some("-1" | "reverse") => DrivingDirection::Backward,
+        Some("no" | "false" | "0") => DrivingDirection::Both,
+        Some(_) => DrivingDirection::Unknown,
+        None => {
+            let implied = tags.get("highway").is_some_and(|x| x == "motorway")
+                || tags
+                    .get("junction")
+                    .is_some_and(|x| x == "roundabout" || x == "circular");
+
+            if implied {
+                DrivingDirection::Forward
+            } else {
+                DrivingDirection::Both
+            }
+        }
+    }
+}
+
+/// Parse a `maxspeed` value to km/h, for example `50`, `30 mph`, `walk` or `NL:urban`.
+fn parse_max_speed(value: &str) -> Option<f32> {
+    let value = value.split(';').next()?.trim();
+
+    match value {
+        "walk" => return Some(6.0),
+        "none" | "signals" | "variable" => return None,
+        _ => {}
+    }
+
+    if let Some((_, zone)) = value.split_once(':') {
+        return match zone {
+            "living_street" => Some(15.0),
+            "urban" => Some(50.0),
+            "rural" => Some(80.0),
+            "trunk" => Some(100.0),
+            "motorway" => Some(130.0),
+            _ => None,
+        };
+    }
+
+    match value.strip_suffix("mph") {
+        Some(mph) => mph.trim().parse::<f32>().ok().map(|x| x * 1.609_344),
+        None => value.trim_end_matches("km/h").trim().parse().ok(),
+    }
+}
+
+/// Speed in km/h for roads without a (valid) `maxspeed` tag.
+fn default_speed(highway: &str) -> Option<f32> {
+    let speed = match highway {
+        "motorway" => 120.0,
+        "trunk" => 100.0,
+        "primary" | "secondary" | "motorway_link" => 80.0,
+        "tertiary" | "trunk_link" | "primary_link" => 60.0,
+        "secondary_link" | "tertiary_link" | "unclassified" | "road" => 50.0,
+        "residential" => 30.0,
+        "living_street" | "service" => 15.0,
+        _ => return None,
+    };
+
+    Some(speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph::{NetworkData, NodeId};
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/small.osm.pbf");
+
+    fn sections_of_way(road_map: &RoadMap, way: usize) -> Vec<&RoadSection> {
+        let mut sections = road_map
+            .roads
+            .values()
+            .filter(|section| section.attributes.wegvak_id == way)
+            .collect::<Vec<_>>();
+        sections.sort_by_key(|section| section.attributes.junction_start.num());
+        sections
+    }
+
+    #[test]
+    fn load_fixture() {
+        let road_map = load_osm(FIXTURE).unwrap();
+
+        // The footway is skipped and way 105 references a node outside the extract.
+        assert_eq!(road_map.roads.len(), 6);
+        assert_eq!(road_map.junction_spatial.size(), 7);
+
+        // Way 100 is split at node 2, which is shared with way 101.
+        let dorpsstraat = sections_of_way(&road_map, 100);
+        assert_eq!(dorpsstraat.len(), 2);
+        assert_eq!(
+            dorpsstraat[0].attributes.junction_start,
+            JunctionId::from(1)
+        );
+        assert_eq!(dorpsstraat[0].attributes.junction_end, JunctionId::from(2));
+        assert_eq!(dorpsstraat[1].attributes.junction_end, JunctionId::from(3));
+        assert_eq!(
+            dorpsstraat[0].attributes.street_name.as_deref(),
+            Some("Dorpsstraat")
+        );
+
+        // 0.001 degrees of longitude is roughly 70 meters at this latitude.
+        let length = dorpsstraat[0].length();
+        assert!(length > 60.0 && length < 80.0, "length: {}", length);
+    }
+
+    #[test]
+    fn fixture_directions_and_speeds() {
+        let road_map = load_osm(FIXTURE).unwrap();
+
+        let way = |id| &sections_of_way(&road_map, id)[0].attributes;
+
+        assert_eq!(way(100).direction, DrivingDirection::Both);
+        assert_eq!(way(100).speed_limit, Some(30.0));
+        assert_eq!(way(101).direction, DrivingDirection::Forward);
+        assert_eq!(way(101).speed_limit, Some(50.0));
+        assert_eq!(way(103).direction, DrivingDirection::Forward);
+        assert_eq!(way(103).speed_limit, Some(120.0));
+        assert_eq!(way(103).route_numbers, vec!["A12", "E35"]);
+        assert_eq!(way(104).direction, DrivingDirection::Backward);
+        assert!((way(104).speed_limit.unwrap() - 48.28).abs() < 0.01);
+    }
+
+    #[test]
+    fn closed_way_is_split() {
+        // Way 2 is a loop that is only connected to way 1 at node 2.
+        let way = |id, refs: Vec<i64>| OsmWay {
+            id,
+            refs,
+            tags: HashMap::from([("highway".to_string(), "residential".to_string())]),
+        };
+        let ways = [way(1, vec![1, 2]), way(2, vec![2, 3, 4, 5, 2])];
+        let locations = (1..=5)
+            .map(|x| (x, DVec2::new(x as f64 * 10.0, (x % 2) as f64 * 10.0)))
+            .collect::<HashMap<_, _>>();
+
+        let road_map = RoadMap::from_sections(load_road_sections(&ways, &locations));
+        assert_eq!(road_map.roads.len(), 3);
+
+        let mut ends = sections_of_way(&road_map, 2)
+            .iter()
+            .map(|x| {
+                (
+                    x.attributes.junction_start.num(),
+                    x.attributes.junction_end.num(),
+                )
+            })
+            .collect::<Vec<_>>();
+        ends.sort();
+        assert_eq!(ends, vec![(2, 4), (4, 2)]);
+
+        // The loop can be driven from node 2 and back.
+        let network = preprocess_osm(&road_map);
+        assert_eq!(network.nodes().len(), 3);
+        assert!(network.validate().is_ok());
+    }
+
+    #[test]
+    fn fixture_graph() {
+        let road_map = load_osm(FIXTURE).unwrap();
+        let network = preprocess_osm(&road_map);
+
+        assert_eq!(network.nodes().len(), 7);
+        // Every road section is stored at both of its junctions.
+        assert_eq!(network.edges().len(), 12);
+
+        for node in 0..network.nodes().len() {
+            let (junction, location) = network.data.node_data(NodeId::from(node));
+            let junction = road_map
+                .junction_spatial
+                .iter()
+                .find(|x| x.junction_id == *junction)
+                .unwrap();
+            assert_eq!(junction.location, *location);
+        }
+    }
+
+    #[test]
+    fn fixture_is_reproducible() {
+        use highway::generation::{calculate_layer, contraction::ContractionFactor};
+
+        let generate = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+
+            pool.install(|| {
+                let road_map = load_osm(FIXTURE).unwrap();
+                let network = preprocess_osm(&road_map);
+                let layer = calculate_layer(2, &network, &ContractionFactor::new(2.0));
+
+                bincode::serialize(&(network, layer)).unwrap()
+            })
+        };
+
+        let bytes = generate(1);
+        assert_eq!(bytes, generate(1));
+        assert_eq!(bytes, generate(4));
+    }
+
+    #[test]
+    fn max_speed_values() {
+        assert_eq!(parse_max_speed("50"), Some(50.0));
+        assert_eq!(parse_max_speed("80 km/h"), Some(80.0));
+        assert_eq!(parse_max_speed("30;50"), Some(30.0));
+        assert_eq!(parse_max_speed("walk"), Some(6.0));
+        assert_eq!(parse_max_speed("NL:urban"), Some(50.0));
+        assert_eq!(parse_max_speed("none"), None);
+        assert_eq!(parse_max_speed("fast"), None);
+        assert!((parse_max_speed("20 mph").unwrap() - 32.19).abs() < 0.01);
+    }
+
+    #[test]
+    fn oneway_values() {
+        let tags = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        assert_eq!(
+            driving_direction(&tags(&[("highway", "residential")])),
+            DrivingDirection::Both
+        );
+        assert_eq!(
+            driving_direction(&tags(&[("highway", "residential"), ("oneway", "yes")])),
+            DrivingDirection::Forward
+        );
+        assert_eq!(
+            driving_direction(&tags(&[("highway", "primary"), ("oneway", "-1")])),
+            DrivingDirection::Backward
+        );
+        assert_eq!(
+            driving_direction(&tags(&[("highway", "motorway")])),
+            DrivingDirection::Forward
+        );
+        assert_eq!(
+            driving_direction(&tags(&[("highway", "motorway"), ("oneway", "no")])),
+            DrivingDirection::Both
+        );
+        assert_eq!(
+            driving_direction(&tags(&[
+                ("highway", "tertiary"),
+                ("junction", "roundabout")
+            ])),
+            DrivingDirection::Forward
+        );
+        assert_eq!(
+            driving_direction(&tags(&[("highway", "service"), ("oneway", "reversible")])),
+            DrivingDirection::Unknown
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use super::{DirectedNetworkGraphContainer, HoverState};
+use crate::world::{WorldEntity, WorldEntitySelectionType};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_shapefile::{RoadId, RoadMap};
+use graph::{EdgeId, EdgeOverlay};
+use std::collections::{HashMap, HashSet};
+
+/// The largest distance in meters from the cursor to a road that is closed on click.
+const MAX_CLICK_DISTANCE: f64 = 25.0;
+
+pub struct ClosuresUIPlugin;
+
+impl Plugin for ClosuresUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RoadClosures::default())
+            .add_event::<RoadClosuresChanged>()
+            .add_systems(Update, index_system)
+            .add_systems(Update, gui_system)
+            .add_systems(Update, click_system)
+            .add_systems(Update, colouring_system);
+    }
+}
+
+/// Roads that are closed for the route search, without preprocessing the network again.
+#[derive(Debug, Default, Resource)]
+pub struct RoadClosures {
+    /// Clicking a road closes or reopens it, instead of selecting a node for the route.
+    pub closing: bool,
+    roads: HashSet<RoadId>,
+    /// The blocked edges of the closed roads.
+    pub overlay: EdgeOverlay,
+    /// The edges of every road of the network, both directions are stored at both ends.
+    road_edges: HashMap<RoadId, Vec<EdgeId>>,
+}
+
+impl RoadClosures {
+    /// Close `road` in both directions, or reopen it when it is closed.
+    fn toggle(&mut self, road: RoadId) {
+        let closed = self.roads.insert(road);
+        if !closed {
+            self.roads.remove(&road);
+        }
+
+        for edge in self.road_edges.get(&road).into_iter().flatten() {
+            if closed {
+                self.overlay.block(*edge);
+            } else {
+                self.overlay.unblock(*edge);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.roads.clear();
+        self.overlay.clear();
+    }
+}
+
+/// Sent when a road is closed or reopened, to find the route again.
+#[derive(Debug, Clone, Event)]
+pub struct RoadClosuresChanged;
+
+/// Index the edges of the roads when the network is inserted, the closures of another network are dropped.
+fn index_system(
+    network: Option<Res<DirectedNetworkGraphContainer>>,
+    mut closures: ResMut<RoadClosures>,
+    mut event_writer: EventWriter<RoadClosuresChanged>,
+) {
+    let Some(network) = network else {
+        return;
+    };
+    if !network.is_changed() {
+        return;
+    }
+
+    let mut road_edges = HashMap::<RoadId, Vec<EdgeId>>::new();
+    for edge in (0..network.edges().len()).map(EdgeId::from) {
+        road_edges
+            .entry(*network.edge_data(edge))
+            .or_default()
+            .push(edge);
+    }
+    closures.road_edges = road_edges;
+
+    if !closures.roads.is_empty() {
+        closures.clear();
+        event_writer.send(RoadClosuresChanged);
+    }
+}
+
+fn gui_system(
+    mut egui_context: EguiContexts,
+    mut closures: ResMut<RoadClosures>,
+    mut event_writer: EventWriter<RoadClosuresChanged>,
+) {
+    egui::Window::new("Road closures").show(egui_context.ctx_mut(), |ui| {
+        ui.checkbox(&mut closures.closing, "Close roads on click");
+        ui.label(format!("Closed roads: {}", closures.roads.len()));
+
+        if ui
+            .add_enabled(!closures.roads.is_empty(), egui::Button::new("Reopen all"))
+            .clicked()
+        {
+            closures.clear();
+            event_writer.send(RoadClosuresChanged);
+        }
+    });
+}
+
+fn click_system(
+    mut egui_context: EguiContexts,
+    mut closures: ResMut<RoadClosures>,
+    road_map: Res<RoadMap>,
+    hover: Res<HoverState>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut event_writer: EventWriter<RoadClosuresChanged>,
+) {
+    if !closures.closing || !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    // Clicks on the egui windows are not for the map.
+    if egui_context.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+    let Some(position) = hover.position else {
+        return;
+    };
+
+    if let Some((road, distance)) = road_map.nearest_road(position) {
+        if distance <= MAX_CLICK_DISTANCE {
+            closures.toggle(road);
+            event_writer.send(RoadClosuresChanged);
+        }
+    }
+}
+
+fn colouring_system(closures: Res<RoadClosures>, mut query: Query<&mut WorldEntity>) {
+    if closures.roads.is_empty() {
+        return;
+    }
+
+    query.par_iter_mut().for_each(|mut we| {
+        if closures.roads.contains(&we.id) {
+            we.selected = WorldEntitySelectionType::Closed;
+        }
+    });
+}
@@ -0,0 +1,131 @@
+use super::DirectedNetworkGraphContainer;
+use crate::{
+    nwb::NWBNetworkData,
+    world::{WorldEntity, WorldEntitySelectionType},
+};
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use bevy_egui::{egui, EguiContexts};
+use bevy_shapefile::RoadId;
+use futures_lite::future;
+use graph::{DirectedNetworkGraph, NodeId, StronglyConnectedComponents};
+use std::collections::HashSet;
+
+pub struct ComponentsUIPlugin;
+
+impl Plugin for ComponentsUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ComponentState::default())
+            .add_systems(Update, gui_system)
+            .add_systems(Update, waiting_for_task)
+            .add_systems(Update, colouring_system);
+    }
+}
+
+/// Overlay of the strongly connected components, to find errors in the road data.
+/// Roads outside of the largest component cannot be reached from, or cannot reach, most of the network.
+#[derive(Debug, Default, Resource)]
+pub struct ComponentState {
+    pub show_small_components: bool,
+    components: Option<StronglyConnectedComponents>,
+    /// Roads with an edge that starts outside of the largest component.
+    small_component_roads: HashSet<RoadId>,
+    /// The components of the loaded graph, while they are computed.
+    task: Option<Task<(StronglyConnectedComponents, HashSet<RoadId>)>>,
+}
+
+/// The components of `network` and the roads outside of the largest one.
+fn small_components(
+    network: &DirectedNetworkGraph<NWBNetworkData>,
+) -> (StronglyConnectedComponents, HashSet<RoadId>) {
+    let components = network.strongly_connected_components();
+
+    let roads = (0..network.nodes().len())
+        .map(NodeId::from)
+        .filter(|node| !components.in_largest(*node))
+        .flat_map(|node| {
+            network
+                .out_edges(node)
+                .map(|(id, _)| *network.edge_data(id))
+        })
+        .collect();
+
+    (components, roads)
+}
+
+fn gui_system(
+    mut egui_context: EguiContexts,
+    mut state: ResMut<ComponentState>,
+    network: Option<Res<DirectedNetworkGraphContainer>>,
+) {
+    let Some(network) = network else {
+        return;
+    };
+
+    // The components of another graph are dropped, a running task is cancelled.
+    if network.is_changed() {
+        state.components = None;
+        state.small_component_roads.clear();
+        state.task = None;
+    }
+
+    egui::Window::new("Components").show(egui_context.ctx_mut(), |ui| {
+        ui.checkbox(&mut state.show_small_components, "Show small components");
+
+        if state.show_small_components && state.components.is_none() && state.task.is_none() {
+            let network = network.0.clone();
+            let pool = AsyncComputeTaskPool::get();
+            state.task = Some(pool.spawn(async move { small_components(&network) }));
+        }
+
+        if state.task.is_some() {
+            ui.add(egui::Spinner::new());
+        }
+
+        if let Some(components) = &state.components {
+            let nodes = network.nodes().len();
+            let largest = components.sizes().first().cloned().unwrap_or_default();
+
+            ui.label(format!("Components: {}", components.len()));
+            ui.label(format!("Largest: {} of {} nodes", largest, nodes));
+            ui.label(format!(
+                "Roads outside: {}",
+                state.small_component_roads.len()
+            ));
+
+            ui.collapsing("Sizes", |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for line in components.to_string().lines().skip(1) {
+                            ui.label(line);
+                        }
+                    });
+            });
+        }
+    });
+}
+
+fn waiting_for_task(mut state: ResMut<ComponentState>) {
+    if let Some(task) = &mut state.task {
+        if let Some((components, roads)) = future::block_on(future::poll_once(task)) {
+            state.components = Some(components);
+            state.small_component_roads = roads;
+            state.task = None;
+        }
+    }
+}
+
+fn colouring_system(state: Res<ComponentState>, mut query: Query<&mut WorldEntity>) {
+    if !state.show_small_components {
+        return;
+    }
+
+    query.par_iter_mut().for_each(|mut we| {
+        if state.small_component_roads.contains(&we.id) {
+            we.selected = WorldEntitySelectionType::SmallComponent;
+        }
+    });
+}
@@ -1,9 +1,238 @@
-use bevy::app::{App, Plugin};
+use crate::world::{WorldEntity, WorldEntitySelectionType};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_shapefile::{DrivingDirection, RoadAttributes, RoadMap};
+use std::collections::BTreeSet;
+
+const MAX_SPEED_LIMIT: f32 = 130.0;
 
 pub struct FilterUIPlugin;
 
 impl Plugin for FilterUIPlugin {
-    fn build(&self, app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FilterResource::default())
+            .add_systems(Update, init_filter_options)
+            .add_systems(Update, gui_system)
+            .add_systems(Update, filter_system);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    /// Colour matching roads, leave the others untouched.
+    Highlight,
+    /// Hide all roads that do not match.
+    Show,
+}
+
+#[derive(Debug, Resource)]
+pub struct FilterResource {
+    pub enabled: bool,
+    pub mode: FilterMode,
+    /// Selected BST codes, no selection means every road type matches.
+    pub road_types: BTreeSet<Option<String>>,
+    /// Selected road authorities, no selection means every authority matches.
+    pub authorities: BTreeSet<Option<String>>,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    pub include_unknown_speed: bool,
+    pub directions: BTreeSet<DrivingDirection>,
+
+    authority_search: String,
+}
+
+impl Default for FilterResource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: FilterMode::Highlight,
+            road_types: BTreeSet::new(),
+            authorities: BTreeSet::new(),
+            min_speed: 0.0,
+            max_speed: MAX_SPEED_LIMIT,
+            include_unknown_speed: true,
+            directions: BTreeSet::from([
+                DrivingDirection::Forward,
+                DrivingDirection::Backward,
+                DrivingDirection::Both,
+                DrivingDirection::Unknown,
+            ]),
+            authority_search: String::new(),
+        }
+    }
+}
+
+impl FilterResource {
+    pub fn matches(&self, record: &RoadAttributes) -> bool {
+        let road_type = self.road_types.is_empty() || self.road_types.contains(&record.road_type);
+        let authority = self.authorities.is_empty() || self.authorities.contains(&record.authority);
+        let speed = match record.speed_limit {
+            Some(speed) => speed >= self.min_speed && speed <= self.max_speed,
+            None => self.include_unknown_speed,
+        };
+        let direction = self.directions.contains(&record.direction);
+
+        road_type && authority && speed && direction
+    }
+}
+
+/// The values that can be picked in the filter window.
+#[derive(Debug, Resource)]
+struct FilterOptions {
+    road_types: Vec<Option<String>>,
+    authorities: Vec<Option<String>>,
+}
+
+fn init_filter_options(
+    mut commands: Commands,
+    road_map: Option<Res<RoadMap>>,
+    options: Option<Res<FilterOptions>>,
+) {
+    if options.is_some() {
+        return;
+    }
+    let Some(road_map) = road_map else {
+        return;
+    };
+
+    let road_types = road_map
+        .roads
+        .values()
+        .map(|section| section.attributes.road_type.clone())
+        .collect::<BTreeSet<_>>();
+    let authorities = road_map
+        .roads
+        .values()
+        .map(|section| section.attributes.authority.clone())
+        .collect::<BTreeSet<_>>();
+
+    commands.insert_resource(FilterOptions {
+        road_types: road_types.into_iter().collect(),
+        authorities: authorities.into_iter().collect(),
+    });
+}
+
+fn gui_system(
+    mut egui_context: EguiContexts,
+    mut filter: ResMut<FilterResource>,
+    options: Option<Res<FilterOptions>>,
+) {
+    let Some(options) = options else {
+        return;
+    };
+
+    egui::Window::new("Filter").show(egui_context.ctx_mut(), |ui| {
+        ui.checkbox(&mut filter.enabled, "Enable filter");
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut filter.mode, FilterMode::Highlight, "Highlight");
+            ui.radio_value(&mut filter.mode, FilterMode::Show, "Show only");
+        });
+
+        ui.collapsing("Road types", |ui| {
+            egui::ScrollArea::vertical()
+                .id_source("road_types")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for road_type in &options.road_types {
+                        let label = road_type.as_deref().unwrap_or("(Geen)");
+                        toggle(ui, &mut filter.road_types, road_type, label);
+                    }
+                });
+        });
+
+        ui.collapsing("Road authority", |ui| {
+            ui.text_edit_singleline(&mut filter.authority_search);
+            let search = filter.authority_search.to_lowercase();
+
+            egui::ScrollArea::vertical()
+                .id_source("authorities")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for authority in &options.authorities {
+                        let label = authority.as_deref().unwrap_or("(Geen)");
+                        if label.to_lowercase().contains(&search) {
+                            toggle(ui, &mut filter.authorities, authority, label);
+                        }
+                    }
+                });
+        });
+
+        ui.collapsing("Speed limit", |ui| {
+            let max_speed = filter.max_speed;
+            ui.add(egui::Slider::new(&mut filter.min_speed, 0.0..=max_speed).text("Min km/h"));
+            let min_speed = filter.min_speed;
+            ui.add(
+                egui::Slider::new(&mut filter.max_speed, min_speed..=MAX_SPEED_LIMIT)
+                    .text("Max km/h"),
+            );
+            ui.checkbox(&mut filter.include_unknown_speed, "Include unknown");
+        });
+
+        ui.collapsing("Driving direction", |ui| {
+            let directions = [
+                (DrivingDirection::Forward, "Forward (H)"),
+                (DrivingDirection::Backward, "Backward (T)"),
+                (DrivingDirection::Both, "Both (B)"),
+                (DrivingDirection::Unknown, "Unknown (O)"),
+            ];
+            for (direction, label) in directions {
+                toggle(ui, &mut filter.directions, &direction, label);
+            }
+        });
+    });
+}
+
+fn toggle<T: Ord + Clone>(ui: &mut egui::Ui, set: &mut BTreeSet<T>, value: &T, label: &str) {
+    let mut selected = set.contains(value);
+    if ui.checkbox(&mut selected, label).changed() {
+        if selected {
+            set.insert(value.clone());
+        } else {
+            set.remove(value);
+        }
+    }
 }
 
-pub struct FilterResource {}
+fn filter_system(
+    filter: Res<FilterResource>,
+    road_map: Option<Res<RoadMap>>,
+    mut query: Query<(&mut WorldEntity, &mut Visibility)>,
+) {
+    let Some(road_map) = road_map else {
+        return;
+    };
+
+    if !filter.enabled {
+        if filter.is_changed() {
+            query.par_iter_mut().for_each(|(_, mut visibility)| {
+                *visibility = Visibility::Inherited;
+            });
+        }
+        return;
+    }
+
+    query.par_iter_mut().for_each(|(mut we, mut visibility)| {
+        let matches = road_map
+            .roads
+            .get(&we.id)
+            .map(|section| filter.matches(&section.attributes))
+            .unwrap_or_default();
+
+        match filter.mode {
+            FilterMode::Highlight => {
+                *visibility = Visibility::Inherited;
+                if matches {
+                    we.selected = WorldEntitySelectionType::Filtered;
+                }
+            }
+            FilterMode::Show => {
+                *visibility = if matches {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+            }
+        }
+    });
+}
@@ -0,0 +1,70 @@
+use super::HoverState;
+use crate::geocoding;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_shapefile::RoadMap;
+
+pub struct HoverUIPlugin;
+
+impl Plugin for HoverUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, tooltip_system);
+    }
+}
+
+fn tooltip_system(
+    mut egui_context: EguiContexts,
+    hover: Res<HoverState>,
+    road_map: Option<Res<RoadMap>>,
+) {
+    let Some(road_map) = road_map else {
+        return;
+    };
+    let Some(position) = hover.position else {
+        return;
+    };
+
+    let ctx = egui_context.ctx_mut();
+
+    // Do not cover the egui windows with a tooltip.
+    if ctx.is_pointer_over_area() {
+        return;
+    }
+
+    let Some(result) = geocoding::reverse_geocode(&road_map, position) else {
+        return;
+    };
+
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("road_tooltip"), |ui| {
+        let address = result.address;
+
+        ui.strong(address.street_name.as_deref().unwrap_or("Unnamed road"));
+
+        if let Some(residence) = &address.residence {
+            ui.label(residence);
+        }
+        if let Some(municipality) = &address.municipality {
+            ui.label(format!("Municipality: {}", municipality));
+        }
+        if let Some(authority) = &address.authority {
+            ui.label(format!("Road authority: {}", authority));
+        }
+        if let Some(range) = address.house_numbers_left {
+            ui.label(format!("House numbers (left): {}", range));
+        }
+        if let Some(range) = address.house_numbers_right {
+            ui.label(format!("House numbers (right): {}", range));
+        }
+
+        ui.separator();
+
+        ui.label(format!("Road: {}", result.road_id.num()));
+        if let Some((junction, node)) = hover.junction {
+            ui.label(format!("Junction: {} (node {})", junction.num(), node.0));
+        }
+        match address.speed_limit {
+            Some(speed) => ui.label(format!("Speed limit: {} km/h", speed)),
+            None => ui.label("Speed limit: unknown"),
+        };
+    });
+}
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, path::Path};
 
 use crate::{
+    geojson::{self, Projection},
     nwb::NWBNetworkData,
     world::{WorldEntity, WorldEntitySelectionType},
 };
@@ -9,21 +10,134 @@ use bevy::{
     tasks::{AsyncComputeTaskPool, Task},
 };
 use bevy_egui::{egui, EguiContexts};
-use bevy_shapefile::RoadId;
+use bevy_shapefile::{RoadId, RoadMap};
 use futures_lite::future;
-use highway::generation::intermediate_network::IntermediateData;
-use graph::{DirectedNetworkGraph, EdgeId, NetworkData};
+use highway::generation::{
+    contraction::{ContractionFactor, ContractionPolicy, EdgeDifference, ListGrowth, UnpackLimit},
+    hierarchy::{build_hierarchy_with_state, HierarchyConfig, StopReason},
+    intermediate_network::IntermediateData,
+    ComputedState,
+};
+use graph::{DirectedNetworkGraph, EdgeId, NeighbourhoodSize, NetworkData, Progress};
 
 use super::DirectedNetworkGraphContainer;
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyKind {
+    #[default]
+    ContractionFactor,
+    EdgeDifference,
+    UnpackLimit,
+    ListGrowth,
+}
+
+impl PolicyKind {
+    const ALL: [PolicyKind; 4] = [
+        PolicyKind::ContractionFactor,
+        PolicyKind::EdgeDifference,
+        PolicyKind::UnpackLimit,
+        PolicyKind::ListGrowth,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            PolicyKind::ContractionFactor => "Contraction factor",
+            PolicyKind::EdgeDifference => "Edge difference",
+            PolicyKind::UnpackLimit => "Unpack limit",
+            PolicyKind::ListGrowth => "List growth",
+        }
+    }
+}
+
 #[derive(Debug, Default, Resource)]
 pub struct LayerState {
     pub preprocess_layers: usize,
     pub neighbourhood_size: usize,
+    /// Limit the neighbourhoods to `neighbourhood_distance` metres.
+    pub distance_cap: bool,
+    pub neighbourhood_distance: f32,
+    pub policy: PolicyKind,
     pub contraction_factor: f32,
+    pub max_edge_difference: i64,
+    pub max_shortcut_hops: u32,
+    pub max_shortcut_roads: u32,
+    pub max_list_growth: u32,
     pub base_selected: bool,
     pub layers_selected: Vec<bool>,
-    pub processing: bool,
+    /// The progress of the preprocessing task, while it runs.
+    pub processing: Option<Progress>,
+}
+
+impl LayerState {
+    fn neighbourhood_size(&self) -> NeighbourhoodSize {
+        if self.distance_cap {
+            NeighbourhoodSize::Bounded {
+                nodes: self.neighbourhood_size,
+                distance: self.neighbourhood_distance,
+            }
+        } else {
+            NeighbourhoodSize::Nodes(self.neighbourhood_size)
+        }
+    }
+
+    /// The cached layers of these settings, named after the levels, the neighbourhood size,
+    /// the policy and its parameters, and the fingerprint of the `base` graph.
+    fn cache_path(&self, base: u64) -> String {
+        let size = if self.distance_cap {
+            format!(
+                "n{}_d{}",
+                self.neighbourhood_size, self.neighbourhood_distance
+            )
+        } else {
+            format!("n{}", self.neighbourhood_size)
+        };
+
+        format!(
+            "data/layers_{}_{}_{}_{:016x}.graph",
+            self.preprocess_layers,
+            size,
+            self.policy_key(),
+            base
+        )
+    }
+
+    fn policy_key(&self) -> String {
+        match self.policy {
+            PolicyKind::ContractionFactor => format!("factor_{}", self.contraction_factor),
+            PolicyKind::EdgeDifference => format!(
+                "edges_{}_{}",
+                self.max_edge_difference, self.max_shortcut_hops
+            ),
+            PolicyKind::UnpackLimit => format!(
+                "unpack_{}_{}",
+                self.contraction_factor, self.max_shortcut_roads
+            ),
+            PolicyKind::ListGrowth => format!(
+                "growth_{}_{}",
+                self.contraction_factor, self.max_list_growth
+            ),
+        }
+    }
+
+    fn contraction_policy(&self) -> Box<dyn ContractionPolicy> {
+        match self.policy {
+            PolicyKind::ContractionFactor => {
+                Box::new(ContractionFactor::new(self.contraction_factor))
+            }
+            PolicyKind::EdgeDifference => Box::new(EdgeDifference {
+                max_difference: self.max_edge_difference,
+                max_hops: self.max_shortcut_hops,
+            }),
+            PolicyKind::UnpackLimit => Box::new(UnpackLimit {
+                contraction_factor: self.contraction_factor,
+                max_roads: self.max_shortcut_roads,
+            }),
+            PolicyKind::ListGrowth => Box::new(ListGrowth {
+                contraction_factor: self.contraction_factor,
+                max_growth: self.max_list_growth,
+            }),
+        }
+    }
 }
 
 #[derive(Component)]
@@ -35,69 +149,144 @@ pub fn gui_system(
     mut state: ResMut<LayerState>,
     preprocess: Option<Res<PreProcess>>,
     base_network: Res<DirectedNetworkGraphContainer>,
+    road_map: Res<RoadMap>,
 ) {
     egui::Window::new("Preprocessing").show(egui_context.ctx_mut(), |ui| {
         ui.label("Preprocess");
         ui.add(egui::Slider::new(&mut state.preprocess_layers, 1..=20).text("Layers"));
         ui.add(egui::Slider::new(&mut state.neighbourhood_size, 1..=90).text("Neighbourhood size"));
+        ui.checkbox(&mut state.distance_cap, "Cap neighbourhood distance");
+        if state.distance_cap {
+            let slider = egui::Slider::new(&mut state.neighbourhood_distance, 100.0..=20000.0);
+            ui.add(slider.logarithmic(true).text("Neighbourhood distance (m)"));
+        }
+
+        egui::ComboBox::from_label("Contraction")
+            .selected_text(state.policy.name())
+            .show_ui(ui, |ui| {
+                for kind in PolicyKind::ALL {
+                    ui.selectable_value(&mut state.policy, kind, kind.name());
+                }
+            });
+
+        if state.policy == PolicyKind::EdgeDifference {
+            let slider = egui::Slider::new(&mut state.max_edge_difference, -10..=10);
+            ui.add(slider.text("Max edge difference"));
+            let slider = egui::Slider::new(&mut state.max_shortcut_hops, 1..=100);
+            ui.add(slider.text("Max shortcut hops"));
+        } else {
+            let slider = egui::Slider::new(&mut state.contraction_factor, 0.5..=5.0);
+            ui.add(slider.text("Contraction factor"));
+        }
+        if state.policy == PolicyKind::UnpackLimit {
+            let slider = egui::Slider::new(&mut state.max_shortcut_roads, 1..=2000);
+            ui.add(slider.text("Max shortcut roads"));
+        }
+        if state.policy == PolicyKind::ListGrowth {
+            let slider = egui::Slider::new(&mut state.max_list_growth, 1..=500);
+            ui.add(slider.text("Max list growth"));
+        }
+
+        if let Some(progress) = &state.processing {
+            match progress.report() {
+                Some(report) => {
+                    let eta = report
+                        .eta()
+                        .map(|x| format!(", {}s left", x.as_secs()))
+                        .unwrap_or_default();
+                    let text = format!(
+                        "Level {}: {} {}/{}{}",
+                        report.level, report.phase, report.done, report.total, eta
+                    );
+                    ui.add(egui::ProgressBar::new(report.fraction()).text(text));
+                }
+                None => {
+                    ui.add(egui::Spinner::new());
+                }
+            }
 
-        if state.processing {
-            ui.add(egui::Spinner::new());
+            if progress.is_cancelled() {
+                ui.label("Cancelling");
+            } else if ui.button("Cancel").clicked() {
+                progress.cancel();
+            }
         } else if ui.button("Start Preprocess").clicked() {
             let network = base_network.clone();
-            let layer_count = state.preprocess_layers;
-            let neighbourhood_size = state.neighbourhood_size;
-            let contraction_factor = state.contraction_factor;
+            let path = state.cache_path(base_network.fingerprint());
+            let config = HierarchyConfig {
+                neighbourhood_sizes: vec![state.neighbourhood_size()],
+                policy: state.contraction_policy(),
+                max_levels: state.preprocess_layers,
+                ..Default::default()
+            };
 
-            let task = AsyncComputeTaskPool::get().spawn(async move {
-                clicked_preprocess(network, layer_count, neighbourhood_size, contraction_factor)
-            });
-            state.processing = true;
+            let progress = Progress::new();
+            state.processing = Some(progress.clone());
+
+            let task = AsyncComputeTaskPool::get()
+                .spawn(async move { clicked_preprocess(network, path, config, progress) });
 
             commands.spawn(ComputeTask(task));
         }
 
         if let Some(preprocess) = preprocess {
-            ui.add(egui::Checkbox::new(&mut state.base_selected, "Layer: 1"));
+            // Level 0 is the base graph, like in the GeoJSON export.
+            ui.add(egui::Checkbox::new(&mut state.base_selected, "Layer: 0"));
 
             for i in 0..preprocess.layers.len() {
-                let rekt = format!("Layer: {}", i + 2);
+                let rekt = format!("Layer: {}", i + 1);
                 ui.add(egui::Checkbox::new(&mut state.layers_selected[i], rekt));
             }
+
+            if ui.button("Export GeoJSON").clicked() {
+                for (i, layer) in preprocess.layers.iter().enumerate() {
+                    let level = i as u8 + 1;
+                    let value = geojson::layer_features(&road_map, layer, level, Projection::Wgs84);
+                    geojson::write_geojson(&value, format!("data/layer_{}.geojson", level))
+                        .expect("Could not write");
+                }
+            }
         }
     });
 }
 
+/// The layers are only written when they are calculated, `calculate` returns `None` when it is cancelled.
 fn load_or_calculate<P: AsRef<Path>, F>(
     path: P,
     calculate: F,
-) -> DirectedNetworkGraph<IntermediateData>
+) -> Option<Vec<DirectedNetworkGraph<IntermediateData>>>
 where
-    F: Fn() -> DirectedNetworkGraph<IntermediateData>,
+    F: FnOnce() -> Option<Vec<DirectedNetworkGraph<IntermediateData>>>,
 {
-    if let Ok(network) = crate::read_file(&path) {
-        network
+    if let Ok(layers) = crate::read_file::<Vec<DirectedNetworkGraph<_>>, _>(&path) {
+        for layer in &layers {
+            layer.debug_validate();
+        }
+        Some(layers)
     } else {
-        let network = calculate();
-        crate::write_file(&network, path).expect("Could not write");
+        let layers = calculate()?;
+        crate::write_file(&layers, path).expect("Could not write");
 
-        network
+        Some(layers)
     }
 }
 
 pub fn handle_preprocess_task(
     mut commands: Commands,
     mut state: ResMut<LayerState>,
-    mut query: Query<(Entity, &mut ComputeTask<PreProcess>)>,
+    mut query: Query<(Entity, &mut ComputeTask<Option<PreProcess>>)>,
 ) {
     if let Ok((entity, mut task)) = query.get_single_mut() {
         if let Some(preprocess) = future::block_on(future::poll_once(&mut task.0)) {
-            state.processing = false;
+            state.processing = None;
 
-            state.base_selected = false;
-            state.layers_selected = vec![false; preprocess.layers.len()];
+            // The task returns nothing when it is cancelled.
+            if let Some(preprocess) = preprocess {
+                state.base_selected = false;
+                state.layers_selected = vec![false; preprocess.layers.len()];
 
-            commands.insert_resource(preprocess);
+                commands.insert_resource(preprocess);
+            }
 
             commands.entity(entity).despawn();
         }
@@ -106,17 +295,24 @@ pub fn handle_preprocess_task(
 
 fn clicked_preprocess(
     base: DirectedNetworkGraph<NWBNetworkData>,
-    layer_count: usize,
-    neighbourhood: usize,
-    contraction_factor: f32,
-) -> PreProcess {
-    println!("Clicked: {}", layer_count);
+    path: String,
+    config: HierarchyConfig,
+    progress: Progress,
+) -> Option<PreProcess> {
+    println!("Clicked: {}", config.max_levels);
+
+    // The neighbourhoods of the base graph do not depend on the contraction settings,
+    // they are computed again when they are of another size or base graph.
+    let base_state = crate::read_file::<ComputedState, _>("data/neighbourhoods.graph").ok();
 
-    let mut layers = Vec::new();
+    let layers = load_or_calculate(path, || {
+        let hierarchy = build_hierarchy_with_state(&base, base_state, &config, &progress);
+        if let Some(base_state) = hierarchy.radii.first() {
+            crate::write_file(base_state, "data/neighbourhoods.graph").expect("Could not write");
+        }
 
-    layers.push(load_or_calculate("data/layer_0.graph", || {
-        highway::generation::calculate_layer(neighbourhood, &base, contraction_factor)
-    }));
+        (hierarchy.stop_reason != StopReason::Cancelled).then_some(hierarchy.layers)
+    })?;
 
     println!(
         "Base edges: {}, nodes: {}",
@@ -124,27 +320,7 @@ fn clicked_preprocess(
         base.nodes().len()
     );
 
-    for i in 1..layer_count {
-        let size = neighbourhood;
-        let network = layers.last().unwrap();
-        let path = format!("data/layer_{}.graph", i);
-        let next_layer = load_or_calculate(path, || {
-            highway::generation::calculate_layer(size, network, contraction_factor)
-        });
-
-        println!(
-            "Layer {} edges: {}/{}, nodes: {}/{}",
-            i,
-            next_layer.edges().len(),
-            network.edges().len() as f32 / next_layer.edges().len() as f32,
-            next_layer.nodes().len(),
-            network.nodes().len() as f32 / next_layer.nodes().len() as f32
-        );
-
-        layers.push(next_layer);
-    }
-
-    PreProcess::new(base, layers)
+    Some(PreProcess::new(base, layers))
 }
 
 pub fn colouring_system(
@@ -1,10 +1,10 @@
 use crate::{
     nwb::NWBNetworkData,
-    world::{WorldEntity, WorldEntitySelectionType},
+    world::{RenderOrigin, WorldEntity, WorldEntitySelectionType},
 };
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{math::DVec2, prelude::*, window::PrimaryWindow};
 use bevy_egui::EguiPlugin;
-use bevy_shapefile::RoadMap;
+use bevy_shapefile::{JunctionId, RoadMap};
 pub use layers::PreProcess;
 use graph::{DirectedNetworkGraph, NodeId};
 use std::{
@@ -12,10 +12,16 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use self::{filter::FilterUIPlugin, layers::LayerState, route::RouteUIPlugin};
+use self::{
+    closures::ClosuresUIPlugin, components::ComponentsUIPlugin, filter::FilterUIPlugin, hover::HoverUIPlugin,
+    layers::{LayerState, PolicyKind}, route::RouteUIPlugin,
+};
 
 mod layers;
+mod closures;
+mod components;
 mod filter;
+mod hover;
 mod route;
 
 pub struct HighwayUiPlugin;
@@ -41,14 +47,25 @@ impl Plugin for HighwayUiPlugin {
         app.add_plugins(EguiPlugin)
             .add_plugins(RouteUIPlugin)
             .add_plugins(FilterUIPlugin)
+            .add_plugins(HoverUIPlugin)
+            .add_plugins(ComponentsUIPlugin)
+            .add_plugins(ClosuresUIPlugin)
             .add_event::<PointClickedEvent>()
+            .insert_resource(HoverState::default())
             .insert_resource(LayerState {
                 preprocess_layers: 6,
                 neighbourhood_size: 30,
+                distance_cap: false,
+                neighbourhood_distance: 5000.0,
+                policy: PolicyKind::ContractionFactor,
                 contraction_factor: 2.0,
+                max_edge_difference: 0,
+                max_shortcut_hops: 10,
+                max_shortcut_roads: 500,
+                max_list_growth: 64,
                 base_selected: false,
                 layers_selected: vec![],
-                processing: false,
+                processing: None,
             })
             .add_systems(Update, layers::colouring_system)
             .add_systems(Update, layers::handle_preprocess_task)
@@ -57,16 +74,28 @@ impl Plugin for HighwayUiPlugin {
     }
 }
 
+/// What is currently under the cursor in world space.
+#[derive(Debug, Default, Resource)]
+pub struct HoverState {
+    pub position: Option<DVec2>,
+    pub junction: Option<(JunctionId, NodeId)>,
+}
+
 fn mouse_point_system(
     windows: Query<&Window, With<PrimaryWindow>>,
     network: Res<DirectedNetworkGraphContainer>,
     road_map: Res<RoadMap>,
+    origin: Res<RenderOrigin>,
     camera_q: Query<(&GlobalTransform, &Camera)>,
 
     mut event_writer: EventWriter<PointClickedEvent>,
     buttons: Res<ButtonInput<MouseButton>>,
     mut query: Query<&mut WorldEntity>,
+    mut hover: ResMut<HoverState>,
 ) {
+    hover.position = None;
+    hover.junction = None;
+
     if let Ok(window) = windows.get_single() {
         if let Ok((transform, camera)) = camera_q.get_single() {
             if let Some(position) = window.cursor_position() {
@@ -74,16 +103,23 @@ fn mouse_point_system(
                     2.0 * position.x / window.width() - 1.0,
                     -(2.0 * position.y / window.height() - 1.0),
                 );
-                let world = crate::world::convert(position, transform, camera);
+                let world = origin.to_world(crate::world::convert(position, transform, camera));
                 let node = road_map
                     .junction_spatial
                     .nearest_neighbor(&[world.x, world.y])
                     .unwrap();
 
-                let node_id = (0..network.nodes().len())
+                // Junctions of pruned components are not part of the graph.
+                let Some(node_id) = (0..network.nodes().len())
                     .map(NodeId::from)
                     .find(|x| network.node_data(*x).0 == node.junction_id)
-                    .unwrap();
+                else {
+                    hover.position = Some(world);
+                    return;
+                };
+
+                hover.position = Some(world);
+                hover.junction = Some((node.junction_id, node_id));
 
                 let out_edges = network
                     .out_edges(node_id)
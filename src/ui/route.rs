@@ -1,5 +1,7 @@
+use super::closures::{RoadClosures, RoadClosuresChanged};
 use super::DirectedNetworkGraphContainer;
 use super::PointClickedEvent;
+use crate::geojson::{self, Projection};
 use crate::world::WorldEntity;
 use crate::world::WorldEntitySelectionType;
 use bevy::prelude::*;
@@ -8,7 +10,7 @@ use bevy::tasks::Task;
 use bevy_egui::{egui, EguiContexts};
 use bevy_shapefile::RoadMap;
 use futures_lite::future;
-use graph::{DirectedNetworkGraph, EdgeId, NetworkData, NodeId, F32};
+use graph::{DirectedNetworkGraph, EdgeId, EdgeOverlay, NetworkData, NodeId, F32};
 use std::{
     cmp::Reverse,
     collections::{BinaryHeap, HashMap, HashSet},
@@ -21,6 +23,7 @@ impl Plugin for RouteUIPlugin {
         app.insert_resource(NodeSelectionState::default())
             .add_systems(Update, gui_system)
             .add_systems(Update, waiting_for_task)
+            .add_systems(Update, detour_system)
             .add_systems(Update, route_draw);
     }
 }
@@ -31,16 +34,42 @@ pub enum NodeSelectionState {
     NoRoute,
     FindingNode1,
     FindingNode2(NodeId),
-    FindingRoute(Task<Result<Vec<EdgeId>, String>>),
-    FoundRoute(Vec<EdgeId>),
+    FindingRoute(NodeId, NodeId, Task<Result<Vec<EdgeId>, String>>),
+    FoundRoute(FoundRoute),
 }
+
+#[derive(Debug)]
+pub struct FoundRoute {
+    source: NodeId,
+    target: NodeId,
+    /// The route without the road closures.
+    edges: Vec<EdgeId>,
+    detour: Detour,
+}
+
+/// The route around the closed roads.
+#[derive(Debug)]
+pub enum Detour {
+    /// No roads are closed.
+    None,
+    Searching(Task<Result<Vec<EdgeId>, String>>),
+    Found(Vec<EdgeId>),
+    NotFound,
+}
+
 pub fn gui_system(
     graph: Res<DirectedNetworkGraphContainer>,
     road_map: Res<RoadMap>,
     mut egui_context: EguiContexts,
+    closures: Res<RoadClosures>,
     mut state: ResMut<NodeSelectionState>,
     mut event_reader: EventReader<PointClickedEvent>,
 ) {
+    // The clicks close roads instead.
+    if closures.closing {
+        event_reader.clear();
+    }
+
     egui::Window::new("Routing").show(egui_context.ctx_mut(), |ui| {
         ui.label("Routing");
 
@@ -59,8 +88,10 @@ pub fn gui_system(
                     let graph = graph.0.clone();
 
                     let pool = AsyncComputeTaskPool::get();
-                    let task = pool.spawn(async move { find_route(source, target, &graph) });
-                    *state = NodeSelectionState::FindingRoute(task);
+                    let task = pool.spawn(async move {
+                        find_route(source, target, &graph, &EdgeOverlay::new())
+                    });
+                    *state = NodeSelectionState::FindingRoute(source, target, task);
                 }
                 _ => {}
             }
@@ -70,33 +101,117 @@ pub fn gui_system(
             NodeSelectionState::NoRoute => ("No route".to_string(), "".to_string()),
             NodeSelectionState::FindingNode1 => ("Finding node 1".to_string(), "".to_string()),
             NodeSelectionState::FindingNode2(n1) => (format!("Node: {:?}", n1), "".to_string()),
-            NodeSelectionState::FindingRoute(_) => ("Searching route".to_string(), "".to_string()),
-            NodeSelectionState::FoundRoute(e) => (
+            NodeSelectionState::FindingRoute(..) => ("Searching route".to_string(), "".to_string()),
+            NodeSelectionState::FoundRoute(route) => (
                 "Found route".to_string(),
-                format!(
-                    "L: {}",
-                    e.iter()
-                        .map(|eid| road_map.road_length(*graph.edge_data(*eid)))
-                        .sum::<f32>()
-                ),
+                format!("L: {}", route_length(&route.edges, &graph, &road_map)),
             ),
         };
 
         ui.label(n1);
         ui.label(n2);
+
+        if let NodeSelectionState::FoundRoute(route) = state.as_ref() {
+            match &route.detour {
+                Detour::None => {}
+                Detour::Searching(_) => {
+                    ui.label("Searching detour");
+                }
+                Detour::Found(edges) => {
+                    ui.label(format!(
+                        "Detour L: {}",
+                        route_length(edges, &graph, &road_map)
+                    ));
+                }
+                Detour::NotFound => {
+                    ui.label("No detour around the closed roads");
+                }
+            }
+
+            if ui.button("Export GeoJSON").clicked() {
+                let roads = route
+                    .edges
+                    .iter()
+                    .map(|eid| *graph.edge_data(*eid))
+                    .collect::<Vec<_>>();
+                let value = geojson::route_features(&road_map, &roads, Projection::Wgs84);
+                geojson::write_geojson(&value, "data/route.geojson").expect("Could not write");
+            }
+        }
     });
 }
 
+fn route_length(
+    edges: &[EdgeId],
+    network: &DirectedNetworkGraphContainer,
+    road_map: &RoadMap,
+) -> f64 {
+    edges
+        .iter()
+        .map(|eid| road_map.road_length(*network.edge_data(*eid)))
+        .sum::<f64>()
+}
+
 fn waiting_for_task(mut route_state: ResMut<NodeSelectionState>) {
-    if let NodeSelectionState::FindingRoute(task) = route_state.as_mut() {
-        if let Some(task) = future::block_on(future::poll_once(task)) {
-            let state = match task {
-                Ok(a) => NodeSelectionState::FoundRoute(a),
-                Err(_) => NodeSelectionState::NoRoute,
+    match route_state.as_mut() {
+        NodeSelectionState::FindingRoute(source, target, task) => {
+            if let Some(task) = future::block_on(future::poll_once(task)) {
+                let state = match task {
+                    Ok(a) => NodeSelectionState::FoundRoute(FoundRoute {
+                        source: *source,
+                        target: *target,
+                        edges: a,
+                        detour: Detour::None,
+                    }),
+                    Err(_) => NodeSelectionState::NoRoute,
+                };
+                *route_state = state
             };
-            *route_state = state
-        };
+        }
+        NodeSelectionState::FoundRoute(route) => {
+            if let Detour::Searching(task) = &mut route.detour {
+                if let Some(task) = future::block_on(future::poll_once(task)) {
+                    route.detour = match task {
+                        Ok(a) => Detour::Found(a),
+                        Err(_) => Detour::NotFound,
+                    };
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Search the detour of a new route, or of the current route when the closures changed.
+fn detour_system(
+    graph: Res<DirectedNetworkGraphContainer>,
+    closures: Res<RoadClosures>,
+    mut route_state: ResMut<NodeSelectionState>,
+    mut event_reader: EventReader<RoadClosuresChanged>,
+) {
+    let changed = event_reader.read().count() > 0;
+
+    let NodeSelectionState::FoundRoute(route) = route_state.as_mut() else {
+        return;
+    };
+    let new_route = matches!(route.detour, Detour::None) && !closures.overlay.is_empty();
+    if !changed && !new_route {
+        return;
     }
+
+    if closures.overlay.is_empty() {
+        route.detour = Detour::None;
+        return;
+    }
+
+    let (source, target) = (route.source, route.target);
+    let graph = graph.0.clone();
+    let overlay = closures.overlay.clone();
+
+    // A previous search is dropped, which cancels it.
+    let pool = AsyncComputeTaskPool::get();
+    let task = pool.spawn(async move { find_route(source, target, &graph, &overlay) });
+    route.detour = Detour::Searching(task);
 }
 
 fn route_draw(
@@ -107,13 +222,24 @@ fn route_draw(
     if let NodeSelectionState::FoundRoute(route) = route_state.as_ref() {
         // Collect all roadIds.
         let l = route
+            .edges
             .iter()
             .map(|e| network.edge_data(*e))
             .collect::<HashSet<_>>();
+        let detour = match &route.detour {
+            Detour::Found(edges) => edges
+                .iter()
+                .map(|e| network.edge_data(*e))
+                .filter(|x| !l.contains(x))
+                .collect::<HashSet<_>>(),
+            _ => HashSet::new(),
+        };
 
         query.iter_mut().for_each(|mut a| {
             if l.contains(&a.id) {
                 a.selected = WorldEntitySelectionType::Route;
+            } else if detour.contains(&a.id) {
+                a.selected = WorldEntitySelectionType::Detour;
             }
         });
     }
@@ -123,6 +249,7 @@ fn find_route<D>(
     source: NodeId,
     target: NodeId,
     network: &DirectedNetworkGraph<D>,
+    overlay: &EdgeOverlay,
 ) -> Result<Vec<EdgeId>, String>
 where
     D: NetworkData,
@@ -132,7 +259,7 @@ where
     // let mut test = Vec::new();
 
     let mut heap = BinaryHeap::new();
-    explore_node(network, source, &mut heap, 0f32);
+    explore_node(network, overlay, source, &mut heap, 0f32);
 
     while let Some((_, F32(old_distance), current, parent)) = heap.pop() {
         evaluated += 1;
@@ -158,7 +285,7 @@ where
             return Ok(path.into_iter().map(|(_, edge)| edge).collect());
         }
 
-        explore_node(network, current, &mut heap, old_distance);
+        explore_node(network, overlay, current, &mut heap, old_distance);
     }
     println!("Not found but Evaluated: {}", evaluated);
 
@@ -167,13 +294,17 @@ where
 
 fn explore_node<D: NetworkData>(
     network: &DirectedNetworkGraph<D>,
+    overlay: &EdgeOverlay,
     source: NodeId,
     heap: &mut BinaryHeap<(Reverse<F32>, F32, NodeId, (NodeId, EdgeId))>,
     old_distance: f32,
 ) {
     for (id, edge) in network.out_edges(source) {
+        let Some(weight) = overlay.weight(id, edge) else {
+            continue;
+        };
         let target = edge.target();
-        let distance = old_distance + edge.distance();
+        let distance = old_distance + weight;
 
         heap.push((Reverse(F32(distance)), F32(distance), target, (source, id)));
     }
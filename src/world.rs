@@ -3,13 +3,13 @@ use crate::{
     nwb::{self},
     ui::{DirectedNetworkGraphContainer, PreProcess},
 };
-use bevy::prelude::*;
+use bevy::{math::DVec2, prelude::*};
 use bevy_polyline::prelude::{Polyline, PolylineBundle, PolylineMaterial};
-use bevy_shapefile::{RoadId, RoadMap, RoadSection, AABB};
+use bevy_shapefile::{JunctionRepairConfig, RoadId, RoadMap, RoadSection, AABB};
 use graph::DirectedNetworkGraph;
 use std::{
     collections::{HashMap, HashSet},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 pub struct WorldPlugin {
@@ -24,19 +24,48 @@ pub struct LoadedMaterials {
     outgoing_material: Handle<PolylineMaterial>,
     incoming_material: Handle<PolylineMaterial>,
     route_material: Handle<PolylineMaterial>,
+    detour_material: Handle<PolylineMaterial>,
+    closed_material: Handle<PolylineMaterial>,
+    filtered_material: Handle<PolylineMaterial>,
+    component_material: Handle<PolylineMaterial>,
 }
 
 #[derive(Debug, Clone, Resource)]
 pub struct WorldConfig {
-    pub database_path: String,
     pub road_map_path: String,
     pub shapefile_path: String,
+    pub speed_limit_path: String,
     pub directed_graph_path: String,
+    /// Where the changes of the junction repair are written to.
+    pub junction_repair_report_path: String,
+
+    pub junction_repair: JunctionRepairConfig,
+    /// Only keep the largest strongly connected component of the graph,
+    /// so a route exists between every pair of nodes.
+    /// The pruned graph is cached next to `directed_graph_path`, see [`WorldConfig::graph_path`].
+    pub keep_largest_component: bool,
 
     pub selected_colour: Color,
     pub normal_colour: Color,
 }
 
+impl WorldConfig {
+    /// The cached road map, see [`crate::versioned_path`].
+    pub fn road_map_file(&self) -> PathBuf {
+        crate::versioned_path(&self.road_map_path)
+    }
+
+    /// The cached graph, the graph with only the largest component gets its own file.
+    pub fn graph_path(&self) -> PathBuf {
+        let path = Path::new(&self.directed_graph_path);
+        if self.keep_largest_component {
+            crate::versioned_path(path.with_extension("largest.graph"))
+        } else {
+            crate::versioned_path(path)
+        }
+    }
+}
+
 #[derive(Debug, Clone, Component)]
 pub struct WorldEntity {
     pub id: RoadId,
@@ -51,16 +80,42 @@ pub enum WorldEntitySelectionType {
     Outgoing,
     Incoming,
     Route,
+    /// The route around the closed roads, where it differs from the route.
+    Detour,
+    Closed,
+    Filtered,
+    /// Part of a strongly connected component other than the largest one.
+    SmallComponent,
+}
+
+/// Distance in metres the camera may move away from the render origin before the origin is moved.
+const RECENTRE_DISTANCE: f32 = 10_000.0;
+
+/// The Rijksdriehoek position that is drawn at the origin of the scene.
+///
+/// Road geometry is kept in full precision, only the vertices sent to the GPU are f32.
+/// Making them relative to a nearby origin keeps them precise at every location in the country.
+#[derive(Debug, Clone, Copy, Default, Resource)]
+pub struct RenderOrigin(pub DVec2);
+
+impl RenderOrigin {
+    pub fn to_render(&self, point: DVec2) -> Vec2 {
+        (point - self.0).as_vec2()
+    }
+
+    pub fn to_world(&self, point: Vec2) -> DVec2 {
+        self.0 + point.as_dvec2()
+    }
 }
 
 #[derive(Debug, Default, Resource)]
 pub struct WorldTracker {
-    pub map: HashMap<RoadId, Entity>,
+    pub map: HashMap<RoadId, Vec<Entity>>,
 }
 
 impl WorldTracker {
-    pub fn track(&mut self, id: RoadId, entity: Entity) {
-        self.map.insert(id, entity);
+    pub fn track(&mut self, id: RoadId, entities: Vec<Entity>) {
+        self.map.insert(id, entities);
     }
     pub fn remove(&mut self, id: RoadId) {
         self.map.remove(&id);
@@ -77,7 +132,7 @@ impl Plugin for WorldPlugin {
             .add_systems(Update, colour_system) // Used for drawing the layers
             // .add_systems(Update, test_algorithm)
             // .add_systems(Update, help)
-            .add_systems(Update, visible_entities);
+            .add_systems(Update, (recentre_origin, visible_entities).chain());
     }
 }
 
@@ -116,12 +171,36 @@ fn init_materials(
         color: Color::PINK,
         ..Default::default()
     });
+    let detour_material = polyline_materials.add(PolylineMaterial {
+        width: 9.0,
+        color: Color::GREEN,
+        ..Default::default()
+    });
+    let closed_material = polyline_materials.add(PolylineMaterial {
+        width: 6.0,
+        color: Color::MAROON,
+        ..Default::default()
+    });
+    let filtered_material = polyline_materials.add(PolylineMaterial {
+        width: 3.0,
+        color: Color::CYAN,
+        ..Default::default()
+    });
+    let component_material = polyline_materials.add(PolylineMaterial {
+        width: 3.0,
+        color: Color::ORANGE,
+        ..Default::default()
+    });
     commands.insert_resource(LoadedMaterials {
         normal_material,
         selected_material,
         incoming_material,
         outgoing_material,
         route_material,
+        detour_material,
+        closed_material,
+        filtered_material,
+        component_material,
     });
 }
 
@@ -159,14 +238,31 @@ fn init_road_map(config: Res<WorldConfig>, mut commands: Commands) {
 }
 
 fn load_road_map(config: &Res<WorldConfig>) -> RoadMap {
-    let road_map_path = Path::new(&config.road_map_path);
+    let road_map_path = config.road_map_file();
 
-    if let Ok(road_map) = crate::read_file(road_map_path) {
+    if let Ok(road_map) = crate::read_file(&road_map_path) {
         road_map
     } else {
         println!("File {:?} not found, creating...", road_map_path);
-        let road_map = bevy_shapefile::from_shapefile(&config.shapefile_path)
+        let mut road_map = bevy_shapefile::from_shapefile(&config.shapefile_path)
             .expect("Could not read shapefile");
+        // The speed limits are optional, without them the roads have no `speed_limit`.
+        if let Err(error) = road_map.load_speed_limits(&config.speed_limit_path) {
+            println!(
+                "Could not read speed limits {:?}: {:?}",
+                config.speed_limit_path, error
+            );
+        }
+
+        // Repair the topology before the graph is built from the road map.
+        let report = road_map.repair_junctions(&config.junction_repair);
+        println!(
+            "Junction repair: merged {} junctions, split {} roads",
+            report.merged.len(),
+            report.split.len()
+        );
+        std::fs::write(&config.junction_repair_report_path, report.to_string())
+            .expect("Could not write junction repair report");
 
         crate::write_file(&road_map, road_map_path).expect("Could not write road_map");
 
@@ -178,14 +274,22 @@ fn load_graph(
     config: Res<WorldConfig>,
     road_map: &RoadMap,
 ) -> DirectedNetworkGraph<nwb::NWBNetworkData> {
-    let network_path = Path::new(&config.directed_graph_path);
+    let network_path = config.graph_path();
 
-    if let Ok(network) = crate::read_file(network_path) {
+    if let Ok(network) = crate::read_file::<DirectedNetworkGraph<_>, _>(&network_path) {
+        network.debug_validate();
         network
     } else {
         println!("File {:?} not found, creating...", network_path);
-        let network = nwb::preprocess_roadmap(road_map, &config.database_path);
-        crate::write_file(&network, network_path).expect("Could not write network");
+        let mut network = nwb::preprocess_roadmap(road_map);
+
+        let components = network.strongly_connected_components();
+        println!("Strongly connected components: {}", components);
+        if config.keep_largest_component {
+            network = network.largest_component();
+        }
+
+        crate::write_file(&network, &network_path).expect("Could not write network");
         network
     }
 }
@@ -202,10 +306,35 @@ fn mark_on_changed_preprocess(
     }
 }
 
+/// Move the render origin to the camera when it has travelled far away from it.
+/// All spawned roads are despawned, `visible_entities` spawns them again relative to the new origin.
+fn recentre_origin(
+    mut commands: Commands,
+    mut origin: ResMut<RenderOrigin>,
+    mut tracker: ResMut<WorldTracker>,
+    mut q_camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    if let Ok(mut transform) = q_camera.get_single_mut() {
+        let offset = transform.translation.truncate();
+        if offset.length() < RECENTRE_DISTANCE {
+            return;
+        }
+
+        origin.0 = origin.to_world(offset);
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+
+        for entity in tracker.map.drain().flat_map(|(_, entities)| entities) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
 fn visible_entities(
     mut commands: Commands,
     materials: Res<LoadedMaterials>,
     road_map: Res<RoadMap>,
+    origin: Res<RenderOrigin>,
     mut tracker: ResMut<WorldTracker>,
     mut polylines: ResMut<Assets<Polyline>>,
     q_camera: Query<
@@ -217,8 +346,8 @@ fn visible_entities(
     >,
 ) {
     if let Ok((camera, transform)) = q_camera.get_single() {
-        let min = convert(Vec2::new(-1.0, -1.0), transform, camera);
-        let max = convert(Vec2::new(1.0, 1.0), transform, camera);
+        let min = origin.to_world(convert(Vec2::new(-1.0, -1.0), transform, camera));
+        let max = origin.to_world(convert(Vec2::new(1.0, 1.0), transform, camera));
 
         let visible = road_map
             .road_spatial
@@ -239,17 +368,25 @@ fn visible_entities(
         );
 
         for id in removed {
-            let entity = tracker.map.get(&id).unwrap();
-            commands.entity(*entity).despawn();
+            for entity in tracker.map.get(&id).unwrap() {
+                commands.entity(*entity).despawn();
+            }
 
             tracker.remove(id);
         }
 
         for id in added {
             let section = road_map.roads.get(&id).unwrap();
-            let entity = spawn_figure(&mut commands, id, section, &mut polylines, &materials);
-
-            tracker.track(id, entity);
+            let entities = spawn_figure(
+                &mut commands,
+                id,
+                section,
+                &origin,
+                &mut polylines,
+                &materials,
+            );
+
+            tracker.track(id, entities);
         }
     }
 }
@@ -270,6 +407,12 @@ fn colour_system(
             WorldEntitySelectionType::Outgoing => loaded_materials.outgoing_material.clone_weak(),
             WorldEntitySelectionType::Incoming => loaded_materials.incoming_material.clone_weak(),
             WorldEntitySelectionType::Route => loaded_materials.route_material.clone_weak(),
+            WorldEntitySelectionType::Detour => loaded_materials.detour_material.clone_weak(),
+            WorldEntitySelectionType::Closed => loaded_materials.closed_material.clone_weak(),
+            WorldEntitySelectionType::Filtered => loaded_materials.filtered_material.clone_weak(),
+            WorldEntitySelectionType::SmallComponent => {
+                loaded_materials.component_material.clone_weak()
+            }
         };
         *mode = material;
         we.selected = WorldEntitySelectionType::NotSelected;
@@ -283,28 +426,35 @@ pub fn convert(pos: Vec2, transform: &GlobalTransform, camera: &Camera) -> Vec2
         .truncate()
 }
 
+/// Spawn the polylines of a road section, one entity for every part of the section.
 fn spawn_figure(
     commands: &mut Commands,
     id: RoadId,
     section: &RoadSection,
+    origin: &RenderOrigin,
     polylines: &mut Assets<Polyline>,
     materials: &LoadedMaterials,
-) -> Entity {
-    commands
-        .spawn(PolylineBundle {
-            polyline: polylines.add(Polyline {
-                vertices: section
-                    .points
-                    .iter()
-                    .map(|c| Vec3::new(c.x, c.y, 0.0))
-                    .collect(),
-            }),
-            material: materials.normal_material.clone_weak(),
-            ..Default::default()
-        })
-        .insert(WorldEntity {
-            id,
-            selected: WorldEntitySelectionType::NotSelected,
+) -> Vec<Entity> {
+    section
+        .parts
+        .iter()
+        .map(|part| {
+            commands
+                .spawn(PolylineBundle {
+                    polyline: polylines.add(Polyline {
+                        vertices: part
+                            .iter()
+                            .map(|c| origin.to_render(*c).extend(0.0))
+                            .collect(),
+                    }),
+                    material: materials.normal_material.clone_weak(),
+                    ..Default::default()
+                })
+                .insert(WorldEntity {
+                    id,
+                    selected: WorldEntitySelectionType::NotSelected,
+                })
+                .id()
         })
-        .id()
+        .collect()
 }